@@ -0,0 +1,708 @@
+use std::ops::Deref;
+
+use crate::parser::{parse_identifier, parse_keyword, parse_type};
+use crate::span::Span;
+use crate::token_stream::TokenStream;
+use crate::tokeniser::tokenise;
+use crate::tokens::{Keyword, Symbol, Token, TokenType};
+
+// A typed parse tree of a Jack program. The grammar functions below mirror the
+// compile_* functions in parser.rs, but build a tree instead of writing XML, so
+// that backends and tools can walk the program. Names and declarations carry
+// the span they were parsed from, for tools that point back into the source.
+
+// An identifier together with where it appears.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Name {
+    pub text: String,
+    pub span: Span,
+}
+
+impl Name {
+    pub fn new(text: &str, span: Span) -> Self {
+        Self {
+            text: text.to_string(),
+            span,
+        }
+    }
+}
+
+impl Deref for Name {
+    type Target = String;
+
+    fn deref(&self) -> &String {
+        &self.text
+    }
+}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+impl std::fmt::Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Class {
+    pub name: Name,
+    pub class_vars: Vec<ClassVarDec>,
+    pub subroutines: Vec<Subroutine>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassVarKind {
+    Static,
+    Field,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassVarDec {
+    pub kind: ClassVarKind,
+    pub var_type: Type,
+    pub names: Vec<Name>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Char,
+    Boolean,
+    Class(Name),
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Char => write!(f, "char"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Class(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubroutineKind {
+    Constructor,
+    Function,
+    Method,
+}
+
+impl std::fmt::Display for SubroutineKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubroutineKind::Constructor => write!(f, "constructor"),
+            SubroutineKind::Function => write!(f, "function"),
+            SubroutineKind::Method => write!(f, "method"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub kind: SubroutineKind,
+    // None for void subroutines
+    pub return_type: Option<Type>,
+    pub name: Name,
+    pub parameters: Vec<Parameter>,
+    pub locals: Vec<VarDec>,
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parameter {
+    pub var_type: Type,
+    pub name: Name,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarDec {
+    pub var_type: Type,
+    pub names: Vec<Name>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatementKind {
+    Let {
+        name: Name,
+        index: Option<Expression>,
+        value: Expression,
+    },
+    If {
+        condition: Expression,
+        then_branch: Vec<Statement>,
+        else_branch: Option<Vec<Statement>>,
+    },
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+    },
+    Do(SubroutineCall),
+    Return(Option<Expression>),
+}
+
+// term (op term)*, evaluated left to right as Jack has no operator precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    pub term: Term,
+    pub rest: Vec<(BinaryOp, Term)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    IntegerConstant(u16),
+    StringConstant(String),
+    KeywordConstant(KeywordConstant),
+    Variable(Name),
+    ArrayEntry(Name, Box<Expression>),
+    Call(SubroutineCall),
+    Parenthesised(Box<Expression>),
+    Unary(UnaryOp, Box<Term>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordConstant {
+    True,
+    False,
+    Null,
+    This,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    And,
+    Or,
+    LessThan,
+    GreaterThan,
+    Equals,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOp {
+    fn from_symbol(symbol: &Symbol) -> Option<BinaryOp> {
+        match symbol {
+            Symbol::Plus => Some(BinaryOp::Add),
+            Symbol::Minus => Some(BinaryOp::Subtract),
+            Symbol::Times => Some(BinaryOp::Multiply),
+            Symbol::Divide => Some(BinaryOp::Divide),
+            Symbol::And => Some(BinaryOp::And),
+            Symbol::Or => Some(BinaryOp::Or),
+            Symbol::LessThan => Some(BinaryOp::LessThan),
+            Symbol::GreaterThan => Some(BinaryOp::GreaterThan),
+            Symbol::Equals => Some(BinaryOp::Equals),
+            Symbol::ShiftLeft => Some(BinaryOp::ShiftLeft),
+            Symbol::ShiftRight => Some(BinaryOp::ShiftRight),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl UnaryOp {
+    fn from_symbol(symbol: &Symbol) -> Option<UnaryOp> {
+        match symbol {
+            Symbol::Minus => Some(UnaryOp::Negate),
+            Symbol::Not => Some(UnaryOp::Not),
+            Symbol::ShiftLeft => Some(UnaryOp::ShiftLeft),
+            Symbol::ShiftRight => Some(UnaryOp::ShiftRight),
+            _ => None,
+        }
+    }
+}
+
+// subroutineName(expressionList) when receiver is None, otherwise
+// (className | varName).subroutineName(expressionList)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubroutineCall {
+    pub receiver: Option<Name>,
+    pub name: Name,
+    pub arguments: Vec<Expression>,
+}
+
+// Parses every class in the given source.
+pub fn parse_classes(input_data: String) -> Result<Vec<Class>, String> {
+    let tokens = tokenise(input_data)?;
+    parse_tokens(&tokens).map_err(|(message, _)| message)
+}
+
+// Parses every class in the tokens, reporting the span of the token where
+// parsing failed along with the error.
+pub fn parse_tokens(tokens: &[Token]) -> Result<Vec<Class>, (String, Span)> {
+    let mut stream = TokenStream::new(tokens);
+    let mut classes = vec![];
+    while let Some(token) = stream.peek() {
+        let class = match token.token {
+            TokenType::Keyword(Keyword::Class) => parse_class(&mut stream),
+            _ => Err("Compilation call to something not the class at the top level".to_string()),
+        };
+        match class {
+            Ok(class) => classes.push(class),
+            Err(message) => return Err((message, stream.current_span())),
+        }
+    }
+    Ok(classes)
+}
+
+// Parses a complete class.
+pub fn parse_class(stream: &mut TokenStream) -> Result<Class, String> {
+    let start = stream.position();
+    stream.expect(&TokenType::Keyword(Keyword::Class))?;
+    let name = parse_name(stream)?;
+    stream.expect(&TokenType::Symbol(Symbol::BracketCurlyLeft))?;
+
+    let mut class_vars = vec![];
+    while matches!(
+        stream.peek(),
+        Some(token) if matches!(token.token, TokenType::Keyword(Keyword::Static | Keyword::Field))
+    ) {
+        class_vars.push(parse_class_var_dec(stream)?);
+    }
+
+    let mut subroutines = vec![];
+    while matches!(
+        stream.peek(),
+        Some(token) if matches!(
+            token.token,
+            TokenType::Keyword(Keyword::Constructor | Keyword::Function | Keyword::Method)
+        )
+    ) {
+        subroutines.push(parse_subroutine(stream)?);
+    }
+
+    stream.expect(&TokenType::Symbol(Symbol::BracketCurlyRight))?;
+    Ok(Class {
+        name,
+        class_vars,
+        subroutines,
+        span: stream.span_since(start),
+    })
+}
+
+// Parses a static declaration or a field declaration.
+fn parse_class_var_dec(stream: &mut TokenStream) -> Result<ClassVarDec, String> {
+    let start = stream.position();
+    let kind = match parse_keyword(stream, &[Keyword::Static, Keyword::Field])? {
+        TokenType::Keyword(Keyword::Static) => ClassVarKind::Static,
+        _ => ClassVarKind::Field,
+    };
+    let var_type = parse_var_type(stream)?;
+    let names = parse_var_names(stream)?;
+    Ok(ClassVarDec {
+        kind,
+        var_type,
+        names,
+        span: stream.span_since(start),
+    })
+}
+
+// Parses a complete method, function, or constructor.
+fn parse_subroutine(stream: &mut TokenStream) -> Result<Subroutine, String> {
+    let start = stream.position();
+    let kind = match parse_keyword(
+        stream,
+        &[Keyword::Constructor, Keyword::Function, Keyword::Method],
+    )? {
+        TokenType::Keyword(Keyword::Constructor) => SubroutineKind::Constructor,
+        TokenType::Keyword(Keyword::Function) => SubroutineKind::Function,
+        _ => SubroutineKind::Method,
+    };
+    let type_span = stream.current_span();
+    let return_type = match parse_type(stream, true)? {
+        TokenType::Keyword(Keyword::Void) => None,
+        other => Some(to_type(other, type_span)),
+    };
+    let name = parse_name(stream)?;
+
+    stream.expect(&TokenType::Symbol(Symbol::BracketLeft))?;
+    let parameters = parse_parameter_list(stream)?;
+    stream.expect(&TokenType::Symbol(Symbol::BracketRight))?;
+
+    stream.expect(&TokenType::Symbol(Symbol::BracketCurlyLeft))?;
+    let mut locals = vec![];
+    while matches!(stream.peek(), Some(token) if token.token == TokenType::Keyword(Keyword::Var)) {
+        locals.push(parse_var_dec(stream)?);
+    }
+    let statements = parse_statements(stream)?;
+    stream.expect(&TokenType::Symbol(Symbol::BracketCurlyRight))?;
+
+    Ok(Subroutine {
+        kind,
+        return_type,
+        name,
+        parameters,
+        locals,
+        statements,
+        span: stream.span_since(start),
+    })
+}
+
+// Parses a (possibly empty) parameter list, not including the enclosing "()".
+fn parse_parameter_list(stream: &mut TokenStream) -> Result<Vec<Parameter>, String> {
+    let mut parameters = vec![];
+    if matches!(stream.peek(), Some(token) if token.token == TokenType::Symbol(Symbol::BracketRight))
+    {
+        return Ok(parameters);
+    }
+    loop {
+        let var_type = parse_var_type(stream)?;
+        let name = parse_name(stream)?;
+        parameters.push(Parameter { var_type, name });
+        if !skip_symbol(stream, Symbol::Comma) {
+            break;
+        }
+    }
+    Ok(parameters)
+}
+
+// Parses a var declaration.
+pub fn parse_var_dec(stream: &mut TokenStream) -> Result<VarDec, String> {
+    let start = stream.position();
+    stream.expect(&TokenType::Keyword(Keyword::Var))?;
+    let var_type = parse_var_type(stream)?;
+    let names = parse_var_names(stream)?;
+    Ok(VarDec {
+        var_type,
+        names,
+        span: stream.span_since(start),
+    })
+}
+
+// Parses "varName (, varName)* ;"
+fn parse_var_names(stream: &mut TokenStream) -> Result<Vec<Name>, String> {
+    let mut names = vec![parse_name(stream)?];
+    while skip_symbol(stream, Symbol::Comma) {
+        names.push(parse_name(stream)?);
+    }
+    stream.expect(&TokenType::Symbol(Symbol::SemiColon))?;
+    Ok(names)
+}
+
+// Parses a sequence of statements, not including the enclosing "}".
+// Unlike compile_statements, statements following a return are kept so that
+// later passes can report them as unreachable.
+pub fn parse_statements(stream: &mut TokenStream) -> Result<Vec<Statement>, String> {
+    let mut statements = vec![];
+    while let Some(token) = stream.peek() {
+        let start = stream.position();
+        let kind = match token.token {
+            TokenType::Keyword(Keyword::Let) => parse_let(stream)?,
+            TokenType::Keyword(Keyword::If) => parse_if(stream)?,
+            TokenType::Keyword(Keyword::While) => parse_while(stream)?,
+            TokenType::Keyword(Keyword::Do) => parse_do(stream)?,
+            TokenType::Keyword(Keyword::Return) => parse_return(stream)?,
+            _ => break,
+        };
+        statements.push(Statement {
+            kind,
+            span: stream.span_since(start),
+        });
+    }
+    Ok(statements)
+}
+
+fn parse_let(stream: &mut TokenStream) -> Result<StatementKind, String> {
+    stream.expect(&TokenType::Keyword(Keyword::Let))?;
+    let name = parse_name(stream)?;
+    let index = if skip_symbol(stream, Symbol::BracketSquareLeft) {
+        let index = parse_expression(stream)?;
+        stream.expect(&TokenType::Symbol(Symbol::BracketSquareRight))?;
+        Some(index)
+    } else {
+        None
+    };
+    stream.expect(&TokenType::Symbol(Symbol::Equals))?;
+    let value = parse_expression(stream)?;
+    stream.expect(&TokenType::Symbol(Symbol::SemiColon))?;
+    Ok(StatementKind::Let { name, index, value })
+}
+
+fn parse_if(stream: &mut TokenStream) -> Result<StatementKind, String> {
+    stream.expect(&TokenType::Keyword(Keyword::If))?;
+    let condition = parse_condition(stream)?;
+    let then_branch = parse_block(stream)?;
+    let else_branch =
+        if matches!(stream.peek(), Some(token) if token.token == TokenType::Keyword(Keyword::Else))
+        {
+            stream.advance();
+            Some(parse_block(stream)?)
+        } else {
+            None
+        };
+    Ok(StatementKind::If {
+        condition,
+        then_branch,
+        else_branch,
+    })
+}
+
+fn parse_while(stream: &mut TokenStream) -> Result<StatementKind, String> {
+    stream.expect(&TokenType::Keyword(Keyword::While))?;
+    let condition = parse_condition(stream)?;
+    let body = parse_block(stream)?;
+    Ok(StatementKind::While { condition, body })
+}
+
+fn parse_do(stream: &mut TokenStream) -> Result<StatementKind, String> {
+    stream.expect(&TokenType::Keyword(Keyword::Do))?;
+    let call = parse_subroutine_call(stream)?;
+    stream.expect(&TokenType::Symbol(Symbol::SemiColon))?;
+    Ok(StatementKind::Do(call))
+}
+
+fn parse_return(stream: &mut TokenStream) -> Result<StatementKind, String> {
+    stream.expect(&TokenType::Keyword(Keyword::Return))?;
+    let value = match stream.peek() {
+        Some(token) if token.token == TokenType::Symbol(Symbol::SemiColon) => None,
+        Some(_) => Some(parse_expression(stream)?),
+        None => return Err("Unexpected end of tokens when compiling return".to_string()),
+    };
+    stream.expect(&TokenType::Symbol(Symbol::SemiColon))?;
+    Ok(StatementKind::Return(value))
+}
+
+// "(" expression ")"
+fn parse_condition(stream: &mut TokenStream) -> Result<Expression, String> {
+    stream.expect(&TokenType::Symbol(Symbol::BracketLeft))?;
+    let condition = parse_expression(stream)?;
+    stream.expect(&TokenType::Symbol(Symbol::BracketRight))?;
+    Ok(condition)
+}
+
+// "{" statements "}"
+fn parse_block(stream: &mut TokenStream) -> Result<Vec<Statement>, String> {
+    stream.expect(&TokenType::Symbol(Symbol::BracketCurlyLeft))?;
+    let statements = parse_statements(stream)?;
+    stream.expect(&TokenType::Symbol(Symbol::BracketCurlyRight))?;
+    Ok(statements)
+}
+
+// Parses "subroutineName(expressionList)" or
+// "className|varName.subroutineName(expressionList)".
+fn parse_subroutine_call(stream: &mut TokenStream) -> Result<SubroutineCall, String> {
+    let first = parse_name(stream)?;
+    let (receiver, name) = if skip_symbol(stream, Symbol::Period) {
+        let name = parse_name(stream)?;
+        (Some(first), name)
+    } else {
+        (None, first)
+    };
+    if let Err(err) = stream.expect(&TokenType::Symbol(Symbol::BracketLeft)) {
+        return Err(format!("Error while parsing subroutine call: {}", err));
+    }
+    let arguments = parse_expression_list(stream)?;
+    if let Err(err) = stream.expect(&TokenType::Symbol(Symbol::BracketRight)) {
+        return Err(format!("Error while parsing subroutine call: {}", err));
+    }
+    Ok(SubroutineCall {
+        receiver,
+        name,
+        arguments,
+    })
+}
+
+// Parses an expression.
+pub fn parse_expression(stream: &mut TokenStream) -> Result<Expression, String> {
+    let term = parse_term(stream)?;
+    let mut rest = vec![];
+    while let Some(op) = stream.peek().and_then(|token| match &token.token {
+        TokenType::Symbol(symbol) => BinaryOp::from_symbol(symbol),
+        _ => None,
+    }) {
+        stream.advance();
+        rest.push((op, parse_term(stream)?));
+    }
+    Ok(Expression { term, rest })
+}
+
+// Parses a term, looking at the token after an identifier to tell a variable,
+// an array entry and a subroutine call apart.
+fn parse_term(stream: &mut TokenStream) -> Result<Term, String> {
+    let is_call = matches!(stream.peek(), Some(token) if matches!(token.token, TokenType::Identifier(_)))
+        && matches!(
+            stream.peek_nth(1),
+            Some(token) if matches!(token.token, TokenType::Symbol(Symbol::BracketLeft | Symbol::Period))
+        );
+    if is_call {
+        return Ok(Term::Call(parse_subroutine_call(stream)?));
+    }
+    let start = stream.checkpoint();
+    let token = match stream.advance() {
+        Some(token) => token,
+        None => return Err("Unexpected end of tokens when compiling term".to_string()),
+    };
+    let term = match &token.token {
+        TokenType::IntegerConstant(value) => Term::IntegerConstant(*value),
+        TokenType::StringConstant(value) => Term::StringConstant(value.clone()),
+        TokenType::Keyword(Keyword::True) => Term::KeywordConstant(KeywordConstant::True),
+        TokenType::Keyword(Keyword::False) => Term::KeywordConstant(KeywordConstant::False),
+        TokenType::Keyword(Keyword::Null) => Term::KeywordConstant(KeywordConstant::Null),
+        TokenType::Keyword(Keyword::This) => Term::KeywordConstant(KeywordConstant::This),
+        TokenType::Symbol(Symbol::BracketLeft) => {
+            let inner = parse_expression(stream)?;
+            stream.expect(&TokenType::Symbol(Symbol::BracketRight))?;
+            Term::Parenthesised(Box::new(inner))
+        }
+        TokenType::Symbol(symbol) if UnaryOp::from_symbol(symbol).is_some() => {
+            let op = UnaryOp::from_symbol(symbol).unwrap();
+            Term::Unary(op, Box::new(parse_term(stream)?))
+        }
+        TokenType::Identifier(identifier) => {
+            let name = Name::new(&identifier.identifier, token.span);
+            if skip_symbol(stream, Symbol::BracketSquareLeft) {
+                let index = parse_expression(stream)?;
+                stream.expect(&TokenType::Symbol(Symbol::BracketSquareRight))?;
+                Term::ArrayEntry(name, Box::new(index))
+            } else {
+                Term::Variable(name)
+            }
+        }
+        _ => {
+            // Report the error at the token that does not start a term
+            stream.rewind(start);
+            return Err(format!("Unexpected token {:?} when compiling term", token.token));
+        }
+    };
+    Ok(term)
+}
+
+// Parses a (possibly empty) comma-separated list of expressions.
+fn parse_expression_list(stream: &mut TokenStream) -> Result<Vec<Expression>, String> {
+    let mut expressions = vec![];
+    match stream.peek() {
+        Some(token) if token.token == TokenType::Symbol(Symbol::BracketRight) => {}
+        Some(_) => {
+            expressions.push(parse_expression(stream)?);
+            while skip_symbol(stream, Symbol::Comma) {
+                expressions.push(parse_expression(stream)?);
+            }
+        }
+        None => {
+            return Err("Unexpected end of tokens when compiling expression list".to_string())
+        }
+    }
+    Ok(expressions)
+}
+
+// Consumes the given symbol if it is the next token.
+fn skip_symbol(stream: &mut TokenStream, symbol: Symbol) -> bool {
+    if matches!(stream.peek(), Some(token) if token.token == TokenType::Symbol(symbol.clone())) {
+        stream.advance();
+        true
+    } else {
+        false
+    }
+}
+
+fn parse_name(stream: &mut TokenStream) -> Result<Name, String> {
+    let span = stream.current_span();
+    let text = identifier_name(parse_identifier(stream)?);
+    Ok(Name { text, span })
+}
+
+fn parse_var_type(stream: &mut TokenStream) -> Result<Type, String> {
+    let span = stream.current_span();
+    Ok(to_type(parse_type(stream, false)?, span))
+}
+
+fn identifier_name(token: TokenType) -> String {
+    match token {
+        TokenType::Identifier(identifier) => identifier.identifier,
+        other => other.to_string(),
+    }
+}
+
+fn to_type(token: TokenType, span: Span) -> Type {
+    match token {
+        TokenType::Keyword(Keyword::Int) => Type::Int,
+        TokenType::Keyword(Keyword::Char) => Type::Char,
+        TokenType::Keyword(Keyword::Boolean) => Type::Boolean,
+        other => Type::Class(Name::new(&identifier_name(other), span)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expression_is_left_to_right() {
+        let tokens = tokenise(String::from("1 + 2 * -x")).unwrap();
+        let mut stream = TokenStream::new(&tokens);
+        let expression = parse_expression(&mut stream).unwrap();
+        assert_eq!(expression.term, Term::IntegerConstant(1));
+        assert_eq!(
+            expression.rest,
+            vec![
+                (BinaryOp::Add, Term::IntegerConstant(2)),
+                (
+                    BinaryOp::Multiply,
+                    Term::Unary(
+                        UnaryOp::Negate,
+                        Box::new(Term::Variable(Name::new("x", Span::new(9, 10))))
+                    )
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_class() {
+        let classes = parse_classes(String::from(
+            "class Test {
+field int x, y;
+method int get(int i) { var Array a; let a[i] = x; return a[i]; do Output.println(); }
+}",
+        ))
+        .unwrap();
+        assert_eq!(classes.len(), 1);
+        let class = &classes[0];
+        assert_eq!(class.name, "Test");
+        assert_eq!(class.class_vars[0].names, vec!["x", "y"]);
+        let get = &class.subroutines[0];
+        assert_eq!(get.kind, SubroutineKind::Method);
+        assert_eq!(get.return_type, Some(Type::Int));
+        assert_eq!(get.parameters[0].name, "i");
+        assert_eq!(get.locals[0].var_type.to_string(), "Array");
+        assert_eq!(get.name.span, Span::new(40, 43));
+        // Statements after return are kept
+        assert_eq!(get.statements.len(), 3);
+        assert!(matches!(
+            &get.statements[2].kind,
+            StatementKind::Do(SubroutineCall { receiver: Some(receiver), name, arguments })
+                if receiver == "Output" && name == "println" && arguments.is_empty()
+        ));
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+
+use crate::ast::{
+    BinaryOp, Class, ClassVarKind, Expression, KeywordConstant, Statement, StatementKind,
+    Subroutine, SubroutineCall, SubroutineKind, Term, Type, UnaryOp,
+};
+
+// Translates parsed Jack classes into a single portable C99 translation unit.
+//
+// Every Jack value is a 16-bit jack_word and all arithmetic goes through the
+// wrapping helpers in c_runtime.c. Objects are blocks on the runtime's
+// simulated heap, with one field offset constant per field, so a class is
+// effectively a struct laid out in jack_ram. Jack evaluates subroutine
+// arguments left to right while C leaves the order unspecified, so programs
+// relying on side effects between arguments may behave differently.
+
+const RUNTIME: &str = include_str!("c_runtime.c");
+const OS_CLASSES: [&str; 8] = [
+    "Array", "Keyboard", "Math", "Memory", "Output", "Screen", "String", "Sys",
+];
+const INDENT: &str = "    ";
+
+struct ClassInfo<'a> {
+    class: &'a Class,
+    fields: Vec<(&'a str, &'a Type)>,
+    statics: Vec<(&'a str, &'a Type)>,
+    subroutines: HashMap<&'a str, SubroutineKind>,
+}
+
+impl<'a> ClassInfo<'a> {
+    fn new(class: &'a Class) -> Self {
+        let mut fields = vec![];
+        let mut statics = vec![];
+        for dec in &class.class_vars {
+            let target = match dec.kind {
+                ClassVarKind::Field => &mut fields,
+                ClassVarKind::Static => &mut statics,
+            };
+            for name in &dec.names {
+                target.push((name.as_str(), &dec.var_type));
+            }
+        }
+        let subroutines = class
+            .subroutines
+            .iter()
+            .map(|subroutine| (subroutine.name.as_str(), subroutine.kind))
+            .collect();
+        Self {
+            class,
+            fields,
+            statics,
+            subroutines,
+        }
+    }
+}
+
+enum Variable<'a> {
+    Local(&'a str, &'a Type),
+    Argument(&'a str, &'a Type),
+    Field(&'a str, &'a Type),
+    Static(&'a str, &'a Type),
+}
+
+impl<'a> Variable<'a> {
+    fn var_type(&self) -> &'a Type {
+        match self {
+            Variable::Local(_, t)
+            | Variable::Argument(_, t)
+            | Variable::Field(_, t)
+            | Variable::Static(_, t) => t,
+        }
+    }
+}
+
+pub fn transpile(classes: &[Class]) -> Result<String, String> {
+    let mut program: HashMap<&str, ClassInfo> = HashMap::new();
+    for class in classes {
+        if program.insert(&class.name, ClassInfo::new(class)).is_some() {
+            return Err(format!("Class {} is defined more than once", class.name));
+        }
+    }
+    let has_main = program
+        .get("Main")
+        .is_some_and(|main| main.subroutines.get("main") == Some(&SubroutineKind::Function));
+    if !has_main {
+        return Err("Program has no Main.main function".to_string());
+    }
+
+    let mut output = String::new();
+    output.push_str("/* Generated by JackAnalyzer from Jack sources. */\n");
+    for class in classes {
+        if OS_CLASSES.contains(&class.name.as_str()) {
+            output.push_str(&format!("#define JACK_USER_{}\n", class.name));
+        }
+    }
+    output.push_str(RUNTIME);
+    output.push('\n');
+
+    // Class layouts and static variables
+    for class in classes {
+        let info = &program[class.name.as_str()];
+        output.push_str(&format!("/* class {} */\n", class.name));
+        output.push_str("enum {\n");
+        for (offset, (name, _)) in info.fields.iter().enumerate() {
+            output.push_str(&format!("{}F_{}__{} = {},\n", INDENT, class.name, name, offset));
+        }
+        output.push_str(&format!("{}sizeof_{} = {}\n", INDENT, class.name, info.fields.len()));
+        output.push_str("};\n");
+        for (name, _) in &info.statics {
+            output.push_str(&format!("static jack_word S_{}__{};\n", class.name, name));
+        }
+    }
+    output.push('\n');
+
+    // Prototypes, so subroutines can call each other in any order
+    for class in classes {
+        for subroutine in &class.subroutines {
+            output.push_str(&format!("{};\n", signature(class, subroutine)));
+        }
+    }
+
+    for class in classes {
+        for subroutine in &class.subroutines {
+            let writer = SubroutineWriter {
+                program: &program,
+                class: &program[class.name.as_str()],
+                subroutine,
+            };
+            output.push('\n');
+            writer.write_definition(&mut output)?;
+        }
+    }
+
+    let entry = if program.contains_key("Sys") {
+        "Sys_init"
+    } else {
+        "Main_main"
+    };
+    output.push_str(&format!(
+        "\nint main(void) {{\n{i}jack_init();\n{i}{}();\n{i}Sys_halt();\n{i}return 0;\n}}\n",
+        entry,
+        i = INDENT
+    ));
+    Ok(output)
+}
+
+fn signature(class: &Class, subroutine: &Subroutine) -> String {
+    let mut parameters: Vec<String> = vec![];
+    if subroutine.kind == SubroutineKind::Method {
+        parameters.push("jack_word self".to_string());
+    }
+    for parameter in &subroutine.parameters {
+        parameters.push(format!("jack_word a_{}", parameter.name));
+    }
+    let parameters = if parameters.is_empty() {
+        "void".to_string()
+    } else {
+        parameters.join(", ")
+    };
+    format!("jack_word {}_{}({})", class.name, subroutine.name, parameters)
+}
+
+struct SubroutineWriter<'a> {
+    program: &'a HashMap<&'a str, ClassInfo<'a>>,
+    class: &'a ClassInfo<'a>,
+    subroutine: &'a Subroutine,
+}
+
+impl<'a> SubroutineWriter<'a> {
+    fn write_definition(&self, output: &mut String) -> Result<(), String> {
+        output.push_str(&format!(
+            "{} {{\n",
+            signature(self.class.class, self.subroutine)
+        ));
+        if self.subroutine.kind == SubroutineKind::Constructor {
+            output.push_str(&format!(
+                "{}jack_word self = Memory_alloc(sizeof_{});\n",
+                INDENT, self.class.class.name
+            ));
+        }
+        for dec in &self.subroutine.locals {
+            for name in &dec.names {
+                output.push_str(&format!("{}jack_word l_{} = 0;\n", INDENT, name));
+            }
+        }
+        self.write_statements(&self.subroutine.statements, 1, output)?;
+        output.push_str(&format!("{}return 0;\n}}\n", INDENT));
+        Ok(())
+    }
+
+    fn write_statements(
+        &self,
+        statements: &[Statement],
+        depth: usize,
+        output: &mut String,
+    ) -> Result<(), String> {
+        let indent = INDENT.repeat(depth);
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::Let { name, index, value } => {
+                    let variable = self.lookup(name)?;
+                    let value = self.expression(value)?;
+                    match index {
+                        Some(index) => {
+                            // Jack computes the target address before the value
+                            output.push_str(&format!(
+                                "{indent}{{\n{indent}{INDENT}jack_word address = jack_add({}, {});\n{indent}{INDENT}jack_poke(address, {});\n{indent}}}\n",
+                                self.read(&variable),
+                                self.expression(index)?,
+                                value,
+                            ));
+                        }
+                        None => {
+                            output.push_str(&format!("{}{}\n", indent, self.assign(&variable, value)));
+                        }
+                    }
+                }
+                StatementKind::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                } => {
+                    output.push_str(&format!("{}if ({}) {{\n", indent, self.expression(condition)?));
+                    self.write_statements(then_branch, depth + 1, output)?;
+                    if let Some(else_branch) = else_branch {
+                        output.push_str(&format!("{}}} else {{\n", indent));
+                        self.write_statements(else_branch, depth + 1, output)?;
+                    }
+                    output.push_str(&format!("{}}}\n", indent));
+                }
+                StatementKind::While { condition, body } => {
+                    output.push_str(&format!(
+                        "{}while ({}) {{\n",
+                        indent,
+                        self.expression(condition)?
+                    ));
+                    self.write_statements(body, depth + 1, output)?;
+                    output.push_str(&format!("{}}}\n", indent));
+                }
+                StatementKind::Do(call) => {
+                    output.push_str(&format!("{}{};\n", indent, self.call(call)?));
+                }
+                StatementKind::Return(value) => {
+                    let value = match value {
+                        Some(value) => self.expression(value)?,
+                        None => "0".to_string(),
+                    };
+                    output.push_str(&format!("{}return {};\n", indent, value));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Locals shadow arguments, which shadow the class's fields and statics.
+    fn lookup(&self, name: &str) -> Result<Variable<'a>, String> {
+        for dec in &self.subroutine.locals {
+            if let Some(local) = dec.names.iter().find(|local| *local == name) {
+                return Ok(Variable::Local(local, &dec.var_type));
+            }
+        }
+        if let Some(parameter) = self.subroutine.parameters.iter().find(|p| p.name == name) {
+            return Ok(Variable::Argument(&parameter.name, &parameter.var_type));
+        }
+        if let Some((field, var_type)) = self.class.fields.iter().find(|(f, _)| *f == name) {
+            if self.subroutine.kind == SubroutineKind::Function {
+                return Err(format!(
+                    "Field {} used in function {}.{}",
+                    name, self.class.class.name, self.subroutine.name
+                ));
+            }
+            return Ok(Variable::Field(field, var_type));
+        }
+        if let Some((static_var, var_type)) = self.class.statics.iter().find(|(s, _)| *s == name) {
+            return Ok(Variable::Static(static_var, var_type));
+        }
+        Err(format!(
+            "Undefined variable {} in {}.{}",
+            name, self.class.class.name, self.subroutine.name
+        ))
+    }
+
+    fn read(&self, variable: &Variable) -> String {
+        let class_name = &self.class.class.name;
+        match variable {
+            Variable::Local(name, _) => format!("l_{}", name),
+            Variable::Argument(name, _) => format!("a_{}", name),
+            Variable::Field(name, _) => format!("jack_peek(jack_add(self, F_{}__{}))", class_name, name),
+            Variable::Static(name, _) => format!("S_{}__{}", class_name, name),
+        }
+    }
+
+    fn assign(&self, variable: &Variable, value: String) -> String {
+        let class_name = &self.class.class.name;
+        match variable {
+            Variable::Local(name, _) => format!("l_{} = {};", name, value),
+            Variable::Argument(name, _) => format!("a_{} = {};", name, value),
+            Variable::Field(name, _) => {
+                format!("jack_poke(jack_add(self, F_{}__{}), {});", class_name, name, value)
+            }
+            Variable::Static(name, _) => format!("S_{}__{} = {};", class_name, name, value),
+        }
+    }
+
+    fn expression(&self, expression: &Expression) -> Result<String, String> {
+        let mut code = self.term(&expression.term)?;
+        for (op, term) in &expression.rest {
+            let rhs = self.term(term)?;
+            code = match op {
+                BinaryOp::Add => format!("jack_add({}, {})", code, rhs),
+                BinaryOp::Subtract => format!("jack_sub({}, {})", code, rhs),
+                BinaryOp::Multiply => format!("jack_mul({}, {})", code, rhs),
+                BinaryOp::Divide => format!("jack_div({}, {})", code, rhs),
+                BinaryOp::And => format!("(jack_word)({} & {})", code, rhs),
+                BinaryOp::Or => format!("(jack_word)({} | {})", code, rhs),
+                BinaryOp::LessThan => format!("jack_lt({}, {})", code, rhs),
+                BinaryOp::GreaterThan => format!("jack_gt({}, {})", code, rhs),
+                BinaryOp::Equals => format!("jack_eq({}, {})", code, rhs),
+                BinaryOp::ShiftLeft => format!("jack_shl({}, {})", code, rhs),
+                BinaryOp::ShiftRight => format!("jack_shr({}, {})", code, rhs),
+            };
+        }
+        Ok(code)
+    }
+
+    fn term(&self, term: &Term) -> Result<String, String> {
+        let code = match term {
+            Term::IntegerConstant(value) => format!("jack_wrap({})", value),
+            Term::StringConstant(value) => {
+                format!("jack_string_literal(\"{}\", {})", c_string(value), value.len())
+            }
+            Term::KeywordConstant(KeywordConstant::True) => "JACK_TRUE".to_string(),
+            Term::KeywordConstant(KeywordConstant::False) => "JACK_FALSE".to_string(),
+            Term::KeywordConstant(KeywordConstant::Null) => "0".to_string(),
+            Term::KeywordConstant(KeywordConstant::This) => {
+                if self.subroutine.kind == SubroutineKind::Function {
+                    return Err(format!(
+                        "'this' used in function {}.{}",
+                        self.class.class.name, self.subroutine.name
+                    ));
+                }
+                "self".to_string()
+            }
+            Term::Variable(name) => self.read(&self.lookup(name)?),
+            Term::ArrayEntry(name, index) => format!(
+                "jack_peek(jack_add({}, {}))",
+                self.read(&self.lookup(name)?),
+                self.expression(index)?
+            ),
+            Term::Call(call) => self.call(call)?,
+            Term::Parenthesised(inner) => format!("({})", self.expression(inner)?),
+            Term::Unary(op, inner) => {
+                let inner = self.term(inner)?;
+                match op {
+                    UnaryOp::Negate => format!("jack_neg({})", inner),
+                    UnaryOp::Not => format!("(jack_word)~{}", inner),
+                    UnaryOp::ShiftLeft => format!("jack_shl({}, 1)", inner),
+                    UnaryOp::ShiftRight => format!("jack_shr({}, 1)", inner),
+                }
+            }
+        };
+        Ok(code)
+    }
+
+    fn call(&self, call: &SubroutineCall) -> Result<String, String> {
+        let mut arguments = vec![];
+        let (class_name, function) = match &call.receiver {
+            None => {
+                let class_name = self.class.class.name.as_str();
+                match self.class.subroutines.get(call.name.as_str()) {
+                    Some(SubroutineKind::Method) => {
+                        if self.subroutine.kind == SubroutineKind::Function {
+                            return Err(format!(
+                                "Method {} called from function {}.{}",
+                                call.name, class_name, self.subroutine.name
+                            ));
+                        }
+                        arguments.push("self".to_string());
+                    }
+                    Some(_) => {}
+                    None => {
+                        return Err(format!(
+                            "Class {} has no subroutine {}",
+                            class_name, call.name
+                        ))
+                    }
+                }
+                (class_name, format!("{}_{}", class_name, call.name))
+            }
+            Some(receiver) => match self.lookup(receiver) {
+                Ok(variable) => match variable.var_type() {
+                    Type::Class(class_name) => {
+                        arguments.push(self.read(&variable));
+                        (class_name.as_str(), format!("{}_{}", class_name, call.name))
+                    }
+                    other => {
+                        return Err(format!(
+                            "Cannot call {} on {} of type {}",
+                            call.name, receiver, other
+                        ))
+                    }
+                },
+                Err(_) => (receiver.as_str(), format!("{}_{}", receiver, call.name)),
+            },
+        };
+        if let Some(target) = self.program.get(class_name) {
+            if !target.subroutines.contains_key(call.name.as_str()) {
+                return Err(format!(
+                    "Class {} has no subroutine {}",
+                    class_name, call.name
+                ));
+            }
+        }
+        for argument in &call.arguments {
+            arguments.push(self.expression(argument)?);
+        }
+        Ok(format!("{}({})", function, arguments.join(", ")))
+    }
+}
+
+// Escapes a Jack string constant for a C string literal, using octal escapes
+// for anything that is not plain printable ASCII (including '?', which could
+// otherwise start a trigraph).
+fn c_string(value: &str) -> String {
+    let mut escaped = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => escaped.push_str(&format!("\\{:03o}", byte)),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parse_classes;
+
+    #[test]
+    fn test_requires_main() {
+        let classes = parse_classes(String::from("class Foo { function void bar() { return; } }"));
+        assert_eq!(
+            transpile(&classes.unwrap()),
+            Err("Program has no Main.main function".to_string())
+        );
+    }
+
+    #[test]
+    fn test_method_call_on_variable() {
+        let classes = parse_classes(String::from(
+            "class Main {
+function void main() { var Main m; let m = Main.new(); do m.run(2); return; }
+constructor Main new() { return this; }
+method void run(int n) { do Output.printInt(n + 1); return; }
+}",
+        ))
+        .unwrap();
+        let program = transpile(&classes).unwrap();
+        assert!(program.contains("jack_word Main_run(jack_word self, jack_word a_n) {"));
+        assert!(program.contains("    Main_run(l_m, jack_wrap(2));"));
+        assert!(program.contains("    Output_printInt(jack_add(a_n, jack_wrap(1)));"));
+        assert!(program.contains("    jack_word self = Memory_alloc(sizeof_Main);"));
+    }
+
+    #[test]
+    fn test_c_string() {
+        assert_eq!(c_string("a?b\\c"), "a\\077b\\134c");
+    }
+}
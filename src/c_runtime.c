@@ -0,0 +1,374 @@
+/*
+ * Jack runtime for the C backend.
+ *
+ * Every Jack value is a 16-bit word. Objects, arrays and strings live on a
+ * simulated heap inside jack_ram, addressed the same way the Hack platform
+ * addresses its RAM, so Memory.peek/poke and array arithmetic behave as they
+ * would on the emulator. OS classes the program defines itself replace the
+ * ones below: the backend emits JACK_USER_<Class> for each of them.
+ */
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef int16_t jack_word;
+
+#define JACK_TRUE ((jack_word)-1)
+#define JACK_FALSE ((jack_word)0)
+#define JACK_RAM_SIZE 32768
+#define JACK_HEAP_BASE 2048
+
+static jack_word jack_ram[JACK_RAM_SIZE];
+static jack_word jack_free_list;
+
+jack_word Sys_error(jack_word code);
+jack_word Memory_alloc(jack_word size);
+jack_word Memory_deAlloc(jack_word object);
+jack_word String_new(jack_word max_length);
+jack_word String_appendChar(jack_word self, jack_word c);
+
+/* 16-bit two's complement arithmetic, wrapping like the Hack ALU. */
+static inline jack_word jack_wrap(int32_t value) { return (jack_word)(uint16_t)(uint32_t)value; }
+static inline jack_word jack_add(jack_word a, jack_word b) { return jack_wrap((int32_t)a + b); }
+static inline jack_word jack_sub(jack_word a, jack_word b) { return jack_wrap((int32_t)a - b); }
+static inline jack_word jack_neg(jack_word a) { return jack_wrap(-(int32_t)a); }
+static inline jack_word jack_mul(jack_word a, jack_word b) { return jack_wrap((int32_t)a * b); }
+static inline jack_word jack_div(jack_word a, jack_word b) {
+    if (b == 0) {
+        return Sys_error(3);
+    }
+    return jack_wrap((int32_t)a / b);
+}
+static inline jack_word jack_lt(jack_word a, jack_word b) { return a < b ? JACK_TRUE : JACK_FALSE; }
+static inline jack_word jack_gt(jack_word a, jack_word b) { return a > b ? JACK_TRUE : JACK_FALSE; }
+static inline jack_word jack_eq(jack_word a, jack_word b) { return a == b ? JACK_TRUE : JACK_FALSE; }
+static inline jack_word jack_shl(jack_word a, jack_word n) {
+    if (n < 0 || n > 15) {
+        return 0;
+    }
+    return jack_wrap((int32_t)(uint16_t)a << n);
+}
+static inline jack_word jack_shr(jack_word a, jack_word n) {
+    int32_t value = a;
+    if (n < 0 || n > 15) {
+        return a < 0 ? -1 : 0;
+    }
+    /* Arithmetic shift, spelled out since >> on negatives is implementation defined. */
+    return jack_wrap(value >= 0 ? value / (1 << n) : -((-value - 1) / (1 << n)) - 1);
+}
+
+static inline jack_word jack_peek(jack_word address) {
+    if (address < 0) {
+        return Sys_error(1);
+    }
+    return jack_ram[address];
+}
+static inline void jack_poke(jack_word address, jack_word value) {
+    if (address < 0) {
+        Sys_error(1);
+        return;
+    }
+    jack_ram[address] = value;
+}
+
+static inline jack_word jack_string_literal(const char *chars, jack_word length) {
+    jack_word s = String_new(length);
+    jack_word i;
+    for (i = 0; i < length; i++) {
+        String_appendChar(s, (jack_word)(unsigned char)chars[i]);
+    }
+    return s;
+}
+
+static inline void jack_init(void) {
+    /* Free blocks are laid out as [total size, next free block]. */
+    jack_free_list = JACK_HEAP_BASE;
+    jack_ram[JACK_HEAP_BASE] = (jack_word)(JACK_RAM_SIZE - JACK_HEAP_BASE);
+    jack_ram[JACK_HEAP_BASE + 1] = 0;
+}
+
+#ifndef JACK_USER_Sys
+jack_word Sys_halt(void) {
+    fflush(stdout);
+    exit(0);
+    return 0;
+}
+jack_word Sys_error(jack_word code) {
+    printf("ERR%d\n", code);
+    fflush(stdout);
+    exit(1);
+    return 0;
+}
+jack_word Sys_wait(jack_word duration) {
+    (void)duration;
+    return 0;
+}
+#endif
+
+#ifndef JACK_USER_Memory
+jack_word Memory_peek(jack_word address) { return jack_peek(address); }
+jack_word Memory_poke(jack_word address, jack_word value) {
+    jack_poke(address, value);
+    return 0;
+}
+/* First fit; allocated blocks keep their total size just before the object. */
+jack_word Memory_alloc(jack_word size) {
+    int32_t needed = (size < 1 ? 1 : size) + 1;
+    jack_word previous = 0;
+    jack_word block = jack_free_list;
+    if (size < 0) {
+        return Sys_error(5);
+    }
+    while (block != 0) {
+        int32_t available = jack_ram[block];
+        if (available >= needed + 2) {
+            jack_word object;
+            jack_ram[block] = (jack_word)(available - needed);
+            object = (jack_word)(block + available - needed);
+            jack_ram[object] = (jack_word)needed;
+            return (jack_word)(object + 1);
+        }
+        if (available >= needed) {
+            if (previous == 0) {
+                jack_free_list = jack_ram[block + 1];
+            } else {
+                jack_ram[previous + 1] = jack_ram[block + 1];
+            }
+            return (jack_word)(block + 1);
+        }
+        previous = block;
+        block = jack_ram[block + 1];
+    }
+    return Sys_error(6);
+}
+jack_word Memory_deAlloc(jack_word object) {
+    jack_word block = (jack_word)(object - 1);
+    if (object <= JACK_HEAP_BASE) {
+        return 0;
+    }
+    jack_ram[block + 1] = jack_free_list;
+    jack_free_list = block;
+    return 0;
+}
+#endif
+
+#ifndef JACK_USER_Math
+jack_word Math_init(void) { return 0; }
+jack_word Math_abs(jack_word x) { return x < 0 ? jack_neg(x) : x; }
+jack_word Math_multiply(jack_word x, jack_word y) { return jack_mul(x, y); }
+jack_word Math_divide(jack_word x, jack_word y) { return jack_div(x, y); }
+jack_word Math_min(jack_word x, jack_word y) { return x < y ? x : y; }
+jack_word Math_max(jack_word x, jack_word y) { return x > y ? x : y; }
+jack_word Math_sqrt(jack_word x) {
+    int32_t root = 0;
+    if (x < 0) {
+        return Sys_error(4);
+    }
+    while ((root + 1) * (root + 1) <= x) {
+        root++;
+    }
+    return (jack_word)root;
+}
+#endif
+
+#ifndef JACK_USER_Array
+jack_word Array_new(jack_word size) {
+    if (size <= 0) {
+        return Sys_error(2);
+    }
+    return Memory_alloc(size);
+}
+jack_word Array_dispose(jack_word self) { return Memory_deAlloc(self); }
+#endif
+
+#ifndef JACK_USER_String
+/* Strings are laid out as [max length, length, characters...]. */
+jack_word String_new(jack_word max_length) {
+    jack_word s;
+    if (max_length < 0) {
+        return Sys_error(14);
+    }
+    s = Memory_alloc((jack_word)(max_length + 2));
+    jack_ram[s] = max_length;
+    jack_ram[s + 1] = 0;
+    return s;
+}
+jack_word String_dispose(jack_word self) { return Memory_deAlloc(self); }
+jack_word String_length(jack_word self) { return jack_peek((jack_word)(self + 1)); }
+jack_word String_charAt(jack_word self, jack_word j) {
+    if (j < 0 || j >= String_length(self)) {
+        return Sys_error(15);
+    }
+    return jack_peek((jack_word)(self + 2 + j));
+}
+jack_word String_setCharAt(jack_word self, jack_word j, jack_word c) {
+    if (j < 0 || j >= String_length(self)) {
+        return Sys_error(16);
+    }
+    jack_poke((jack_word)(self + 2 + j), c);
+    return 0;
+}
+jack_word String_appendChar(jack_word self, jack_word c) {
+    jack_word length = String_length(self);
+    if (length >= jack_peek(self)) {
+        return Sys_error(17);
+    }
+    jack_poke((jack_word)(self + 2 + length), c);
+    jack_poke((jack_word)(self + 1), (jack_word)(length + 1));
+    return self;
+}
+jack_word String_eraseLastChar(jack_word self) {
+    jack_word length = String_length(self);
+    if (length == 0) {
+        return Sys_error(18);
+    }
+    jack_poke((jack_word)(self + 1), (jack_word)(length - 1));
+    return 0;
+}
+jack_word String_intValue(jack_word self) {
+    jack_word length = String_length(self);
+    jack_word value = 0;
+    jack_word i = 0;
+    int negative = 0;
+    if (length > 0 && String_charAt(self, 0) == '-') {
+        negative = 1;
+        i = 1;
+    }
+    for (; i < length; i++) {
+        jack_word c = String_charAt(self, i);
+        if (c < '0' || c > '9') {
+            break;
+        }
+        value = jack_add(jack_mul(value, 10), (jack_word)(c - '0'));
+    }
+    return negative ? jack_neg(value) : value;
+}
+jack_word String_setInt(jack_word self, jack_word value) {
+    char digits[8];
+    int count = 0;
+    int32_t magnitude = value;
+    jack_poke((jack_word)(self + 1), 0);
+    if (magnitude < 0) {
+        String_appendChar(self, '-');
+        magnitude = -magnitude;
+    }
+    do {
+        digits[count++] = (char)('0' + magnitude % 10);
+        magnitude /= 10;
+    } while (magnitude > 0);
+    while (count > 0) {
+        if (String_length(self) >= jack_peek(self)) {
+            return Sys_error(19);
+        }
+        String_appendChar(self, digits[--count]);
+    }
+    return 0;
+}
+jack_word String_newLine(void) { return 128; }
+jack_word String_backSpace(void) { return 129; }
+jack_word String_doubleQuote(void) { return 34; }
+#endif
+
+#ifndef JACK_USER_Output
+jack_word Output_init(void) { return 0; }
+jack_word Output_moveCursor(jack_word i, jack_word j) {
+    (void)i;
+    (void)j;
+    return 0;
+}
+jack_word Output_printChar(jack_word c) {
+    if (c == 128) {
+        putchar('\n');
+    } else if (c == 129) {
+        putchar('\b');
+    } else {
+        putchar((unsigned char)c);
+    }
+    return 0;
+}
+jack_word Output_printString(jack_word s) {
+    jack_word length = String_length(s);
+    jack_word i;
+    for (i = 0; i < length; i++) {
+        Output_printChar(String_charAt(s, i));
+    }
+    return 0;
+}
+jack_word Output_printInt(jack_word i) {
+    printf("%d", i);
+    return 0;
+}
+jack_word Output_println(void) {
+    putchar('\n');
+    return 0;
+}
+jack_word Output_backSpace(void) {
+    putchar('\b');
+    return 0;
+}
+#endif
+
+#ifndef JACK_USER_Screen
+/* There is no display when running natively, so drawing is a no-op. */
+jack_word Screen_init(void) { return 0; }
+jack_word Screen_clearScreen(void) { return 0; }
+jack_word Screen_setColor(jack_word b) {
+    (void)b;
+    return 0;
+}
+jack_word Screen_drawPixel(jack_word x, jack_word y) {
+    (void)x;
+    (void)y;
+    return 0;
+}
+jack_word Screen_drawLine(jack_word x1, jack_word y1, jack_word x2, jack_word y2) {
+    (void)x1;
+    (void)y1;
+    (void)x2;
+    (void)y2;
+    return 0;
+}
+jack_word Screen_drawRectangle(jack_word x1, jack_word y1, jack_word x2, jack_word y2) {
+    (void)x1;
+    (void)y1;
+    (void)x2;
+    (void)y2;
+    return 0;
+}
+jack_word Screen_drawCircle(jack_word x, jack_word y, jack_word r) {
+    (void)x;
+    (void)y;
+    (void)r;
+    return 0;
+}
+#endif
+
+#ifndef JACK_USER_Keyboard
+jack_word Keyboard_init(void) { return 0; }
+jack_word Keyboard_keyPressed(void) { return 0; }
+jack_word Keyboard_readChar(void) {
+    int c = getchar();
+    if (c == EOF) {
+        return 0;
+    }
+    return c == '\n' ? 128 : (jack_word)c;
+}
+jack_word Keyboard_readLine(jack_word message) {
+    jack_word line = String_new(80);
+    int c;
+    Output_printString(message);
+    fflush(stdout);
+    while ((c = getchar()) != EOF && c != '\n') {
+        if (String_length(line) < jack_peek(line)) {
+            String_appendChar(line, (jack_word)c);
+        }
+    }
+    return line;
+}
+jack_word Keyboard_readInt(jack_word message) {
+    jack_word line = Keyboard_readLine(message);
+    jack_word value = String_intValue(line);
+    String_dispose(line);
+    return value;
+}
+#endif
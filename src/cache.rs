@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// The build cache: a directory next to the sources holding, for each output
+// file, a hash of everything it was generated from and a hash of what was
+// written. An output whose recorded hashes both match is up to date and need
+// not be written again. The hash of the sources covers the tool version as
+// well, so that a new version rebuilds everything. The hash of the output
+// tells files the tool wrote, which it may replace, from any others, such as
+// the course's reference files.
+//
+// Each output is generated from its own source alone, so that is all its hash
+// covers. Whole-program checks, such as for a class declared both in the
+// project and in a library, read every source on each build and are never
+// skipped.
+
+pub const CACHE_DIR_NAME: &str = ".jack-cache";
+const HASHES_FILE_NAME: &str = "hashes";
+
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    // Output paths relative to the directory the cache is in
+    entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    // None after the sources failed to compile, which left the output as it was
+    sources: Option<u64>,
+    output: u64,
+}
+
+impl Cache {
+    // The cache of the sources in the directory. A missing or unreadable cache
+    // is an empty one.
+    pub fn load(source_dir: &Path) -> Cache {
+        let dir = source_dir.join(CACHE_DIR_NAME);
+        let text = fs::read_to_string(dir.join(HASHES_FILE_NAME)).unwrap_or_default();
+        // Each line is <sources hash or -> <output hash> <output path>
+        let entries = text
+            .lines()
+            .filter_map(|line| {
+                let (sources, rest) = line.split_once(' ')?;
+                let (output, path) = rest.split_once(' ')?;
+                let entry = Entry {
+                    sources: u64::from_str_radix(sources, 16).ok(),
+                    output: u64::from_str_radix(output, 16).ok()?,
+                };
+                Some((path.to_string(), entry))
+            })
+            .collect();
+        Cache { dir, entries }
+    }
+
+    // Whether the output was written from sources with this hash and has not
+    // been changed since
+    pub fn is_fresh(&self, output_path: &Path, hash: u64) -> bool {
+        match self.entries.get(&self.key(output_path)) {
+            Some(entry) => entry.sources == Some(hash) && hash_file(output_path) == Some(entry.output),
+            None => false,
+        }
+    }
+
+    // Whether the output can be written without losing anything: either there
+    // is no such file or it is as the tool last wrote it
+    pub fn is_generated(&self, output_path: &Path) -> bool {
+        if !output_path.exists() {
+            return true;
+        }
+        match self.entries.get(&self.key(output_path)) {
+            Some(entry) => hash_file(output_path) == Some(entry.output),
+            None => false,
+        }
+    }
+
+    // Records that the output was written from sources with this hash
+    pub fn insert(&mut self, output_path: &Path, hash: u64, output: &str) {
+        let key = self.key(output_path);
+        let entry = Entry {
+            sources: Some(hash),
+            output: self::hash(&[output]),
+        };
+        self.entries.insert(key, entry);
+    }
+
+    // Records that the sources of the output failed to compile
+    pub fn invalidate(&mut self, output_path: &Path) {
+        let key = self.key(output_path);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.sources = None;
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let text: String = self
+            .entries
+            .iter()
+            .map(|(path, entry)| {
+                let sources = entry.sources.map_or("-".to_string(), |hash| format!("{:016x}", hash));
+                format!("{} {:016x} {}\n", sources, entry.output, path)
+            })
+            .collect();
+        fs::write(self.dir.join(HASHES_FILE_NAME), text)
+    }
+
+    fn key(&self, output_path: &Path) -> String {
+        let source_dir = self.dir.parent().unwrap_or(&self.dir);
+        output_path
+            .strip_prefix(source_dir)
+            .unwrap_or(output_path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok().map(|text| hash(&[&text]))
+}
+
+// Removes the cache of the sources in the directory, returning whether there
+// was one.
+pub fn clean(source_dir: &Path) -> io::Result<bool> {
+    let dir = source_dir.join(CACHE_DIR_NAME);
+    if !dir.is_dir() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(dir)?;
+    Ok(true)
+}
+
+// The 64-bit FNV-1a hash of the tool version and the parts, each prefixed with
+// its length so that moving bytes from one part to the next changes the hash.
+pub fn hash(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let version = env!("CARGO_PKG_VERSION");
+    for part in std::iter::once(&version).chain(parts) {
+        let length = (part.len() as u64).to_le_bytes();
+        for byte in length.iter().chain(part.as_bytes()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+        assert_eq!(hash(&["class A {}"]), hash(&["class A {}"]));
+
+        let dir = std::env::temp_dir().join(format!("jack-cache-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let output = dir.join("sub").join("A xml.xml");
+        let reference = dir.join("Reference.xml");
+        fs::write(&reference, "<class>\n</class>\n").unwrap();
+
+        let mut cache = Cache::load(&dir);
+        assert!(!cache.is_fresh(&output, 1));
+        assert!(cache.is_generated(&output));
+        assert!(!cache.is_generated(&reference));
+        fs::write(&output, "<tokens>\n</tokens>\n").unwrap();
+        cache.insert(&output, 1, "<tokens>\n</tokens>\n");
+        cache.save().unwrap();
+        let output_hash = hash(&["<tokens>\n</tokens>\n"]);
+        assert_eq!(
+            fs::read_to_string(dir.join(CACHE_DIR_NAME).join(HASHES_FILE_NAME)).unwrap(),
+            format!("0000000000000001 {:016x} sub/A xml.xml\n", output_hash)
+        );
+
+        let mut cache = Cache::load(&dir);
+        assert!(cache.is_fresh(&output, 1));
+        assert!(!cache.is_fresh(&output, 3));
+        assert!(cache.is_generated(&output));
+        cache.invalidate(&output);
+        assert!(!cache.is_fresh(&output, 1));
+        assert!(cache.is_generated(&output));
+        // Changed by hand, so neither up to date nor to be replaced
+        cache.insert(&output, 1, "<tokens>\n</tokens>\n");
+        fs::write(&output, "<tokens> </tokens>\n").unwrap();
+        assert!(!cache.is_fresh(&output, 1));
+        assert!(!cache.is_generated(&output));
+
+        cache.save().unwrap();
+        assert!(clean(&dir).unwrap());
+        assert!(!clean(&dir).unwrap());
+        assert!(!Cache::load(&dir).is_fresh(&output, 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
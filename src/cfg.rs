@@ -0,0 +1,378 @@
+use crate::ast::{Class, Statement, StatementKind, Subroutine};
+use crate::span::Span;
+
+// Control-flow graphs of subroutines. A basic block runs its let, do and
+// return statements in order and may end in the condition of an if or while,
+// which branches along edges labelled "true" and "false". Every graph has one
+// entry block and one exit block, reached by returning or by falling off the
+// end of the subroutine.
+
+pub const ENTRY: usize = 0;
+pub const EXIT: usize = 1;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Block {
+    pub statements: Vec<Span>,
+    // The span of the whole if or while statement whose condition ends the block
+    pub condition: Option<Span>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    // None for unconditional edges
+    pub label: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    // <class>.<subroutine>
+    pub name: String,
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+    // Whether the end of the body can be reached without a return
+    pub falls_off_end: bool,
+}
+
+impl Cfg {
+    pub fn new(class: &Class, subroutine: &Subroutine) -> Cfg {
+        let mut builder = Builder {
+            blocks: vec![Block::default(), Block::default()],
+            edges: vec![],
+        };
+        let first = builder.block();
+        builder.edge(ENTRY, first, None);
+        let last = builder.statements(first, &subroutine.statements);
+        builder.edge(last, EXIT, None);
+        let falls_off_end = builder.reachable()[last];
+        let (blocks, edges) = builder.simplify();
+        Cfg {
+            name: format!("{}.{}", class.name, subroutine.name),
+            blocks,
+            edges,
+            falls_off_end,
+        }
+    }
+
+    // Whether each block can be reached from the entry
+    pub fn reachable(&self) -> Vec<bool> {
+        reachable(self.blocks.len(), &self.edges)
+    }
+
+    // The statements that can never run, outermost first, counting an if or
+    // while as one statement.
+    pub fn unreachable_statements(&self) -> Vec<Span> {
+        let reachable = self.reachable();
+        let mut spans: Vec<Span> = self
+            .blocks
+            .iter()
+            .zip(reachable)
+            .filter(|(_, reachable)| !reachable)
+            .flat_map(|(block, _)| block.statements.iter().chain(&block.condition))
+            .copied()
+            .collect();
+        spans.sort_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+        let mut outermost: Vec<Span> = vec![];
+        for span in spans {
+            if !outermost.iter().any(|outer| outer.start <= span.start && span.end <= outer.end) {
+                outermost.push(span);
+            }
+        }
+        outermost
+    }
+}
+
+struct Builder {
+    blocks: Vec<Block>,
+    edges: Vec<Edge>,
+}
+
+impl Builder {
+    fn block(&mut self) -> usize {
+        self.blocks.push(Block::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<bool>) {
+        self.edges.push(Edge { from, to, label });
+    }
+
+    // Adds the statements to the current block, returning the block that
+    // control reaches after them.
+    fn statements(&mut self, mut current: usize, statements: &[Statement]) -> usize {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::Let { .. } | StatementKind::Do(_) => {
+                    self.blocks[current].statements.push(statement.span)
+                }
+                StatementKind::Return(_) => {
+                    self.blocks[current].statements.push(statement.span);
+                    self.edge(current, EXIT, None);
+                    // Whatever follows the return is unreachable
+                    current = self.block();
+                }
+                StatementKind::If {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    self.blocks[current].condition = Some(statement.span);
+                    let then_block = self.block();
+                    self.edge(current, then_block, Some(true));
+                    let then_end = self.statements(then_block, then_branch);
+                    let join = self.block();
+                    self.edge(then_end, join, None);
+                    match else_branch {
+                        Some(else_branch) => {
+                            let else_block = self.block();
+                            self.edge(current, else_block, Some(false));
+                            let else_end = self.statements(else_block, else_branch);
+                            self.edge(else_end, join, None);
+                        }
+                        None => self.edge(current, join, Some(false)),
+                    }
+                    current = join;
+                }
+                StatementKind::While { body, .. } => {
+                    let head = self.block();
+                    self.edge(current, head, None);
+                    self.blocks[head].condition = Some(statement.span);
+                    let body_block = self.block();
+                    self.edge(head, body_block, Some(true));
+                    let body_end = self.statements(body_block, body);
+                    self.edge(body_end, head, None);
+                    current = self.block();
+                    self.edge(head, current, Some(false));
+                }
+            }
+        }
+        current
+    }
+
+    fn reachable(&self) -> Vec<bool> {
+        reachable(self.blocks.len(), &self.edges)
+    }
+
+    // Removes the empty blocks left between statements: those nothing reaches
+    // and those with a single way out, whose predecessors are sent straight on.
+    fn simplify(mut self) -> (Vec<Block>, Vec<Edge>) {
+        let mut removed = vec![];
+        for block in EXIT + 1..self.blocks.len() {
+            if self.blocks[block] != Block::default() {
+                continue;
+            }
+            let outgoing: Vec<usize> = (0..self.edges.len())
+                .filter(|&edge| self.edges[edge].from == block)
+                .collect();
+            let has_predecessors = self.edges.iter().any(|edge| edge.to == block && edge.from != block);
+            match outgoing.as_slice() {
+                _ if !has_predecessors => {}
+                [edge] if self.edges[*edge].to != block => {
+                    let target = self.edges[*edge].to;
+                    for edge in &mut self.edges {
+                        if edge.to == block {
+                            edge.to = target;
+                        }
+                    }
+                }
+                _ => continue,
+            }
+            removed.push(block);
+            self.edges.retain(|edge| edge.from != block && edge.to != block);
+        }
+
+        let mut index = vec![0; self.blocks.len()];
+        let mut blocks = vec![];
+        for (old, block) in self.blocks.into_iter().enumerate() {
+            if !removed.contains(&old) {
+                index[old] = blocks.len();
+                blocks.push(block);
+            }
+        }
+        let mut edges: Vec<Edge> = vec![];
+        for edge in self.edges {
+            let edge = Edge {
+                from: index[edge.from],
+                to: index[edge.to],
+                label: edge.label,
+            };
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+        (blocks, edges)
+    }
+}
+
+fn reachable(blocks: usize, edges: &[Edge]) -> Vec<bool> {
+    let mut reachable = vec![false; blocks];
+    let mut stack = vec![ENTRY];
+    while let Some(block) = stack.pop() {
+        if !reachable[block] {
+            reachable[block] = true;
+            stack.extend(edges.iter().filter(|edge| edge.from == block).map(|edge| edge.to));
+        }
+    }
+    reachable
+}
+
+// Builds the graph of every subroutine in the classes.
+pub fn build_all(classes: &[Class]) -> Vec<Cfg> {
+    classes
+        .iter()
+        .flat_map(|class| {
+            class
+                .subroutines
+                .iter()
+                .map(move |subroutine| Cfg::new(class, subroutine))
+        })
+        .collect()
+}
+
+// Writes the graphs as one Graphviz digraph with a cluster per subroutine,
+// labelling blocks with their statements as written in the source.
+pub fn to_dot(graphs: &[Cfg], source: &str) -> String {
+    let mut dot = String::from("digraph cfg {\n  node [shape=box, fontname=\"monospace\"];\n");
+    for (number, graph) in graphs.iter().enumerate() {
+        let node = |block: usize| format!("s{}_{}", number, block);
+        dot.push_str(&format!(
+            "  subgraph cluster_{} {{\n    label=\"{}\";\n",
+            number,
+            escape(&graph.name)
+        ));
+        let reachable = graph.reachable();
+        for (id, block) in graph.blocks.iter().enumerate() {
+            let attributes = match id {
+                ENTRY => "label=\"entry\", shape=oval".to_string(),
+                EXIT => "label=\"exit\", shape=oval".to_string(),
+                _ => {
+                    let mut lines: Vec<String> = block
+                        .statements
+                        .iter()
+                        .map(|span| collapse_whitespace(&source[span.start..span.end]))
+                        .collect();
+                    if let Some(span) = block.condition {
+                        // The condition ends at the opening brace of the body
+                        let text = &source[span.start..span.end];
+                        let header = text.find('{').map_or(text, |brace| &text[..brace]);
+                        lines.push(collapse_whitespace(header));
+                    }
+                    let label: String = lines
+                        .iter()
+                        .map(|line| format!("{}\\l", escape(line)))
+                        .collect();
+                    let style = if reachable[id] { "" } else { ", style=dashed" };
+                    format!("label=\"{}\"{}", label, style)
+                }
+            };
+            dot.push_str(&format!("    {} [{}];\n", node(id), attributes));
+        }
+        for edge in &graph.edges {
+            let label = match edge.label {
+                Some(label) => format!(" [label=\"{}\"]", label),
+                None => String::new(),
+            };
+            dot.push_str(&format!("    {} -> {}{};\n", node(edge.from), node(edge.to), label));
+        }
+        dot.push_str("  }\n");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parse_classes;
+
+    const SOURCE: &str = "class Main {
+  function int f(int n) {
+    var int i;
+    let i = 0;
+    while (i < n) {
+      if (i = 3) { return i; }
+      let i = i + 1;
+    }
+    return n;
+    do Main.f(1);
+  }
+  function void g() {
+    if (true) { return; } else { }
+  }
+}
+";
+
+    #[test]
+    fn test_blocks_and_edges() {
+        let classes = parse_classes(SOURCE.to_string()).unwrap();
+        let graphs = build_all(&classes);
+        let f = &graphs[0];
+        assert_eq!(f.name, "Main.f");
+        let text = |span: &Span| &SOURCE[span.start..span.end];
+        let blocks: Vec<(Vec<&str>, Option<&str>)> = f
+            .blocks
+            .iter()
+            .map(|block| {
+                let statements = block.statements.iter().map(text).collect();
+                let condition = block.condition.map(|span| &text(&span)[..5]);
+                (statements, condition)
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            [
+                (vec![], None),
+                (vec![], None),
+                (vec!["let i = 0;"], None),
+                (vec![], Some("while")),
+                (vec![], Some("if (i")),
+                (vec!["return i;"], None),
+                (vec!["let i = i + 1;"], None),
+                (vec!["return n;"], None),
+                (vec!["do Main.f(1);"], None),
+            ]
+        );
+        let edges: Vec<(usize, usize, Option<bool>)> = f
+            .edges
+            .iter()
+            .map(|edge| (edge.from, edge.to, edge.label))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                (0, 2, None),
+                (2, 3, None),
+                (3, 4, Some(true)),
+                (4, 5, Some(true)),
+                (5, 1, None),
+                (4, 6, Some(false)),
+                (6, 3, None),
+                (3, 7, Some(false)),
+                (7, 1, None),
+                (8, 1, None),
+            ]
+        );
+        assert_eq!(
+            f.unreachable_statements().iter().map(text).collect::<Vec<&str>>(),
+            ["do Main.f(1);"]
+        );
+        assert!(!f.falls_off_end);
+        assert!(graphs[1].falls_off_end);
+        assert!(graphs[1].unreachable_statements().is_empty());
+
+        let dot = to_dot(&graphs[..1], SOURCE);
+        assert!(dot.contains("  subgraph cluster_0 {\n    label=\"Main.f\";\n"));
+        assert!(dot.contains("    s0_3 [label=\"while (i < n)\\l\"];\n"));
+        assert!(dot.contains("    s0_8 [label=\"do Main.f(1);\\l\", style=dashed];\n"));
+        assert!(dot.contains("    s0_3 -> s0_7 [label=\"false\"];\n"));
+    }
+}
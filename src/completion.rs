@@ -0,0 +1,624 @@
+use crate::symbols::{Project, SymbolKind};
+use crate::tokeniser::tokenise_with_recovery;
+use crate::tokens::{Keyword, Symbol, Token, TokenType};
+
+// Completion for partially typed Jack files. A file being edited rarely parses,
+// so instead of the parse tree this works from the recovered tokens: a scan of
+// the tokens finds the declarations of the file's class and of the subroutine
+// around the cursor, and the few tokens before the cursor decide which
+// grammar point it is at. Other classes come from the project's symbols and
+// from the Jack OS.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Symbol(SymbolKind),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+// The subroutines of the Jack OS, as (class, signature)
+const JACK_OS: &[(&str, &str)] = &[
+    ("Math", "function int abs(int x)"),
+    ("Math", "function int multiply(int x, int y)"),
+    ("Math", "function int divide(int x, int y)"),
+    ("Math", "function int min(int x, int y)"),
+    ("Math", "function int max(int x, int y)"),
+    ("Math", "function int sqrt(int x)"),
+    ("String", "constructor String new(int maxLength)"),
+    ("String", "method void dispose()"),
+    ("String", "method int length()"),
+    ("String", "method char charAt(int j)"),
+    ("String", "method void setCharAt(int j, char c)"),
+    ("String", "method String appendChar(char c)"),
+    ("String", "method void eraseLastChar()"),
+    ("String", "method int intValue()"),
+    ("String", "method void setInt(int j)"),
+    ("String", "function char backSpace()"),
+    ("String", "function char doubleQuote()"),
+    ("String", "function char newLine()"),
+    ("Array", "function Array new(int size)"),
+    ("Array", "method void dispose()"),
+    ("Output", "function void moveCursor(int i, int j)"),
+    ("Output", "function void printChar(char c)"),
+    ("Output", "function void printString(String s)"),
+    ("Output", "function void printInt(int i)"),
+    ("Output", "function void println()"),
+    ("Output", "function void backSpace()"),
+    ("Screen", "function void clearScreen()"),
+    ("Screen", "function void setColor(boolean b)"),
+    ("Screen", "function void drawPixel(int x, int y)"),
+    (
+        "Screen",
+        "function void drawLine(int x1, int y1, int x2, int y2)",
+    ),
+    (
+        "Screen",
+        "function void drawRectangle(int x1, int y1, int x2, int y2)",
+    ),
+    ("Screen", "function void drawCircle(int x, int y, int r)"),
+    ("Keyboard", "function char keyPressed()"),
+    ("Keyboard", "function char readChar()"),
+    ("Keyboard", "function String readLine(String message)"),
+    ("Keyboard", "function int readInt(String message)"),
+    ("Memory", "function int peek(int address)"),
+    ("Memory", "function void poke(int address, int value)"),
+    ("Memory", "function Array alloc(int size)"),
+    ("Memory", "function void deAlloc(Array o)"),
+    ("Sys", "function void halt()"),
+    ("Sys", "function void error(int errorCode)"),
+    ("Sys", "function void wait(int duration)"),
+];
+
+const PRIMITIVE_TYPES: [&str; 3] = ["int", "char", "boolean"];
+const CLASS_MEMBER_KEYWORDS: [&str; 5] = ["static", "field", "constructor", "function", "method"];
+const STATEMENT_KEYWORDS: [&str; 5] = ["let", "if", "while", "do", "return"];
+const KEYWORD_CONSTANTS: [&str; 4] = ["true", "false", "null", "this"];
+
+// What a "{" opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Class,
+    Subroutine,
+    If,
+    Else,
+    While,
+}
+
+// A variable declared in the file being completed
+#[derive(Clone)]
+struct Variable {
+    name: String,
+    kind: SymbolKind,
+    var_type: String,
+}
+
+// A subroutine declared in the file being completed
+#[derive(Clone)]
+struct Declared {
+    name: String,
+    kind: SymbolKind,
+    signature: String,
+}
+
+// The declarations of a file and the state of the grammar at the cursor.
+#[derive(Clone, Default)]
+struct Scan {
+    class: Option<String>,
+    class_vars: Vec<Variable>,
+    subroutines: Vec<Declared>,
+    subroutine_kind: Option<SymbolKind>,
+    scope: Vec<Variable>,
+    has_statements: bool,
+    blocks: Vec<Block>,
+    last_closed: Option<Block>,
+    parentheses: usize,
+}
+
+pub fn complete(project: &Project, file: usize, offset: usize) -> Vec<Completion> {
+    let text = &project.files[file].text;
+    let (tokens, _) = tokenise_with_recovery(text);
+
+    // A name being typed is the prefix of every suggestion
+    let mut start = offset;
+    for token in &tokens {
+        if token.span.start < offset && offset <= token.span.end {
+            match token.token {
+                TokenType::Identifier(_) | TokenType::Keyword(_) => start = token.span.start,
+                // Nothing completes inside a constant
+                TokenType::StringConstant(_) | TokenType::IntegerConstant(_) => return vec![],
+                TokenType::Symbol(_) => {}
+            }
+        }
+    }
+    let prefix = &text[start..offset];
+    let before: Vec<&Token> = tokens
+        .iter()
+        .filter(|token| token.span.end <= start)
+        .collect();
+    let scan = scan(text, &tokens, start);
+
+    let previous = before.last().map(|token| &token.token);
+    let mut completions = match previous {
+        Some(TokenType::Symbol(Symbol::Period)) => match before.iter().rev().nth(1) {
+            Some(receiver) if matches!(receiver.token, TokenType::Identifier(_)) => members(
+                project,
+                file,
+                &scan,
+                &text[receiver.span.start..receiver.span.end],
+            ),
+            _ => vec![],
+        },
+        _ => match scan.blocks.len() {
+            0 => match previous {
+                Some(TokenType::Keyword(Keyword::Class)) => vec![],
+                _ => keywords(&["class"]),
+            },
+            1 if scan.parentheses > 0 => match previous {
+                Some(TokenType::Symbol(Symbol::BracketLeft | Symbol::Comma)) => {
+                    types(project, file, &scan)
+                }
+                _ => vec![],
+            },
+            1 => match previous {
+                Some(TokenType::Symbol(Symbol::BracketCurlyLeft))
+                | Some(TokenType::Symbol(Symbol::BracketCurlyRight))
+                | Some(TokenType::Symbol(Symbol::SemiColon)) => keywords(&CLASS_MEMBER_KEYWORDS),
+                Some(TokenType::Keyword(Keyword::Static | Keyword::Field)) => {
+                    types(project, file, &scan)
+                }
+                Some(TokenType::Keyword(
+                    Keyword::Constructor | Keyword::Function | Keyword::Method,
+                )) => {
+                    let mut completions = keywords(&["void"]);
+                    completions.extend(types(project, file, &scan));
+                    completions
+                }
+                _ => vec![],
+            },
+            _ => in_subroutine(project, file, &scan, previous),
+        },
+    };
+
+    let mut seen = std::collections::HashSet::new();
+    completions.retain(|completion| {
+        completion.label.starts_with(prefix) && seen.insert(completion.label.clone())
+    });
+    completions
+}
+
+// Suggestions inside a subroutine body.
+fn in_subroutine(
+    project: &Project,
+    file: usize,
+    scan: &Scan,
+    previous: Option<&TokenType>,
+) -> Vec<Completion> {
+    match previous {
+        Some(TokenType::Symbol(Symbol::BracketCurlyLeft | Symbol::SemiColon))
+        | Some(TokenType::Symbol(Symbol::BracketCurlyRight))
+            if scan.parentheses == 0 =>
+        {
+            let mut completions = vec![];
+            if scan.blocks.len() == 2 && !scan.has_statements {
+                completions.extend(keywords(&["var"]));
+            }
+            completions.extend(keywords(&STATEMENT_KEYWORDS));
+            if previous == Some(&TokenType::Symbol(Symbol::BracketCurlyRight))
+                && scan.last_closed == Some(Block::If)
+            {
+                completions.extend(keywords(&["else"]));
+            }
+            completions
+        }
+        Some(TokenType::Keyword(Keyword::Var)) => types(project, file, scan),
+        Some(TokenType::Keyword(Keyword::Let)) => variables(scan),
+        Some(TokenType::Keyword(Keyword::Do)) => {
+            let mut completions = variables(scan);
+            completions.extend(own_subroutines(scan));
+            completions.extend(classes(project, file, scan));
+            completions
+        }
+        Some(TokenType::Keyword(Keyword::Return))
+        | Some(TokenType::Symbol(
+            Symbol::BracketLeft
+            | Symbol::BracketSquareLeft
+            | Symbol::Comma
+            | Symbol::Plus
+            | Symbol::Minus
+            | Symbol::Times
+            | Symbol::Divide
+            | Symbol::And
+            | Symbol::Or
+            | Symbol::LessThan
+            | Symbol::GreaterThan
+            | Symbol::Equals
+            | Symbol::Not
+            | Symbol::ShiftLeft
+            | Symbol::ShiftRight,
+        )) if !(previous == Some(&TokenType::Symbol(Symbol::Comma)) && scan.parentheses == 0) => {
+            let mut completions = keywords(&KEYWORD_CONSTANTS);
+            // Functions have no object for this to refer to
+            if scan.subroutine_kind == Some(SymbolKind::Function) {
+                completions.retain(|completion| completion.label != "this");
+            }
+            completions.extend(variables(scan));
+            completions.extend(own_subroutines(scan));
+            completions.extend(classes(project, file, scan));
+            completions
+        }
+        _ => vec![],
+    }
+}
+
+fn keywords(keywords: &[&str]) -> Vec<Completion> {
+    keywords
+        .iter()
+        .map(|keyword| Completion {
+            label: keyword.to_string(),
+            kind: CompletionKind::Keyword,
+            detail: "keyword".to_string(),
+        })
+        .collect()
+}
+
+fn types(project: &Project, file: usize, scan: &Scan) -> Vec<Completion> {
+    let mut completions = keywords(&PRIMITIVE_TYPES);
+    completions.extend(classes(project, file, scan));
+    completions
+}
+
+// The class of the file, the other classes of the project and the Jack OS.
+fn classes(project: &Project, file: usize, scan: &Scan) -> Vec<Completion> {
+    let mut names: Vec<String> = project
+        .symbols
+        .iter()
+        .filter(|symbol| symbol.kind == SymbolKind::Class && symbol.file != file)
+        .map(|symbol| symbol.name.clone())
+        .chain(JACK_OS.iter().map(|(class, _)| class.to_string()))
+        .collect();
+    names.sort();
+    names.dedup();
+    scan.class
+        .iter()
+        .cloned()
+        .chain(names)
+        .map(|name| Completion {
+            detail: format!("class {}", name),
+            label: name,
+            kind: CompletionKind::Symbol(SymbolKind::Class),
+        })
+        .collect()
+}
+
+// The locals, arguments, fields and statics in scope, innermost first.
+fn variables(scan: &Scan) -> Vec<Completion> {
+    scan.scope
+        .iter()
+        .chain(scan.class_vars.iter().filter(|variable| {
+            // Functions have no object whose fields they could use
+            variable.kind != SymbolKind::Field || scan.subroutine_kind != Some(SymbolKind::Function)
+        }))
+        .map(|variable| Completion {
+            label: variable.name.clone(),
+            kind: CompletionKind::Symbol(variable.kind),
+            detail: format!("{} {} {}", variable.kind, variable.var_type, variable.name),
+        })
+        .collect()
+}
+
+fn own_subroutines(scan: &Scan) -> Vec<Completion> {
+    scan.subroutines
+        .iter()
+        .map(|subroutine| Completion {
+            label: subroutine.name.clone(),
+            kind: CompletionKind::Symbol(subroutine.kind),
+            detail: subroutine.signature.clone(),
+        })
+        .collect()
+}
+
+// The subroutines callable as "receiver.name": methods when the receiver is a
+// variable holding an object, otherwise the constructors and functions of the
+// class named by the receiver.
+fn members(project: &Project, file: usize, scan: &Scan, receiver: &str) -> Vec<Completion> {
+    let variable = scan
+        .scope
+        .iter()
+        .chain(&scan.class_vars)
+        .find(|variable| variable.name == receiver);
+    let (class, methods) = match variable {
+        Some(variable) if PRIMITIVE_TYPES.contains(&variable.var_type.as_str()) => return vec![],
+        Some(variable) => (variable.var_type.as_str(), true),
+        None => (receiver, false),
+    };
+    let wanted = |kind: SymbolKind| (kind == SymbolKind::Method) == methods;
+
+    let subroutines: Vec<(String, SymbolKind, String)> = if scan.class.as_deref() == Some(class) {
+        scan.subroutines
+            .iter()
+            .map(|subroutine| {
+                (
+                    subroutine.name.clone(),
+                    subroutine.kind,
+                    subroutine.signature.clone(),
+                )
+            })
+            .collect()
+    } else if let Some(id) = project
+        .class(class)
+        .filter(|&id| project.symbols[id].file != file)
+    {
+        let class = &project.symbols[id].name;
+        project
+            .symbols
+            .iter()
+            .filter(|symbol| &symbol.class == class && symbol.subroutine.is_none())
+            .filter(|symbol| {
+                matches!(
+                    symbol.kind,
+                    SymbolKind::Constructor | SymbolKind::Function | SymbolKind::Method
+                )
+            })
+            .map(|symbol| (symbol.name.clone(), symbol.kind, symbol.detail.clone()))
+            .collect()
+    } else {
+        JACK_OS
+            .iter()
+            .filter(|(os_class, _)| *os_class == class)
+            .map(|(_, signature)| {
+                let kind = match signature.split(' ').next() {
+                    Some("constructor") => SymbolKind::Constructor,
+                    Some("method") => SymbolKind::Method,
+                    _ => SymbolKind::Function,
+                };
+                let name = signature.split(' ').nth(2).unwrap_or("");
+                let name = name.split('(').next().unwrap_or("");
+                (name.to_string(), kind, signature.to_string())
+            })
+            .collect()
+    };
+    subroutines
+        .into_iter()
+        .filter(|(_, kind, _)| wanted(*kind))
+        .map(|(label, kind, detail)| Completion {
+            label,
+            kind: CompletionKind::Symbol(kind),
+            detail,
+        })
+        .collect()
+}
+
+// Walks all the tokens, collecting the class's declarations, and records the
+// grammar state and the subroutine's scope as they are at the cursor.
+fn scan(text: &str, tokens: &[Token], cursor: usize) -> Scan {
+    let source = |token: &Token| text[token.span.start..token.span.end].to_string();
+    let identifier = |index: usize| match tokens.get(index) {
+        Some(token) if matches!(token.token, TokenType::Identifier(_)) => Some(source(token)),
+        _ => None,
+    };
+    // "type name (, name)*" starting at the index
+    let names = |index: usize| -> Vec<String> {
+        let mut names = vec![];
+        let mut index = index + 1;
+        while let Some(name) = identifier(index) {
+            names.push(name);
+            match tokens.get(index + 1) {
+                Some(token) if token.token == TokenType::Symbol(Symbol::Comma) => index += 2,
+                _ => break,
+            }
+        }
+        names
+    };
+
+    let mut scan = Scan::default();
+    let mut at_cursor: Option<Scan> = None;
+    let mut pending = Block::Class;
+    for (i, token) in tokens.iter().enumerate() {
+        if at_cursor.is_none() && token.span.start >= cursor {
+            at_cursor = Some(scan.clone());
+        }
+        let depth = scan.blocks.len();
+        match &token.token {
+            TokenType::Keyword(Keyword::Class) if depth == 0 => {
+                scan.class = identifier(i + 1);
+                scan.class_vars.clear();
+                scan.subroutines.clear();
+                pending = Block::Class;
+            }
+            TokenType::Keyword(keyword @ (Keyword::Static | Keyword::Field)) if depth == 1 => {
+                let kind = match keyword {
+                    Keyword::Static => SymbolKind::Static,
+                    _ => SymbolKind::Field,
+                };
+                if let Some(var_type) = tokens.get(i + 1) {
+                    for name in names(i + 1) {
+                        scan.class_vars.push(Variable {
+                            name,
+                            kind,
+                            var_type: source(var_type),
+                        });
+                    }
+                }
+            }
+            TokenType::Keyword(
+                keyword @ (Keyword::Constructor | Keyword::Function | Keyword::Method),
+            ) if depth == 1 => {
+                let kind = match keyword {
+                    Keyword::Constructor => SymbolKind::Constructor,
+                    Keyword::Function => SymbolKind::Function,
+                    _ => SymbolKind::Method,
+                };
+                pending = Block::Subroutine;
+                scan.subroutine_kind = Some(kind);
+                scan.scope.clear();
+                scan.has_statements = false;
+
+                // "type name ( (type name (, type name)*)? )"
+                let mut parameters = vec![];
+                let mut index = i + 4;
+                while let (Some(var_type), Some(name)) = (tokens.get(index), identifier(index + 1))
+                {
+                    parameters.push(format!("{} {}", source(var_type), name));
+                    scan.scope.push(Variable {
+                        name,
+                        kind: SymbolKind::Argument,
+                        var_type: source(var_type),
+                    });
+                    match tokens.get(index + 2) {
+                        Some(token) if token.token == TokenType::Symbol(Symbol::Comma) => {
+                            index += 3
+                        }
+                        _ => break,
+                    }
+                }
+                if let (Some(return_type), Some(name)) = (tokens.get(i + 1), identifier(i + 2)) {
+                    scan.subroutines.push(Declared {
+                        signature: format!(
+                            "{} {} {}({})",
+                            source(token),
+                            source(return_type),
+                            name,
+                            parameters.join(", ")
+                        ),
+                        name,
+                        kind,
+                    });
+                }
+            }
+            TokenType::Keyword(Keyword::Var) if depth == 2 => {
+                if let Some(var_type) = tokens.get(i + 1) {
+                    for name in names(i + 1) {
+                        scan.scope.push(Variable {
+                            name,
+                            kind: SymbolKind::Local,
+                            var_type: source(var_type),
+                        });
+                    }
+                }
+            }
+            TokenType::Keyword(Keyword::Let | Keyword::Do | Keyword::Return) => {
+                scan.has_statements = true
+            }
+            TokenType::Keyword(Keyword::If) => {
+                scan.has_statements = true;
+                pending = Block::If;
+            }
+            TokenType::Keyword(Keyword::While) => {
+                scan.has_statements = true;
+                pending = Block::While;
+            }
+            TokenType::Keyword(Keyword::Else) => pending = Block::Else,
+            TokenType::Symbol(Symbol::BracketCurlyLeft) => {
+                scan.blocks.push(pending);
+                scan.parentheses = 0;
+            }
+            TokenType::Symbol(Symbol::BracketCurlyRight) => {
+                scan.last_closed = scan.blocks.pop();
+                scan.parentheses = 0;
+            }
+            TokenType::Symbol(Symbol::BracketLeft) => scan.parentheses += 1,
+            TokenType::Symbol(Symbol::BracketRight) => {
+                scan.parentheses = scan.parentheses.saturating_sub(1)
+            }
+            _ => {}
+        }
+    }
+
+    // The state at the cursor, with every subroutine of the class
+    let mut result = at_cursor.unwrap_or_else(|| scan.clone());
+    result.subroutines = scan.subroutines;
+    if result.class.is_none() || result.class == scan.class {
+        result.class_vars = scan.class_vars;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const BALL: &str = "class Ball {
+  field int x;
+  constructor Ball new() { let x = 0; return this; }
+  method void move(int dx) { let x = x + dx; return; }
+  function int radius() { return 4; }
+}
+";
+
+    // Completes at the "|" in the source, with Ball.jack in the project.
+    fn labels(source: &str) -> Vec<String> {
+        let offset = source.find('|').unwrap();
+        let source = source.replacen('|', "", 1);
+        let project = Project::new(vec![
+            (PathBuf::from("Ball.jack"), BALL.to_string()),
+            (PathBuf::from("Main.jack"), source),
+        ]);
+        complete(&project, 1, offset)
+            .into_iter()
+            .map(|completion| completion.label)
+            .collect()
+    }
+
+    #[test]
+    fn test_keywords_at_grammar_points() {
+        assert_eq!(labels("|"), ["class"]);
+        assert_eq!(labels("class Main { f|"), ["field", "function"]);
+        assert_eq!(
+            labels("class Main { function void main() { |"),
+            ["var", "let", "if", "while", "do", "return"]
+        );
+        assert_eq!(
+            labels("class Main { function void main() { if (true) { return; } |"),
+            ["let", "if", "while", "do", "return", "else"]
+        );
+        assert_eq!(
+            labels("class Main { function void main() { var B|"),
+            ["Ball"]
+        );
+    }
+
+    #[test]
+    fn test_names_in_scope() {
+        let source = "class Main {
+  field int count;
+  static int total;
+  method void run(int steps) {
+    var Ball ball;
+    let ball = Ball.new();
+    let |";
+        assert_eq!(labels(source), ["steps", "ball", "count", "total"]);
+        let source = source.replace("method", "function");
+        assert_eq!(labels(&source), ["steps", "ball", "total"]);
+        let source = source.replace("let |", "do b|");
+        assert_eq!(labels(&source), ["ball"]);
+        let source = source.replace("do b|", "let total = t|");
+        assert_eq!(labels(&source), ["true", "total"]);
+        let source = source.replace("function", "method");
+        assert_eq!(labels(&source), ["true", "this", "total"]);
+    }
+
+    #[test]
+    fn test_members() {
+        let body = "class Main { function void main() { var Ball ball; do ";
+        assert_eq!(labels(&format!("{}ball.|", body)), ["move"]);
+        assert_eq!(labels(&format!("{}Ball.|", body)), ["new", "radius"]);
+        assert_eq!(
+            labels(&format!("{}Output.print|", body)),
+            ["printChar", "printString", "printInt", "println"]
+        );
+        let project = Project::new(vec![(
+            PathBuf::from("Main.jack"),
+            format!("{}Math.|", body),
+        )]);
+        let abs = &complete(&project, 0, body.len() + 5)[0];
+        assert_eq!(abs.detail, "function int abs(int x)");
+        assert_eq!(abs.kind, CompletionKind::Symbol(SymbolKind::Function));
+    }
+}
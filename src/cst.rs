@@ -0,0 +1,1074 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::span::Span;
+use crate::tokeniser::{tokenise_with_recovery, LexError};
+use crate::tokens::{Keyword, Symbol, Token, TokenType};
+
+// A lossless concrete syntax tree: unlike the parse tree and the AST it keeps
+// every byte of the source, whitespace, comments and text the tokeniser
+// rejected included, so that printing the tree gives back the source exactly.
+//
+// The tree comes in two layers. Green nodes hold only kinds, texts and widths,
+// which makes them immutable values that can be shared between trees. Red
+// nodes are built on demand over the green ones and know their offset in the
+// source and their parent. The nodes mirror the grammar functions of the
+// parser, one node for each element of its XML.
+//
+// Trivia is attached to the innermost node that is open at the next token, but
+// never to a node starting at that token, so a comment before a subroutine
+// belongs to the class, not to the subroutine.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    // Tokens
+    Keyword,
+    Symbol,
+    IntegerConstant,
+    StringConstant,
+    Identifier,
+    Whitespace,
+    Comment,
+    // A character the tokeniser reports and skips
+    Unknown,
+
+    // Nodes
+    SourceFile,
+    Class,
+    ClassVarDec,
+    SubroutineDec,
+    ParameterList,
+    SubroutineBody,
+    VarDec,
+    Statements,
+    LetStatement,
+    IfStatement,
+    WhileStatement,
+    DoStatement,
+    ReturnStatement,
+    Expression,
+    Term,
+    ExpressionList,
+    // The tokens after a syntax error
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenToken {
+    pub kind: SyntaxKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenNode {
+    pub kind: SyntaxKind,
+    // The length of the node's text in bytes
+    pub width: usize,
+    pub children: Vec<GreenElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> GreenNode {
+        let width = children.iter().map(GreenElement::width).sum();
+        GreenNode { kind, width, children }
+    }
+}
+
+impl GreenElement {
+    pub fn width(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.width,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+// The source text of the node
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => write!(f, "{}", node)?,
+                GreenElement::Token(token) => write!(f, "{}", token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+#[derive(Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            offset: 0,
+            parent: None,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.0.offset, self.0.offset + self.0.green.width)
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    // The node itself, then its parent and so on up to the root
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> {
+        std::iter::successors(Some(self.clone()), SyntaxNode::parent)
+    }
+
+    // The child nodes and tokens in source order
+    pub fn children(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut children = vec![];
+        for child in &self.0.green.children {
+            children.push(match child {
+                GreenElement::Node(node) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: node.clone(),
+                    offset,
+                    parent: Some(self.clone()),
+                }))),
+                GreenElement::Token(token) => SyntaxElement::Token(SyntaxToken {
+                    green: token.clone(),
+                    offset,
+                    parent: self.clone(),
+                }),
+            });
+            offset += child.width();
+        }
+        children
+    }
+
+    // The token containing the byte at the offset
+    pub fn token_at_offset(&self, offset: usize) -> Option<SyntaxToken> {
+        let child = self.children().into_iter().find(|child| {
+            let span = child.span();
+            span.start <= offset && offset < span.end
+        })?;
+        match child {
+            SyntaxElement::Node(node) => node.token_at_offset(offset),
+            SyntaxElement::Token(token) => Some(token),
+        }
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.text.len())
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+}
+
+impl SyntaxElement {
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxElement::Node(node) => node.span(),
+            SyntaxElement::Token(token) => token.span(),
+        }
+    }
+}
+
+// One line for each node and token, indented by depth, as in
+//     Class@0..12
+//       Keyword@0..5 "class"
+pub fn dump(node: &SyntaxNode) -> String {
+    let mut output = String::new();
+    dump_node(node, 0, &mut output);
+    output
+}
+
+fn dump_node(node: &SyntaxNode, depth: usize, output: &mut String) {
+    let span = node.span();
+    output.push_str(&format!("{}{:?}@{}..{}\n", "  ".repeat(depth), node.kind(), span.start, span.end));
+    for child in node.children() {
+        match child {
+            SyntaxElement::Node(child) => dump_node(&child, depth + 1, output),
+            SyntaxElement::Token(token) => {
+                let span = token.span();
+                output.push_str(&format!(
+                    "{}{:?}@{}..{} {:?}\n",
+                    "  ".repeat(depth + 1),
+                    token.kind(),
+                    span.start,
+                    span.end,
+                    token.text()
+                ));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub span: Span,
+    pub message: String,
+}
+
+pub struct Parse {
+    pub green: Rc<GreenNode>,
+    pub lex_errors: Vec<LexError>,
+    // The syntax error the parser stopped at
+    pub error: Option<SyntaxError>,
+}
+
+impl Parse {
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.lex_errors.is_empty() || self.error.is_some()
+    }
+}
+
+// Parses a source file of any number of classes. The parser stops at the first
+// syntax error, putting the tokens from there on in an Error node, so the tree
+// covers the whole source whether or not it parses. The errors are the ones
+// the AST parser reports for the same source.
+pub fn parse(source: &str) -> Parse {
+    let (tokens, lex_errors) = tokenise_with_recovery(source);
+    let mut parser = Parser {
+        source,
+        tokens,
+        position: 0,
+        offset: 0,
+        stack: vec![(SyntaxKind::SourceFile, vec![])],
+    };
+    let error = match parser.source_file() {
+        Ok(()) => None,
+        Err(message) => {
+            let span = parser.current_span();
+            parser.recover();
+            Some(SyntaxError { span, message })
+        }
+    };
+    Parse {
+        green: parser.finish_root(),
+        lex_errors,
+        error,
+    }
+}
+
+// Replaces the bytes of the span with the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+// A source file kept parsed while it is edited, as in an editor. An edit inside
+// a class variable declaration or a subroutine re-lexes and re-parses just that
+// member and shares every other subtree with the tree before the edit. Any
+// other edit parses the whole text again.
+pub struct Document {
+    pub text: String,
+    pub parse: Parse,
+}
+
+impl Document {
+    pub fn new(text: String) -> Document {
+        let parse = parse(&text);
+        Document { text, parse }
+    }
+
+    pub fn edit(&mut self, edit: &TextEdit) {
+        let mut text = self.text.clone();
+        text.replace_range(edit.span.start..edit.span.end, &edit.text);
+        self.parse = match self.reparse_member(edit, &text) {
+            Some(green) => Parse {
+                green,
+                lex_errors: vec![],
+                error: None,
+            },
+            None => parse(&text),
+        };
+        self.text = text;
+    }
+
+    // The new tree, when the edit falls strictly inside one member of a class
+    // and that member alone parses again as a member of the same kind. Then
+    // parsing the whole text would give the same tree: the text before the
+    // member is unchanged and the member still starts with its first byte and
+    // ends with its last, a ; or }, so the tokens around it are unchanged too.
+    // Only trees without errors are reused, since errors can span members.
+    fn reparse_member(&self, edit: &TextEdit, text: &str) -> Option<Rc<GreenNode>> {
+        if self.parse.has_errors() {
+            return None;
+        }
+        let root = &self.parse.green;
+        let (class_index, class_offset, class) = child_around(root, 0, edit.span)?;
+        let (member_index, member_offset, member) = child_around(class, class_offset, edit.span)?;
+        if !matches!(member.kind, SyntaxKind::ClassVarDec | SyntaxKind::SubroutineDec) {
+            return None;
+        }
+        let width = member.width + edit.text.len() - (edit.span.end - edit.span.start);
+        let reparsed = parse_member(member.kind, &text[member_offset..member_offset + width])?;
+        let class = with_child(class, member_index, reparsed);
+        Some(with_child(root, class_index, class))
+    }
+}
+
+// The index, offset and green node of the child node of a node at the offset
+// whose text contains the span without touching its first or last byte
+fn child_around(node: &GreenNode, offset: usize, span: Span) -> Option<(usize, usize, &Rc<GreenNode>)> {
+    let mut start = offset;
+    for (index, child) in node.children.iter().enumerate() {
+        let end = start + child.width();
+        if let GreenElement::Node(child) = child {
+            if start < span.start && span.end < end {
+                return Some((index, start, child));
+            }
+        }
+        start = end;
+    }
+    None
+}
+
+fn with_child(node: &GreenNode, index: usize, child: Rc<GreenNode>) -> Rc<GreenNode> {
+    let mut children = node.children.clone();
+    children[index] = GreenElement::Node(child);
+    Rc::new(GreenNode::new(node.kind, children))
+}
+
+// Parses the text of a class member on its own, which must be one member of
+// the kind with no errors and nothing after it
+fn parse_member(kind: SyntaxKind, source: &str) -> Option<Rc<GreenNode>> {
+    let (tokens, errors) = tokenise_with_recovery(source);
+    if !errors.is_empty() {
+        return None;
+    }
+    let mut parser = Parser {
+        source,
+        tokens,
+        position: 0,
+        offset: 0,
+        stack: vec![(SyntaxKind::SourceFile, vec![])],
+    };
+    match kind {
+        SyntaxKind::ClassVarDec => parser.class_var_dec().ok()?,
+        SyntaxKind::SubroutineDec => parser.subroutine_dec().ok()?,
+        _ => return None,
+    }
+    if parser.offset != source.len() {
+        return None;
+    }
+    match parser.finish_root().children.as_slice() {
+        [GreenElement::Node(member)] => Some(member.clone()),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    // The next token to parse
+    position: usize,
+    // How much of the source is already in the tree
+    offset: usize,
+    // The kinds and children of the nodes being built, innermost last
+    stack: Vec<(SyntaxKind, Vec<GreenElement>)>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&TokenType> {
+        self.tokens.get(self.position).map(|token| &token.token)
+    }
+
+    fn at(&self, expected: &TokenType) -> bool {
+        self.peek() == Some(expected)
+    }
+
+    fn current_span(&self) -> Span {
+        match self.tokens.get(self.position) {
+            Some(token) => token.span,
+            None => {
+                let end = self.tokens.last().map_or(0, |token| token.span.end);
+                Span::new(end, end)
+            }
+        }
+    }
+
+    fn start(&mut self, kind: SyntaxKind) {
+        self.trivia();
+        self.stack.push((kind, vec![]));
+    }
+
+    fn finish(&mut self) {
+        let (kind, children) = self.stack.pop().unwrap();
+        self.push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+    }
+
+    fn push(&mut self, element: GreenElement) {
+        self.stack.last_mut().unwrap().1.push(element);
+    }
+
+    fn push_token(&mut self, kind: SyntaxKind, text: &str) {
+        self.push(GreenElement::Token(Rc::new(GreenToken {
+            kind,
+            text: text.to_string(),
+        })));
+    }
+
+    // Adds the whitespace, comments and unknown characters up to the next token
+    fn trivia(&mut self) {
+        let end = match self.tokens.get(self.position) {
+            Some(token) => token.span.start,
+            None => self.source.len(),
+        };
+        let source = self.source;
+        for (kind, text) in split_trivia(&source[self.offset..end]) {
+            self.push_token(kind, text);
+        }
+        self.offset = end;
+    }
+
+    fn bump(&mut self) {
+        self.trivia();
+        let token = &self.tokens[self.position];
+        let (kind, span) = (token_kind(&token.token), token.span);
+        let source = self.source;
+        self.push_token(kind, &source[span.start..span.end]);
+        self.offset = span.end;
+        self.position += 1;
+    }
+
+    fn expect(&mut self, expected: &TokenType) -> Result<(), String> {
+        match self.peek() {
+            Some(token) if token == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(token) => Err(format!("Expected {:?}, found {:?}", expected, token)),
+            None => Err("Unexpected end of tokens".to_string()),
+        }
+    }
+
+    // Consumes a token the check accepts, or fails saying what was expected
+    fn expect_where(&mut self, expected: &str, accept: impl Fn(&TokenType) -> bool) -> Result<(), String> {
+        match self.peek() {
+            Some(token) if accept(token) => {
+                self.bump();
+                Ok(())
+            }
+            Some(token) => Err(format!("Expected {}, found {:?}", expected, token)),
+            None => Err("Unexpected end of tokens".to_string()),
+        }
+    }
+
+    // After a syntax error: puts the rest of the tokens in an Error node and
+    // closes the nodes left open
+    fn recover(&mut self) {
+        if self.position < self.tokens.len() {
+            self.start(SyntaxKind::Error);
+            while self.position < self.tokens.len() {
+                self.bump();
+            }
+            self.finish();
+        }
+        while self.stack.len() > 1 {
+            self.finish();
+        }
+    }
+
+    fn finish_root(mut self) -> Rc<GreenNode> {
+        self.trivia();
+        let (kind, children) = self.stack.pop().unwrap();
+        Rc::new(GreenNode::new(kind, children))
+    }
+
+    fn source_file(&mut self) -> Result<(), String> {
+        while let Some(token) = self.peek() {
+            match token {
+                TokenType::Keyword(Keyword::Class) => self.class()?,
+                _ => return Err("Compilation call to something not the class at the top level".to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    fn class(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::Class);
+        self.expect(&TokenType::Keyword(Keyword::Class))?;
+        self.identifier()?;
+        self.expect(&TokenType::Symbol(Symbol::BracketCurlyLeft))?;
+        while matches!(self.peek(), Some(TokenType::Keyword(Keyword::Static | Keyword::Field))) {
+            self.class_var_dec()?;
+        }
+        while matches!(
+            self.peek(),
+            Some(TokenType::Keyword(Keyword::Constructor | Keyword::Method | Keyword::Function))
+        ) {
+            self.subroutine_dec()?;
+        }
+        self.expect(&TokenType::Symbol(Symbol::BracketCurlyRight))?;
+        self.finish();
+        Ok(())
+    }
+
+    fn class_var_dec(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::ClassVarDec);
+        self.keyword(&[Keyword::Static, Keyword::Field])?;
+        self.variable_names()?;
+        self.finish();
+        Ok(())
+    }
+
+    // type varName (, varName)* ;
+    fn variable_names(&mut self) -> Result<(), String> {
+        self.type_name(false)?;
+        self.identifier()?;
+        while self.at(&TokenType::Symbol(Symbol::Comma)) {
+            self.bump();
+            self.identifier()?;
+        }
+        self.expect(&TokenType::Symbol(Symbol::SemiColon))
+    }
+
+    fn keyword(&mut self, valid_keywords: &[Keyword]) -> Result<(), String> {
+        let expected = format!("one of {:?}", valid_keywords);
+        self.expect_where(&expected, |token| {
+            matches!(token, TokenType::Keyword(keyword) if valid_keywords.contains(keyword))
+        })
+    }
+
+    fn type_name(&mut self, allow_void: bool) -> Result<(), String> {
+        self.expect_where("a type", |token| match token {
+            TokenType::Keyword(Keyword::Int | Keyword::Char | Keyword::Boolean) | TokenType::Identifier(_) => true,
+            TokenType::Keyword(Keyword::Void) => allow_void,
+            _ => false,
+        })
+    }
+
+    fn identifier(&mut self) -> Result<(), String> {
+        self.expect_where("an identifier", |token| matches!(token, TokenType::Identifier(_)))
+    }
+
+    fn subroutine_dec(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::SubroutineDec);
+        self.keyword(&[Keyword::Constructor, Keyword::Function, Keyword::Method])?;
+        self.type_name(true)?;
+        self.identifier()?;
+        self.expect(&TokenType::Symbol(Symbol::BracketLeft))?;
+        self.parameter_list()?;
+        self.expect(&TokenType::Symbol(Symbol::BracketRight))?;
+        self.subroutine_body()?;
+        self.finish();
+        Ok(())
+    }
+
+    fn parameter_list(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::ParameterList);
+        if !self.at(&TokenType::Symbol(Symbol::BracketRight)) {
+            self.type_name(false)?;
+            self.identifier()?;
+            while self.at(&TokenType::Symbol(Symbol::Comma)) {
+                self.bump();
+                self.type_name(false)?;
+                self.identifier()?;
+            }
+        }
+        self.finish();
+        Ok(())
+    }
+
+    fn subroutine_body(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::SubroutineBody);
+        self.expect(&TokenType::Symbol(Symbol::BracketCurlyLeft))?;
+        while self.at(&TokenType::Keyword(Keyword::Var)) {
+            self.var_dec()?;
+        }
+        self.statements()?;
+        self.expect(&TokenType::Symbol(Symbol::BracketCurlyRight))?;
+        self.finish();
+        Ok(())
+    }
+
+    fn var_dec(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::VarDec);
+        self.expect(&TokenType::Keyword(Keyword::Var))?;
+        self.variable_names()?;
+        self.finish();
+        Ok(())
+    }
+
+    fn statements(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::Statements);
+        while let Some(TokenType::Keyword(keyword)) = self.peek() {
+            match keyword {
+                Keyword::Let => self.let_statement()?,
+                Keyword::If => self.if_statement()?,
+                Keyword::While => self.while_statement()?,
+                Keyword::Do => self.do_statement()?,
+                Keyword::Return => self.return_statement()?,
+                _ => break,
+            }
+        }
+        self.finish();
+        Ok(())
+    }
+
+    fn let_statement(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::LetStatement);
+        self.expect(&TokenType::Keyword(Keyword::Let))?;
+        self.identifier()?;
+        if self.at(&TokenType::Symbol(Symbol::BracketSquareLeft)) {
+            self.bump();
+            self.expression()?;
+            self.expect(&TokenType::Symbol(Symbol::BracketSquareRight))?;
+        }
+        self.expect(&TokenType::Symbol(Symbol::Equals))?;
+        self.expression()?;
+        self.expect(&TokenType::Symbol(Symbol::SemiColon))?;
+        self.finish();
+        Ok(())
+    }
+
+    fn if_statement(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::IfStatement);
+        self.expect(&TokenType::Keyword(Keyword::If))?;
+        self.condition()?;
+        self.block()?;
+        if self.at(&TokenType::Keyword(Keyword::Else)) {
+            self.bump();
+            self.block()?;
+        }
+        self.finish();
+        Ok(())
+    }
+
+    fn while_statement(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::WhileStatement);
+        self.expect(&TokenType::Keyword(Keyword::While))?;
+        self.condition()?;
+        self.block()?;
+        self.finish();
+        Ok(())
+    }
+
+    // ( expression )
+    fn condition(&mut self) -> Result<(), String> {
+        self.expect(&TokenType::Symbol(Symbol::BracketLeft))?;
+        self.expression()?;
+        self.expect(&TokenType::Symbol(Symbol::BracketRight))
+    }
+
+    // { statements }
+    fn block(&mut self) -> Result<(), String> {
+        self.expect(&TokenType::Symbol(Symbol::BracketCurlyLeft))?;
+        self.statements()?;
+        self.expect(&TokenType::Symbol(Symbol::BracketCurlyRight))
+    }
+
+    fn do_statement(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::DoStatement);
+        self.expect(&TokenType::Keyword(Keyword::Do))?;
+        self.identifier()?;
+        self.subroutine_call()?;
+        self.expect(&TokenType::Symbol(Symbol::SemiColon))?;
+        self.finish();
+        Ok(())
+    }
+
+    // The rest of a subroutine call after its first identifier, which has no
+    // node of its own, as in the parser's XML
+    fn subroutine_call(&mut self) -> Result<(), String> {
+        if self.at(&TokenType::Symbol(Symbol::Period)) {
+            self.bump();
+            self.identifier()?;
+        }
+        if let Err(err) = self.expect(&TokenType::Symbol(Symbol::BracketLeft)) {
+            return Err(format!("Error while parsing subroutine call: {}", err));
+        }
+        self.expression_list()?;
+        if let Err(err) = self.expect(&TokenType::Symbol(Symbol::BracketRight)) {
+            return Err(format!("Error while parsing subroutine call: {}", err));
+        }
+        Ok(())
+    }
+
+    fn return_statement(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::ReturnStatement);
+        self.expect(&TokenType::Keyword(Keyword::Return))?;
+        match self.peek() {
+            Some(TokenType::Symbol(Symbol::SemiColon)) => {}
+            Some(_) => self.expression()?,
+            None => return Err("Unexpected end of tokens when compiling return".to_string()),
+        }
+        self.expect(&TokenType::Symbol(Symbol::SemiColon))?;
+        self.finish();
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::Expression);
+        self.term()?;
+        while matches!(
+            self.peek(),
+            Some(TokenType::Symbol(
+                Symbol::Plus
+                    | Symbol::Minus
+                    | Symbol::Times
+                    | Symbol::Divide
+                    | Symbol::And
+                    | Symbol::Or
+                    | Symbol::LessThan
+                    | Symbol::GreaterThan
+                    | Symbol::Equals
+                    | Symbol::ShiftLeft
+                    | Symbol::ShiftRight
+            ))
+        ) {
+            self.bump();
+            self.term()?;
+        }
+        self.finish();
+        Ok(())
+    }
+
+    fn term(&mut self) -> Result<(), String> {
+        self.start(SyntaxKind::Term);
+        match self.peek() {
+            Some(
+                TokenType::IntegerConstant(_)
+                | TokenType::StringConstant(_)
+                | TokenType::Keyword(Keyword::True | Keyword::False | Keyword::Null | Keyword::This),
+            ) => self.bump(),
+            Some(TokenType::Symbol(Symbol::Minus | Symbol::Not | Symbol::ShiftLeft | Symbol::ShiftRight)) => {
+                self.bump();
+                self.term()?;
+            }
+            Some(TokenType::Symbol(Symbol::BracketLeft)) => {
+                self.bump();
+                self.expression()?;
+                self.expect(&TokenType::Symbol(Symbol::BracketRight))?;
+            }
+            Some(TokenType::Identifier(_)) => {
+                self.bump();
+                match self.peek() {
+                    Some(TokenType::Symbol(Symbol::BracketSquareLeft)) => {
+                        self.bump();
+                        self.expression()?;
+                        self.expect(&TokenType::Symbol(Symbol::BracketSquareRight))?;
+                    }
+                    Some(TokenType::Symbol(Symbol::BracketLeft | Symbol::Period)) => self.subroutine_call()?,
+                    _ => {}
+                }
+            }
+            Some(token) => return Err(format!("Unexpected token {:?} when compiling term", token)),
+            None => return Err("Unexpected end of tokens when compiling term".to_string()),
+        }
+        self.finish();
+        Ok(())
+    }
+
+    fn expression_list(&mut self) -> Result<(), String> {
+        if self.peek().is_none() {
+            return Err("Unexpected end of tokens when compiling expression list".to_string());
+        }
+        self.start(SyntaxKind::ExpressionList);
+        if !self.at(&TokenType::Symbol(Symbol::BracketRight)) {
+            self.expression()?;
+            while self.at(&TokenType::Symbol(Symbol::Comma)) {
+                self.bump();
+                self.expression()?;
+            }
+        }
+        self.finish();
+        Ok(())
+    }
+}
+
+fn token_kind(token: &TokenType) -> SyntaxKind {
+    match token {
+        TokenType::Keyword(_) => SyntaxKind::Keyword,
+        TokenType::Symbol(_) => SyntaxKind::Symbol,
+        TokenType::IntegerConstant(_) => SyntaxKind::IntegerConstant,
+        TokenType::StringConstant(_) => SyntaxKind::StringConstant,
+        TokenType::Identifier(_) => SyntaxKind::Identifier,
+    }
+}
+
+// Splits the text between two tokens the way the tokeniser skips it: runs of
+// whitespace, comments, and single characters it does not know.
+fn split_trivia(text: &str) -> Vec<(SyntaxKind, &str)> {
+    let mut pieces = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let (kind, length) = if c.is_whitespace() {
+            let length = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+            (SyntaxKind::Whitespace, length)
+        } else if rest.starts_with("//") {
+            (SyntaxKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            let length = comment.find("*/").map_or(rest.len(), |end| end + 4);
+            (SyntaxKind::Comment, length)
+        } else {
+            (SyntaxKind::Unknown, c.len_utf8())
+        };
+        pieces.push((kind, &rest[..length]));
+        rest = &rest[length..];
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::random_class;
+    use crate::parser;
+    use crate::tokeniser::tokenise;
+
+    const SOURCES: [&str; 3] = [
+        include_str!("../tests/fixtures/ArrayTest/Main.jack"),
+        include_str!("../tests/fixtures/Square/SquareGame.jack"),
+        include_str!("../tests/fixtures/ExpressionLessSquare/Square.jack"),
+    ];
+
+    // The tree in the parser's XML, leaving out the trivia
+    fn to_xml(node: &SyntaxNode, output: &mut String) {
+        let tag = match node.kind() {
+            SyntaxKind::SourceFile => None,
+            SyntaxKind::Class => Some("class"),
+            SyntaxKind::ClassVarDec => Some("classVarDec"),
+            SyntaxKind::SubroutineDec => Some("subroutineDec"),
+            SyntaxKind::ParameterList => Some("parameterList"),
+            SyntaxKind::SubroutineBody => Some("subroutineBody"),
+            SyntaxKind::VarDec => Some("varDec"),
+            SyntaxKind::Statements => Some("statements"),
+            SyntaxKind::LetStatement => Some("letStatement"),
+            SyntaxKind::IfStatement => Some("ifStatement"),
+            SyntaxKind::WhileStatement => Some("whileStatement"),
+            SyntaxKind::DoStatement => Some("doStatement"),
+            SyntaxKind::ReturnStatement => Some("returnStatement"),
+            SyntaxKind::Expression => Some("expression"),
+            SyntaxKind::Term => Some("term"),
+            SyntaxKind::ExpressionList => Some("expressionList"),
+            kind => panic!("Unexpected node {:?}", kind),
+        };
+        if let Some(tag) = tag {
+            output.push_str(&format!("<{}>\n", tag));
+        }
+        for child in node.children() {
+            match child {
+                SyntaxElement::Node(child) => to_xml(&child, output),
+                SyntaxElement::Token(token) => {
+                    for token in tokenise(token.text().to_string()).unwrap() {
+                        output.push_str(&format!("{}\n", token));
+                    }
+                }
+            }
+        }
+        if let Some(tag) = tag {
+            output.push_str(&format!("</{}>\n", tag));
+        }
+    }
+
+    #[test]
+    fn test_matches_parser() {
+        for source in SOURCES {
+            let parse = parse(source);
+            assert!(!parse.has_errors());
+            let mut xml = String::new();
+            to_xml(&parse.syntax(), &mut xml);
+            assert_eq!(xml, parser::parse(source.to_string()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let odd = [
+            "",
+            "  // only a comment",
+            "class A { /* unterminated",
+            "class A { function void f() { let x = ; } } trailing",
+            "class A { let }",
+            "class \"unterminated\nstring",
+            "@ class $ A { } /*/ still a comment */ é",
+            "class A { function int f() { return 99999 ^ #1; } }\r\n",
+        ];
+        let random = (0..100).map(random_class);
+        let sources = SOURCES.iter().chain(odd.iter()).map(|source| source.to_string());
+        for source in sources.chain(random) {
+            let parse = parse(&source);
+            assert_eq!(parse.green.to_string(), source);
+            assert_eq!(parse.green.width, source.len());
+        }
+        assert!(!parse(&random_class(7)).has_errors());
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = "class A { function void f() { let x = ; } } // end";
+        let parse = parse(source);
+        assert_eq!(
+            parse.error,
+            Some(SyntaxError {
+                span: Span::new(38, 39),
+                message: "Unexpected token Symbol(SemiColon) when compiling term".to_string(),
+            })
+        );
+        let root = parse.syntax();
+        let error = root.token_at_offset(38).unwrap().parent();
+        assert_eq!(error.kind(), SyntaxKind::Error);
+        assert_eq!(error.span(), Span::new(38, 43));
+        assert_eq!(
+            error.ancestors().map(|node| node.kind()).collect::<Vec<_>>(),
+            vec![
+                SyntaxKind::Error,
+                SyntaxKind::Term,
+                SyntaxKind::Expression,
+                SyntaxKind::LetStatement,
+                SyntaxKind::Statements,
+                SyntaxKind::SubroutineBody,
+                SyntaxKind::SubroutineDec,
+                SyntaxKind::Class,
+                SyntaxKind::SourceFile,
+            ]
+        );
+        let comment = root.token_at_offset(source.len() - 1).unwrap();
+        assert_eq!((comment.kind(), comment.text()), (SyntaxKind::Comment, "// end"));
+        assert_eq!(comment.parent().kind(), SyntaxKind::SourceFile);
+    }
+
+    // Edits the document and checks its tree against parsing the new text
+    fn edit_and_check(document: &mut Document, start: usize, end: usize, text: &str) {
+        document.edit(&TextEdit {
+            span: Span::new(start, end),
+            text: text.to_string(),
+        });
+        let full = parse(&document.text);
+        assert_eq!(document.parse.green, full.green, "after replacing {}..{} with {:?}", start, end, text);
+        assert_eq!(document.parse.lex_errors, full.lex_errors);
+        assert_eq!(document.parse.error, full.error);
+    }
+
+    fn members(document: &Document) -> Vec<Rc<GreenNode>> {
+        let nodes = |node: &GreenNode| -> Vec<Rc<GreenNode>> {
+            node.children
+                .iter()
+                .filter_map(|child| match child {
+                    GreenElement::Node(node) => Some(node.clone()),
+                    GreenElement::Token(_) => None,
+                })
+                .collect()
+        };
+        nodes(&nodes(&document.parse.green)[0])
+    }
+
+    #[test]
+    fn test_incremental_edit() {
+        let source = include_str!("../tests/fixtures/Square/SquareGame.jack");
+        let mut document = Document::new(source.to_string());
+        let before = members(&document);
+        assert_eq!(before.len(), 6);
+
+        let statement = source.find("let direction = 0;").unwrap();
+        edit_and_check(&mut document, statement + 16, statement + 17, "1 + 2");
+        let after = members(&document);
+        let shared: Vec<bool> = before.iter().zip(&after).map(|(old, new)| Rc::ptr_eq(old, new)).collect();
+        assert_eq!(shared, vec![true, true, false, true, true, true]);
+
+        // A field declaration stays one
+        let field = document.text.find("int direction;").unwrap();
+        edit_and_check(&mut document, field + 13, field + 13, ", speed");
+        assert!(Rc::ptr_eq(&members(&document)[2], &after[2]));
+
+        // Edits that reach outside one member parse everything again
+        let before = members(&document);
+        let method = document.text.find("method void dispose").unwrap();
+        edit_and_check(&mut document, method + 1, method + 2, "a");
+        edit_and_check(&mut document, method + 1, method + 2, "e");
+        let body = document.text.find("do square.dispose();").unwrap();
+        edit_and_check(&mut document, body, body, "} method void split() {");
+        edit_and_check(&mut document, body, body + 23, "/*");
+        edit_and_check(&mut document, body, body + 2, "");
+        assert_eq!(document.text, source.replace("0;  // initial", "1 + 2;  // initial").replace("int direction;", "int direction, speed;"));
+        assert!(!Rc::ptr_eq(&members(&document)[0], &before[0]));
+    }
+
+    #[test]
+    fn test_random_edits() {
+        let snippets = ["", "x", " ", ";", "}", "{", "/*", "*/", "\"", "é", "let y = 1;", "// c\n", "function void g() { return; }"];
+        for seed in 0..200u64 {
+            let mut document = Document::new(random_class(seed));
+            for step in 0..5u64 {
+                let n = seed * 31 + step * 7;
+                let text = &document.text;
+                let mut start = (n * 7919 % (text.len() as u64 + 1)) as usize;
+                while !text.is_char_boundary(start) {
+                    start -= 1;
+                }
+                let mut end = (start + (n % 4) as usize).min(text.len());
+                while !text.is_char_boundary(end) {
+                    end += 1;
+                }
+                edit_and_check(&mut document, start, end, snippets[(n % snippets.len() as u64) as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_trivia_placement() {
+        let source = "class A {\n  // f\n  function void f() { return; }\n}\n";
+        assert_eq!(
+            dump(&parse(source).syntax()).lines().take(11).collect::<Vec<_>>(),
+            vec![
+                "SourceFile@0..51",
+                "  Class@0..50",
+                "    Keyword@0..5 \"class\"",
+                "    Whitespace@5..6 \" \"",
+                "    Identifier@6..7 \"A\"",
+                "    Whitespace@7..8 \" \"",
+                "    Symbol@8..9 \"{\"",
+                "    Whitespace@9..12 \"\\n  \"",
+                "    Comment@12..16 \"// f\"",
+                "    Whitespace@16..19 \"\\n  \"",
+                "    SubroutineDec@19..48",
+            ]
+        );
+    }
+}
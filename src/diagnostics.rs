@@ -0,0 +1,115 @@
+use crate::ast::parse_tokens;
+use crate::cst::Parse;
+use crate::span::Span;
+use crate::tokeniser::{tokenise_with_recovery, LexError};
+
+// Problems found in a Jack source file, located by byte span. Every kind of
+// problem has a stable code, J0001 and up, that tools can match on.
+
+pub const LEXICAL_ERROR: &str = "J0001";
+pub const SYNTAX_ERROR: &str = "J0002";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn new(name: &str) -> Option<Severity> {
+        match name {
+            "info" => Some(Severity::Info),
+            "warning" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+}
+
+// Tokenises and parses the source, returning every lexical error and the
+// first syntax error.
+pub fn check_source(source: &str) -> Vec<Diagnostic> {
+    let (tokens, lex_errors) = tokenise_with_recovery(source);
+    let mut diagnostics = lexical_diagnostics(&lex_errors);
+    if let Err((message, span)) = parse_tokens(&tokens) {
+        diagnostics.push(syntax_diagnostic(span, &message));
+    }
+    diagnostics
+}
+
+// The same diagnostics as check_source, from a source already parsed to a
+// syntax tree.
+pub fn check_parse(parse: &Parse) -> Vec<Diagnostic> {
+    let mut diagnostics = lexical_diagnostics(&parse.lex_errors);
+    if let Some(error) = &parse.error {
+        diagnostics.push(syntax_diagnostic(error.span, &error.message));
+    }
+    diagnostics
+}
+
+fn lexical_diagnostics(lex_errors: &[LexError]) -> Vec<Diagnostic> {
+    lex_errors
+        .iter()
+        .map(|error| Diagnostic {
+            span: error.span,
+            severity: Severity::Error,
+            code: LEXICAL_ERROR,
+            message: error.message.clone(),
+        })
+        .collect()
+}
+
+fn syntax_diagnostic(span: Span, message: &str) -> Diagnostic {
+    Diagnostic {
+        span,
+        severity: Severity::Error,
+        code: SYNTAX_ERROR,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cst;
+    use crate::generate::random_class;
+
+    #[test]
+    fn test_syntax_tree_matches_parser() {
+        let mut sources = vec![
+            "class A { function void f() { let x = ; } }".to_string(),
+            "class A { function void f() { do x.; let a[1][2] = 3; } }".to_string(),
+            "class A { function void f() { if (x) { } else".to_string(),
+            "class A { function void f( int".to_string(),
+            "class A { function int f() { return 99999 ^ #1; } }".to_string(),
+            "function".to_string(),
+        ];
+        sources.extend((0..5).map(random_class));
+        for source in sources {
+            // Every prefix, so the parse stops in every part of the grammar
+            for end in (0..=source.len()).filter(|&end| source.is_char_boundary(end)) {
+                let source = &source[..end];
+                assert_eq!(check_parse(&cst::parse(source)), check_source(source), "{:?}", source);
+            }
+        }
+    }
+}
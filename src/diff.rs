@@ -0,0 +1,184 @@
+// Line-based unified diffs, in the format understood by patch and git apply.
+
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+// The unified diff from old to new, or an empty string when they are equal.
+pub fn unified_diff(old_name: &str, new_name: &str, old: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    let mut output = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&i| !matches!(lines[i], Line::Same(_)))
+        .collect();
+    let mut i = 0;
+    while i < changed.len() {
+        // Changes closer than twice the context share a hunk
+        let mut last = i;
+        while last + 1 < changed.len() && changed[last + 1] - changed[last] <= 2 * CONTEXT_LINES {
+            last += 1;
+        }
+        let start = changed[i].saturating_sub(CONTEXT_LINES);
+        let end = (changed[last] + CONTEXT_LINES + 1).min(lines.len());
+        output.push_str(&hunk(&lines, start, end));
+        i = last + 1;
+    }
+    output
+}
+
+fn hunk(lines: &[Line], start: usize, end: usize) -> String {
+    // Line numbers of the hunk's first line in the old and new text
+    let mut old_line = 1;
+    let mut new_line = 1;
+    for line in &lines[..start] {
+        match line {
+            Line::Same(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Line::Removed(_) => old_line += 1,
+            Line::Added(_) => new_line += 1,
+        }
+    }
+    let old_count = lines[start..end]
+        .iter()
+        .filter(|line| !matches!(line, Line::Added(_)))
+        .count();
+    let new_count = lines[start..end]
+        .iter()
+        .filter(|line| !matches!(line, Line::Removed(_)))
+        .count();
+    // An empty range is numbered by the line before it
+    let old_line = if old_count == 0 {
+        old_line - 1
+    } else {
+        old_line
+    };
+    let new_line = if new_count == 0 {
+        new_line - 1
+    } else {
+        new_line
+    };
+
+    let mut output = format!(
+        "@@ -{},{} +{},{} @@\n",
+        old_line, old_count, new_line, new_count
+    );
+    for line in &lines[start..end] {
+        let (marker, text) = match line {
+            Line::Same(text) => (' ', text),
+            Line::Removed(text) => ('-', text),
+            Line::Added(text) => ('+', text),
+        };
+        output.push(marker);
+        output.push_str(text);
+        if !text.ends_with('\n') {
+            output.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+    output
+}
+
+// A shortest edit script between the lines, from their longest common
+// subsequence after setting aside the common prefix and suffix.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // common[i][j] is the length of the longest common subsequence of
+    // old_middle[i..] and new_middle[j..]
+    let width = new_middle.len() + 1;
+    let mut common = vec![0u32; (old_middle.len() + 1) * width];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            common[i * width + j] = if old_middle[i] == new_middle[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            lines.push(Line::Same(old_middle[i]));
+            i += 1;
+            j += 1;
+        } else if j == new_middle.len()
+            || (i < old_middle.len() && common[(i + 1) * width + j] >= common[i * width + j + 1])
+        {
+            lines.push(Line::Removed(old_middle[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new_middle[j]));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| Line::Same(line)),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nl\nm";
+        assert_eq!(
+            unified_diff("a/x", "b/x", old, new),
+            "--- a/x
++++ b/x
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -8,5 +8,5 @@
+ h
+ i
+ j
+-k
+ l
++m
+\\ No newline at end of file
+"
+        );
+        assert_eq!(unified_diff("a", "b", old, old), "");
+        assert_eq!(
+            unified_diff("a", "b", "", "x\n"),
+            "--- a\n+++ b\n@@ -0,0 +1,1 @@\n+x\n"
+        );
+    }
+}
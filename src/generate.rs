@@ -0,0 +1,492 @@
+use crate::ast::{
+    parse_classes, BinaryOp, Class, ClassVarKind, Expression, KeywordConstant, Statement,
+    StatementKind, Subroutine, SubroutineCall, Term, Type, UnaryOp,
+};
+use crate::diagnostics::check_source;
+use crate::parser::parse;
+
+// Random syntactically valid Jack classes, with comments and irregular
+// whitespace between the tokens, for property tests of the tokeniser and the
+// parsers. Generation is deterministic for a given seed.
+
+const MAX_DEPTH: usize = 3;
+
+// SplitMix64
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+        choices[self.below(choices.len())]
+    }
+}
+
+struct Generator {
+    rng: Rng,
+    source: String,
+}
+
+pub fn random_class(seed: u64) -> String {
+    let mut generator = Generator {
+        rng: Rng(seed),
+        source: String::new(),
+    };
+    generator.class();
+    generator.source
+}
+
+impl Generator {
+    // Appends a token after whitespace, a comment or, where the tokens cannot
+    // run together, nothing at all.
+    fn token(&mut self, text: &str) {
+        let word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let joinable = !self.source.ends_with(word)
+            && !self.source.ends_with('/')
+            && !text.starts_with(word);
+        if !self.source.is_empty() {
+            let separator = match self.rng.below(10) {
+                0 | 1 if joinable => String::new(),
+                2 => "\n\t".to_string(),
+                3 => format!(" // {}\n", self.comment_text()),
+                4 => format!(" /* {} */ ", self.comment_text()),
+                5 => format!("\n/** {}\n * {} */\n", self.comment_text(), self.comment_text()),
+                _ => " ".to_string(),
+            };
+            self.source.push_str(&separator);
+        }
+        self.source.push_str(text);
+    }
+
+    fn comment_text(&mut self) -> String {
+        let words = ["note", "x < y", "\"quoted\"", "let", "/ slash", "* star", "é", "{ }", "#^~"];
+        (0..self.rng.below(4)).map(|_| self.rng.pick(&words)).collect::<Vec<&str>>().join(" ")
+    }
+
+    fn name(&mut self) -> String {
+        // Some look like keywords, but are not
+        let stems = ["x", "count", "classy", "if_", "_do", "returnValue", "a1", "thisOne", "nullable", "i"];
+        let stem = self.rng.pick(&stems);
+        match self.rng.below(3) {
+            0 => format!("{}{}", stem, self.rng.below(10)),
+            _ => stem.to_string(),
+        }
+    }
+
+    fn class_name(&mut self) -> String {
+        self.rng.pick(&["Main", "Square", "Array", "Output", "Game_2", "String"]).to_string()
+    }
+
+    fn var_type(&mut self) -> String {
+        match self.rng.below(4) {
+            0 => "int".to_string(),
+            1 => "char".to_string(),
+            2 => "boolean".to_string(),
+            _ => self.class_name(),
+        }
+    }
+
+    // name (, name)*
+    fn names(&mut self) {
+        let name = self.name();
+        self.token(&name);
+        for _ in 0..self.rng.below(3) {
+            self.token(",");
+            let name = self.name();
+            self.token(&name);
+        }
+    }
+
+    fn class(&mut self) {
+        self.token("class");
+        let name = self.class_name();
+        self.token(&name);
+        self.token("{");
+        for _ in 0..self.rng.below(3) {
+            let kind = self.rng.pick(&["static", "field"]);
+            self.token(kind);
+            let var_type = self.var_type();
+            self.token(&var_type);
+            self.names();
+            self.token(";");
+        }
+        for _ in 0..self.rng.below(4) {
+            self.subroutine();
+        }
+        self.token("}");
+    }
+
+    fn subroutine(&mut self) {
+        let kind = self.rng.pick(&["constructor", "function", "method"]);
+        self.token(kind);
+        let return_type = if self.rng.chance(30) {
+            "void".to_string()
+        } else {
+            self.var_type()
+        };
+        self.token(&return_type);
+        let name = self.name();
+        self.token(&name);
+        self.token("(");
+        for i in 0..self.rng.below(4) {
+            if i > 0 {
+                self.token(",");
+            }
+            let var_type = self.var_type();
+            self.token(&var_type);
+            let name = self.name();
+            self.token(&name);
+        }
+        self.token(")");
+        self.token("{");
+        for _ in 0..self.rng.below(3) {
+            self.token("var");
+            let var_type = self.var_type();
+            self.token(&var_type);
+            self.names();
+            self.token(";");
+        }
+        self.statements(0);
+        self.token("}");
+    }
+
+    fn statements(&mut self, depth: usize) {
+        for _ in 0..self.rng.below(5) {
+            self.statement(depth);
+        }
+    }
+
+    fn statement(&mut self, depth: usize) {
+        let choice = if depth < MAX_DEPTH { self.rng.below(7) } else { self.rng.below(4) };
+        match choice {
+            0 => {
+                self.token("let");
+                let name = self.name();
+                self.token(&name);
+                if self.rng.chance(30) {
+                    self.token("[");
+                    self.expression(depth + 1);
+                    self.token("]");
+                }
+                self.token("=");
+                self.expression(depth + 1);
+                self.token(";");
+            }
+            1 => {
+                self.token("do");
+                self.call(depth + 1);
+                self.token(";");
+            }
+            2 => {
+                self.token("return");
+                if self.rng.chance(50) {
+                    self.expression(depth + 1);
+                }
+                self.token(";");
+            }
+            3 => {
+                self.token("let");
+                let name = self.name();
+                self.token(&name);
+                self.token("=");
+                let string = self.string_constant();
+                self.token(&string);
+                self.token(";");
+            }
+            4 | 5 => {
+                let keyword = if choice == 4 { "if" } else { "while" };
+                self.token(keyword);
+                self.token("(");
+                self.expression(depth + 1);
+                self.token(")");
+                self.token("{");
+                self.statements(depth + 1);
+                self.token("}");
+                if choice == 4 && self.rng.chance(50) {
+                    self.token("else");
+                    self.token("{");
+                    self.statements(depth + 1);
+                    self.token("}");
+                }
+            }
+            _ => {
+                self.token("if");
+                self.token("(");
+                self.expression(depth + 1);
+                self.token(")");
+                self.token("{");
+                self.token("}");
+            }
+        }
+    }
+
+    fn string_constant(&mut self) -> String {
+        let pieces = ["a", " ", "//", "/*", "*/", "<&>", "\\", "'", "é", "123", "class"];
+        let text: String = (0..self.rng.below(5)).map(|_| self.rng.pick(&pieces)).collect();
+        format!("\"{}\"", text)
+    }
+
+    fn expression(&mut self, depth: usize) {
+        self.term(depth);
+        for _ in 0..self.rng.below(3) {
+            let op = self.rng.pick(&["+", "-", "*", "/", "&", "|", "<", ">", "=", "^", "#"]);
+            self.token(op);
+            self.term(depth);
+        }
+    }
+
+    fn term(&mut self, depth: usize) {
+        let choice = if depth < MAX_DEPTH { self.rng.below(9) } else { self.rng.below(4) };
+        match choice {
+            0 => {
+                let value = match self.rng.below(3) {
+                    0 => 0,
+                    1 => 32767,
+                    _ => self.rng.below(32768),
+                };
+                self.token(&value.to_string());
+            }
+            1 => {
+                let string = self.string_constant();
+                self.token(&string);
+            }
+            2 => {
+                let keyword = self.rng.pick(&["true", "false", "null", "this"]);
+                self.token(keyword);
+            }
+            3 => {
+                let name = self.name();
+                self.token(&name);
+            }
+            4 => {
+                let name = self.name();
+                self.token(&name);
+                self.token("[");
+                self.expression(depth + 1);
+                self.token("]");
+            }
+            5 | 6 => self.call(depth + 1),
+            7 => {
+                self.token("(");
+                self.expression(depth + 1);
+                self.token(")");
+            }
+            _ => {
+                let op = self.rng.pick(&["-", "~", "^", "#"]);
+                self.token(op);
+                self.term(depth + 1);
+            }
+        }
+    }
+
+    fn call(&mut self, depth: usize) {
+        if self.rng.chance(60) {
+            let receiver = if self.rng.chance(50) { self.class_name() } else { self.name() };
+            self.token(&receiver);
+            self.token(".");
+        }
+        let name = self.name();
+        self.token(&name);
+        self.token("(");
+        for i in 0..self.rng.below(3) {
+            if i > 0 {
+                self.token(",");
+            }
+            self.expression(depth);
+        }
+        self.token(")");
+    }
+}
+
+// Writes the classes back as Jack source in a regular layout, without comments.
+pub fn pretty_print(classes: &[Class]) -> String {
+    let mut output = String::new();
+    for class in classes {
+        output.push_str(&format!("class {} {{\n", class.name));
+        for dec in &class.class_vars {
+            let kind = match dec.kind {
+                ClassVarKind::Static => "static",
+                ClassVarKind::Field => "field",
+            };
+            output.push_str(&format!("    {} {} {};\n", kind, dec.var_type, join_names(&dec.names)));
+        }
+        for subroutine in &class.subroutines {
+            print_subroutine(subroutine, &mut output);
+        }
+        output.push_str("}\n");
+    }
+    output
+}
+
+fn join_names(names: &[crate::ast::Name]) -> String {
+    names.iter().map(|name| name.as_str()).collect::<Vec<&str>>().join(", ")
+}
+
+fn print_subroutine(subroutine: &Subroutine, output: &mut String) {
+    let return_type = subroutine
+        .return_type
+        .as_ref()
+        .map_or("void".to_string(), Type::to_string);
+    let parameters: Vec<String> = subroutine
+        .parameters
+        .iter()
+        .map(|parameter| format!("{} {}", parameter.var_type, parameter.name))
+        .collect();
+    output.push_str(&format!(
+        "    {} {} {}({}) {{\n",
+        subroutine.kind,
+        return_type,
+        subroutine.name,
+        parameters.join(", ")
+    ));
+    for dec in &subroutine.locals {
+        output.push_str(&format!("        var {} {};\n", dec.var_type, join_names(&dec.names)));
+    }
+    print_statements(&subroutine.statements, 2, output);
+    output.push_str("    }\n");
+}
+
+fn print_statements(statements: &[Statement], depth: usize, output: &mut String) {
+    let indent = "    ".repeat(depth);
+    for statement in statements {
+        match &statement.kind {
+            StatementKind::Let { name, index, value } => {
+                let index = index
+                    .as_ref()
+                    .map_or(String::new(), |index| format!("[{}]", print_expression(index)));
+                output.push_str(&format!("{}let {}{} = {};\n", indent, name, index, print_expression(value)));
+            }
+            StatementKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                output.push_str(&format!("{}if ({}) {{\n", indent, print_expression(condition)));
+                print_statements(then_branch, depth + 1, output);
+                if let Some(else_branch) = else_branch {
+                    output.push_str(&format!("{}}} else {{\n", indent));
+                    print_statements(else_branch, depth + 1, output);
+                }
+                output.push_str(&format!("{}}}\n", indent));
+            }
+            StatementKind::While { condition, body } => {
+                output.push_str(&format!("{}while ({}) {{\n", indent, print_expression(condition)));
+                print_statements(body, depth + 1, output);
+                output.push_str(&format!("{}}}\n", indent));
+            }
+            StatementKind::Do(call) => output.push_str(&format!("{}do {};\n", indent, print_call(call))),
+            StatementKind::Return(None) => output.push_str(&format!("{}return;\n", indent)),
+            StatementKind::Return(Some(value)) => {
+                output.push_str(&format!("{}return {};\n", indent, print_expression(value)))
+            }
+        }
+    }
+}
+
+fn print_expression(expression: &Expression) -> String {
+    let mut text = print_term(&expression.term);
+    for (op, operand) in &expression.rest {
+        let op = match op {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::LessThan => "<",
+            BinaryOp::GreaterThan => ">",
+            BinaryOp::Equals => "=",
+            BinaryOp::ShiftLeft => "^",
+            BinaryOp::ShiftRight => "#",
+        };
+        text.push_str(&format!(" {} {}", op, print_term(operand)));
+    }
+    text
+}
+
+fn print_term(term: &Term) -> String {
+    match term {
+        Term::IntegerConstant(value) => value.to_string(),
+        Term::StringConstant(value) => format!("\"{}\"", value),
+        Term::KeywordConstant(KeywordConstant::True) => "true".to_string(),
+        Term::KeywordConstant(KeywordConstant::False) => "false".to_string(),
+        Term::KeywordConstant(KeywordConstant::Null) => "null".to_string(),
+        Term::KeywordConstant(KeywordConstant::This) => "this".to_string(),
+        Term::Variable(name) => name.to_string(),
+        Term::ArrayEntry(name, index) => format!("{}[{}]", name, print_expression(index)),
+        Term::Call(call) => print_call(call),
+        Term::Parenthesised(inner) => format!("({})", print_expression(inner)),
+        Term::Unary(op, operand) => {
+            let op = match op {
+                UnaryOp::Negate => "-",
+                UnaryOp::Not => "~",
+                UnaryOp::ShiftLeft => "^",
+                UnaryOp::ShiftRight => "#",
+            };
+            format!("{}{}", op, print_term(operand))
+        }
+    }
+}
+
+fn print_call(call: &SubroutineCall) -> String {
+    let arguments: Vec<String> = call.arguments.iter().map(print_expression).collect();
+    match &call.receiver {
+        Some(receiver) => format!("{}.{}({})", receiver, call.name, arguments.join(", ")),
+        None => format!("{}({})", call.name, arguments.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_print_parse_is_stable() {
+        for seed in 0..300 {
+            let source = random_class(seed);
+            let classes = parse_classes(source.clone())
+                .unwrap_or_else(|e| panic!("seed {}: {}\n{}", seed, e, source));
+            let printed = pretty_print(&classes);
+            // The printed program has the same parse tree as the generated one
+            let expected = parse(source.clone()).unwrap_or_else(|e| panic!("seed {}: {}\n{}", seed, e, source));
+            assert_eq!(parse(printed.clone()), Ok(expected), "seed {}:\n{}", seed, source);
+            let reprinted = pretty_print(&parse_classes(printed.clone()).unwrap());
+            assert_eq!(reprinted, printed, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_tokeniser_never_panics() {
+        let mut rng = Rng(7);
+        let alphabet = b"classvarletdo(){}[].,;+-*/&|<>=~^#\"\n\t /**/0123456789x_\xC3\xA9\xFF\x00";
+        for _ in 0..2000 {
+            let length = rng.below(120);
+            let bytes: Vec<u8> = (0..length)
+                .map(|_| match rng.below(4) {
+                    0 => rng.next() as u8,
+                    _ => alphabet[rng.below(alphabet.len())],
+                })
+                .collect();
+            check_source(&String::from_utf8_lossy(&bytes));
+        }
+        // Every prefix of a valid program, as an editor sees it while typing
+        let source = random_class(11);
+        for (end, _) in source.char_indices() {
+            check_source(&source[..end]);
+        }
+    }
+}
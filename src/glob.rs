@@ -0,0 +1,78 @@
+// Glob patterns choosing which files under a directory are inputs. In a
+// pattern "*" matches any characters but "/", "**" matches any characters
+// including "/", and "?" matches one character other than "/". A pattern with
+// no "/" is matched against the file name alone, as in `--exclude 'Test*'`,
+// and any other against the path relative to the directory.
+
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let path = if pattern.contains('/') {
+        path
+    } else {
+        path.rsplit('/').next().unwrap_or(path)
+    };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    matches_from(&pattern, &path)
+}
+
+fn matches_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // "**/" also matches no directories at all
+            let rest_after_slash = rest.strip_prefix(&['/']).unwrap_or(rest);
+            matches_from(rest_after_slash, path)
+                || (0..=path.len()).any(|skipped| matches_from(rest, &path[skipped..]))
+        }
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&skipped| !path[..skipped].contains(&'/'))
+            .any(|skipped| matches_from(rest, &path[skipped..])),
+        ['?', rest @ ..] => matches!(path, [c, ..] if *c != '/') && matches_from(rest, &path[1..]),
+        [c, rest @ ..] => path.first() == Some(c) && matches_from(rest, &path[1..]),
+    }
+}
+
+// A file is chosen when it matches one of the include patterns, or there are
+// none, and none of the exclude patterns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Filter {
+    pub fn accepts(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|pattern| matches(pattern, path)))
+            && !self.exclude.iter().any(|pattern| matches(pattern, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*.jack", "Main.jack"));
+        assert!(matches("*.jack", "src/game/Main.jack"));
+        assert!(!matches("*.jack", "Main.xml"));
+        assert!(matches("Test?.jack", "lib/Test1.jack"));
+        assert!(!matches("Test?.jack", "Test12.jack"));
+        assert!(matches("src/*.jack", "src/Main.jack"));
+        assert!(!matches("src/*.jack", "src/game/Main.jack"));
+        assert!(matches("src/**/*.jack", "src/game/level/Main.jack"));
+        assert!(matches("src/**/*.jack", "src/Main.jack"));
+        assert!(matches("**/vendor/**", "a/vendor/b/C.jack"));
+        assert!(matches("vendor/**", "vendor/C.jack"));
+        assert!(!matches("vendor/**", "src/vendor/C.jack"));
+
+        let filter = Filter {
+            include: vec!["src/**".to_string()],
+            exclude: vec!["*Test.jack".to_string()],
+        };
+        assert!(filter.accepts("src/Main.jack"));
+        assert!(!filter.accepts("src/MainTest.jack"));
+        assert!(!filter.accepts("lib/Math.jack"));
+        assert!(Filter::default().accepts("anything"));
+    }
+}
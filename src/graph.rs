@@ -0,0 +1,450 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::{Class, Expression, Statement, StatementKind, SubroutineCall, Term, Type};
+use crate::json::Json;
+
+// The call graph of a program and the graph of which classes depend on which.
+// Calls are resolved the way the compiler resolves them: a receiver is a
+// variable of some class type, or else a class name, and a call without a
+// receiver is a method or function of the calling class. Subroutines and
+// classes outside the program, such as the Jack OS, appear as external nodes.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    // <class>.<subroutine> for subroutines, the class name for classes
+    pub name: String,
+    // Not declared in the program
+    pub external: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: BTreeSet<(usize, usize)>,
+    index: HashMap<String, usize>,
+}
+
+impl Graph {
+    fn node(&mut self, name: &str, external: bool) -> usize {
+        if let Some(&id) = self.index.get(name) {
+            return id;
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            external,
+        });
+        self.index.insert(name.to_string(), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+
+    fn successors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .range((node, 0)..(node + 1, 0))
+            .map(|&(_, to)| to)
+    }
+
+    // Groups of nodes that can reach themselves, each in node order: mutually
+    // recursive nodes together and directly recursive nodes alone.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: vec![None; self.nodes.len()],
+            low: vec![0; self.nodes.len()],
+            stack: vec![],
+            on_stack: vec![false; self.nodes.len()],
+            next: 0,
+            components: vec![],
+        };
+        for node in 0..self.nodes.len() {
+            if tarjan.index[node].is_none() {
+                tarjan.visit(node);
+            }
+        }
+        let mut cycles: Vec<Vec<usize>> = tarjan
+            .components
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.edges.contains(&(component[0], component[0]))
+            })
+            .map(|mut component| {
+                component.sort();
+                component
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    // Whether each node can be reached from the root
+    pub fn reachable_from(&self, root: usize) -> Vec<bool> {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            if !reachable[node] {
+                reachable[node] = true;
+                stack.extend(self.successors(node));
+            }
+        }
+        reachable
+    }
+
+    // Highlights the edges within cycles and the nodes in `unused`.
+    pub fn to_dot(&self, name: &str, unused: &[usize]) -> String {
+        let cycles = self.cycles();
+        let mut dot = format!("digraph {} {{\n  node [shape=box];\n", name);
+        for (id, node) in self.nodes.iter().enumerate() {
+            let style = if node.external {
+                ", style=dashed"
+            } else if unused.contains(&id) {
+                ", style=filled, fillcolor=lightgrey"
+            } else {
+                ""
+            };
+            dot.push_str(&format!("  n{} [label=\"{}\"{}];\n", id, node.name, style));
+        }
+        for &(from, to) in &self.edges {
+            let in_cycle = cycles
+                .iter()
+                .any(|cycle| cycle.contains(&from) && cycle.contains(&to));
+            let style = if in_cycle { " [color=red]" } else { "" };
+            dot.push_str(&format!("  n{} -> n{}{};\n", from, to, style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn to_json(&self) -> Json {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                Json::object(vec![
+                    ("name", node.name.as_str().into()),
+                    ("external", node.external.into()),
+                ])
+            })
+            .collect::<Vec<Json>>();
+        let edges = self
+            .edges
+            .iter()
+            .map(|&(from, to)| {
+                Json::object(vec![
+                    ("from", self.nodes[from].name.as_str().into()),
+                    ("to", self.nodes[to].name.as_str().into()),
+                ])
+            })
+            .collect::<Vec<Json>>();
+        Json::object(vec![("nodes", nodes.into()), ("edges", edges.into())])
+    }
+}
+
+struct Tarjan<'a> {
+    graph: &'a Graph,
+    index: Vec<Option<usize>>,
+    low: Vec<usize>,
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    next: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, node: usize) {
+        self.index[node] = Some(self.next);
+        self.low[node] = self.next;
+        self.next += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+        for successor in self.graph.successors(node) {
+            match self.index[successor] {
+                None => {
+                    self.visit(successor);
+                    self.low[node] = self.low[node].min(self.low[successor]);
+                }
+                Some(index) if self.on_stack[successor] => {
+                    self.low[node] = self.low[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+        if Some(self.low[node]) == self.index[node] {
+            let mut component = vec![];
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+pub struct ProgramGraphs {
+    pub calls: Graph,
+    pub classes: Graph,
+}
+
+impl ProgramGraphs {
+    pub fn new(classes: &[Class]) -> ProgramGraphs {
+        let mut graphs = ProgramGraphs {
+            calls: Graph::default(),
+            classes: Graph::default(),
+        };
+        // Declare the program first so that it comes before external nodes
+        for class in classes {
+            graphs.classes.node(&class.name, false);
+            for subroutine in &class.subroutines {
+                graphs
+                    .calls
+                    .node(&format!("{}.{}", class.name, subroutine.name), false);
+            }
+        }
+        for class in classes {
+            let mut types: Vec<&Type> = class.class_vars.iter().map(|dec| &dec.var_type).collect();
+            for subroutine in &class.subroutines {
+                types.extend(&subroutine.return_type);
+                types.extend(subroutine.parameters.iter().map(|parameter| &parameter.var_type));
+                types.extend(subroutine.locals.iter().map(|dec| &dec.var_type));
+
+                let mut scope = Scope {
+                    class: &class.name,
+                    types: HashMap::new(),
+                    calls: vec![],
+                };
+                for dec in &class.class_vars {
+                    for name in &dec.names {
+                        scope.types.insert(name, &dec.var_type);
+                    }
+                }
+                for parameter in &subroutine.parameters {
+                    scope.types.insert(&parameter.name, &parameter.var_type);
+                }
+                for dec in &subroutine.locals {
+                    for name in &dec.names {
+                        scope.types.insert(name, &dec.var_type);
+                    }
+                }
+                scope.statements(&subroutine.statements);
+
+                let caller = graphs
+                    .calls
+                    .node(&format!("{}.{}", class.name, subroutine.name), false);
+                for (callee_class, callee) in scope.calls {
+                    let callee = graphs.calls.node(&format!("{}.{}", callee_class, callee), true);
+                    graphs.calls.edges.insert((caller, callee));
+                    graphs.depend(&class.name, &callee_class);
+                }
+            }
+            for var_type in types {
+                if let Type::Class(name) = var_type {
+                    graphs.depend(&class.name, name);
+                }
+            }
+        }
+        graphs
+    }
+
+    fn depend(&mut self, class: &str, dependency: &str) {
+        if class != dependency {
+            let from = self.classes.node(class, false);
+            let to = self.classes.node(dependency, true);
+            self.classes.edges.insert((from, to));
+        }
+    }
+
+    // The subroutines of the program that Main.main never calls, directly or
+    // indirectly, or None when the program has no Main.main.
+    pub fn unused(&self) -> Option<Vec<usize>> {
+        let main = self.calls.find("Main.main")?;
+        let reachable = self.calls.reachable_from(main);
+        Some(
+            (0..self.calls.nodes.len())
+                .filter(|&node| !reachable[node] && !self.calls.nodes[node].external)
+                .collect(),
+        )
+    }
+
+    pub fn to_json(&self) -> Json {
+        let names = |nodes: &[usize]| -> Json {
+            nodes
+                .iter()
+                .map(|&node| self.calls.nodes[node].name.as_str().into())
+                .collect::<Vec<Json>>()
+                .into()
+        };
+        let cycles: Vec<Json> = self.calls.cycles().iter().map(|cycle| names(cycle)).collect();
+        Json::object(vec![
+            ("calls", self.calls.to_json()),
+            ("classes", self.classes.to_json()),
+            ("recursion", cycles.into()),
+            (
+                "unused",
+                self.unused().map_or(Json::Null, |unused| names(&unused)),
+            ),
+        ])
+    }
+}
+
+// The types of the names visible in a subroutine, and the calls it makes as
+// (class, subroutine) pairs.
+struct Scope<'a> {
+    class: &'a str,
+    types: HashMap<&'a str, &'a Type>,
+    calls: Vec<(String, String)>,
+}
+
+impl Scope<'_> {
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::Let { index, value, .. } => {
+                    if let Some(index) = index {
+                        self.expression(index);
+                    }
+                    self.expression(value);
+                }
+                StatementKind::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                } => {
+                    self.expression(condition);
+                    self.statements(then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.statements(else_branch);
+                    }
+                }
+                StatementKind::While { condition, body } => {
+                    self.expression(condition);
+                    self.statements(body);
+                }
+                StatementKind::Do(call) => self.call(call),
+                StatementKind::Return(Some(value)) => self.expression(value),
+                StatementKind::Return(None) => {}
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        self.term(&expression.term);
+        for (_, term) in &expression.rest {
+            self.term(term);
+        }
+    }
+
+    fn term(&mut self, term: &Term) {
+        match term {
+            Term::ArrayEntry(_, index) => self.expression(index),
+            Term::Call(call) => self.call(call),
+            Term::Parenthesised(inner) => self.expression(inner),
+            Term::Unary(_, inner) => self.term(inner),
+            Term::IntegerConstant(_)
+            | Term::StringConstant(_)
+            | Term::KeywordConstant(_)
+            | Term::Variable(_) => {}
+        }
+    }
+
+    fn call(&mut self, call: &SubroutineCall) {
+        let class = match &call.receiver {
+            None => Some(self.class.to_string()),
+            Some(receiver) => match self.types.get(receiver.as_str()) {
+                Some(Type::Class(class)) => Some(class.to_string()),
+                // Methods cannot be called on int, char or boolean variables
+                Some(_) => None,
+                None => Some(receiver.to_string()),
+            },
+        };
+        if let Some(class) = class {
+            self.calls.push((class, call.name.to_string()));
+        }
+        for argument in &call.arguments {
+            self.expression(argument);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parse_classes;
+
+    #[test]
+    fn test_calls_cycles_and_unused() {
+        let classes = parse_classes(
+            "class Main {
+  function void main() {
+    var Counter c;
+    let c = Counter.new();
+    do c.count(3);
+    do Main.even(4);
+    return;
+  }
+  function boolean even(int n) { if (n = 0) { return true; } return Main.odd(n - 1); }
+  function boolean odd(int n) { if (n = 0) { return false; } return even(n - 1); }
+}
+class Counter {
+  field Array values;
+  constructor Counter new() { let values = Array.new(10); return this; }
+  method void count(int n) { if (n > 0) { do count(n - 1); } return; }
+  method void reset() { do Memory.deAlloc(values); return; }
+}"
+            .to_string(),
+        )
+        .unwrap();
+        let graphs = ProgramGraphs::new(&classes);
+        let names = |graph: &Graph, nodes: &[usize]| -> Vec<String> {
+            nodes.iter().map(|&node| graph.nodes[node].name.clone()).collect()
+        };
+        let edges = |graph: &Graph| -> Vec<String> {
+            graph
+                .edges
+                .iter()
+                .map(|&(from, to)| format!("{} -> {}", graph.nodes[from].name, graph.nodes[to].name))
+                .collect()
+        };
+        assert_eq!(
+            edges(&graphs.calls),
+            [
+                "Main.main -> Main.even",
+                "Main.main -> Counter.new",
+                "Main.main -> Counter.count",
+                "Main.even -> Main.odd",
+                "Main.odd -> Main.even",
+                "Counter.new -> Array.new",
+                "Counter.count -> Counter.count",
+                "Counter.reset -> Memory.deAlloc",
+            ]
+        );
+        assert_eq!(
+            edges(&graphs.classes),
+            ["Main -> Counter", "Counter -> Array", "Counter -> Memory"]
+        );
+        assert!(graphs.classes.nodes[2].external);
+        let cycles: Vec<Vec<String>> = graphs
+            .calls
+            .cycles()
+            .iter()
+            .map(|cycle| names(&graphs.calls, cycle))
+            .collect();
+        assert_eq!(cycles, [vec!["Main.even", "Main.odd"], vec!["Counter.count"]]);
+        assert_eq!(names(&graphs.calls, &graphs.unused().unwrap()), ["Counter.reset"]);
+
+        let json = graphs.to_json();
+        assert_eq!(
+            json.get("unused").unwrap().to_string(),
+            r#"["Counter.reset"]"#
+        );
+        assert_eq!(json.at(&["classes", "nodes"]).unwrap().as_array().unwrap().len(), 4);
+        let dot = graphs.calls.to_dot("calls", &graphs.unused().unwrap());
+        assert!(dot.contains("  n1 -> n2 [color=red];\n"));
+        assert!(dot.contains("  n5 [label=\"Counter.reset\", style=filled, fillcolor=lightgrey];\n"));
+    }
+}
@@ -0,0 +1,886 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use crate::ast::{
+    BinaryOp, Class, ClassVarKind, Expression, KeywordConstant, Statement, StatementKind,
+    Subroutine, SubroutineCall, SubroutineKind, Term, Type, UnaryOp,
+};
+
+// Evaluates parsed Jack classes directly, without generating any code.
+//
+// Values are 16-bit words and all arithmetic wraps. Objects, arrays and strings
+// are allocated on a simulated RAM laid out like the Hack platform's, the same
+// way c_runtime.c does it, so Memory.peek/poke and array arithmetic behave as
+// on the emulator. The Jack OS classes are implemented in Rust below; a class
+// loaded with the same name as an OS class takes precedence over the built-in.
+
+const RAM_SIZE: usize = 32768;
+const HEAP_BASE: i16 = 2048;
+const TRUE: i16 = -1;
+const FALSE: i16 = 0;
+const MAX_CALL_DEPTH: usize = 8192;
+
+// Why evaluation stopped before reaching the end of the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interrupt {
+    // Sys.halt was called
+    Halt,
+    Error(String),
+}
+
+impl From<String> for Interrupt {
+    fn from(message: String) -> Self {
+        Interrupt::Error(message)
+    }
+}
+
+type Eval<T> = Result<T, Interrupt>;
+
+// The variables of one subroutine activation.
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    class: Option<String>,
+    kind: Option<SubroutineKind>,
+    this: i16,
+    variables: Vec<(String, Type, i16)>,
+}
+
+impl Frame {
+    // A frame that is not inside any class, used for evaluating loose
+    // statements such as those typed into the REPL.
+    pub fn top_level() -> Self {
+        Self::default()
+    }
+
+    pub fn declare(&mut self, name: &str, var_type: Type) {
+        self.variables.retain(|(existing, _, _)| existing != name);
+        self.variables.push((name.to_string(), var_type, 0));
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &Type, i16)> {
+        self.variables
+            .iter()
+            .map(|(name, var_type, value)| (name.as_str(), var_type, *value))
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.variables.iter().rposition(|(existing, _, _)| existing == name)
+    }
+}
+
+struct LoadedClass {
+    class: Rc<Class>,
+    fields: Vec<(String, Type)>,
+    statics: Vec<(String, Type, i16)>,
+}
+
+enum Place {
+    Frame(usize),
+    Field(i16),
+    Static(String, usize),
+}
+
+pub struct Interpreter<R: BufRead, W: Write> {
+    classes: HashMap<String, LoadedClass>,
+    ram: Vec<i16>,
+    free_list: i16,
+    depth: usize,
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        let mut ram = vec![0; RAM_SIZE];
+        // Free blocks are laid out as [total size, next free block]
+        ram[HEAP_BASE as usize] = (RAM_SIZE - HEAP_BASE as usize) as i16;
+        ram[HEAP_BASE as usize + 1] = 0;
+        Self {
+            classes: HashMap::new(),
+            ram,
+            free_list: HEAP_BASE,
+            depth: 0,
+            input,
+            output,
+        }
+    }
+
+    // Makes a class callable. Reloading a class replaces its subroutines and
+    // resets its static variables.
+    pub fn load_class(&mut self, class: Class) {
+        let mut fields = vec![];
+        let mut statics = vec![];
+        for dec in &class.class_vars {
+            for name in &dec.names {
+                match dec.kind {
+                    ClassVarKind::Field => fields.push((name.to_string(), dec.var_type.clone())),
+                    ClassVarKind::Static => {
+                        statics.push((name.to_string(), dec.var_type.clone(), 0))
+                    }
+                }
+            }
+        }
+        self.classes.insert(
+            class.name.to_string(),
+            LoadedClass {
+                class: Rc::new(class),
+                fields,
+                statics,
+            },
+        );
+    }
+
+    pub fn input(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    pub fn output(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    // Runs the program the way the Jack OS does: Sys.init if the program
+    // provides its own, otherwise Main.main.
+    pub fn run_main(&mut self) -> Eval<()> {
+        let (class, name) = if self.classes.contains_key("Sys") {
+            ("Sys", "init")
+        } else {
+            ("Main", "main")
+        };
+        if !self.classes.contains_key(class) {
+            return Err(Interrupt::Error("Program has no Main.main function".to_string()));
+        }
+        let result = match self.call_function(class, name, vec![]) {
+            Ok(_) | Err(Interrupt::Halt) => Ok(()),
+            Err(e) => Err(e),
+        };
+        self.output.flush().map_err(|e| e.to_string())?;
+        result
+    }
+
+    pub fn execute(&mut self, frame: &mut Frame, statements: &[Statement]) -> Eval<Option<i16>> {
+        for statement in statements {
+            if let Some(value) = self.execute_statement(frame, statement)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    // Returns Some(value) when the statement returned from the subroutine.
+    fn execute_statement(&mut self, frame: &mut Frame, statement: &Statement) -> Eval<Option<i16>> {
+        match &statement.kind {
+            StatementKind::Let { name, index, value } => {
+                let place = self.place(frame, name)?;
+                match index {
+                    Some(index) => {
+                        let base = self.load(frame, &place);
+                        let address = base.wrapping_add(self.evaluate(frame, index)?);
+                        let value = self.evaluate(frame, value)?;
+                        self.poke(address, value)?;
+                    }
+                    None => {
+                        let value = self.evaluate(frame, value)?;
+                        self.store(frame, &place, value)?;
+                    }
+                }
+            }
+            StatementKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate(frame, condition)? != FALSE {
+                    return self.execute(frame, then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute(frame, else_branch);
+                }
+            }
+            StatementKind::While { condition, body } => {
+                while self.evaluate(frame, condition)? != FALSE {
+                    if let Some(value) = self.execute(frame, body)? {
+                        return Ok(Some(value));
+                    }
+                }
+            }
+            StatementKind::Do(call) => {
+                self.call(frame, call)?;
+            }
+            StatementKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.evaluate(frame, value)?,
+                    None => 0,
+                };
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn evaluate(&mut self, frame: &mut Frame, expression: &Expression) -> Eval<i16> {
+        let mut value = self.evaluate_term(frame, &expression.term)?;
+        for (op, term) in &expression.rest {
+            let rhs = self.evaluate_term(frame, term)?;
+            value = match op {
+                BinaryOp::Add => value.wrapping_add(rhs),
+                BinaryOp::Subtract => value.wrapping_sub(rhs),
+                BinaryOp::Multiply => value.wrapping_mul(rhs),
+                BinaryOp::Divide => {
+                    if rhs == 0 {
+                        return self.sys_error(3);
+                    }
+                    value.wrapping_div(rhs)
+                }
+                BinaryOp::And => value & rhs,
+                BinaryOp::Or => value | rhs,
+                BinaryOp::LessThan => truth(value < rhs),
+                BinaryOp::GreaterThan => truth(value > rhs),
+                BinaryOp::Equals => truth(value == rhs),
+                BinaryOp::ShiftLeft => shift_left(value, rhs),
+                BinaryOp::ShiftRight => shift_right(value, rhs),
+            };
+        }
+        Ok(value)
+    }
+
+    fn evaluate_term(&mut self, frame: &mut Frame, term: &Term) -> Eval<i16> {
+        match term {
+            Term::IntegerConstant(value) => Ok(*value as i16),
+            Term::StringConstant(value) => self.new_string(value),
+            Term::KeywordConstant(KeywordConstant::True) => Ok(TRUE),
+            Term::KeywordConstant(KeywordConstant::False | KeywordConstant::Null) => Ok(FALSE),
+            Term::KeywordConstant(KeywordConstant::This) => match frame.kind {
+                Some(SubroutineKind::Method | SubroutineKind::Constructor) => Ok(frame.this),
+                _ => Err(Interrupt::Error("'this' used outside of a method".to_string())),
+            },
+            Term::Variable(name) => {
+                let place = self.place(frame, name)?;
+                Ok(self.load(frame, &place))
+            }
+            Term::ArrayEntry(name, index) => {
+                let place = self.place(frame, name)?;
+                let base = self.load(frame, &place);
+                let address = base.wrapping_add(self.evaluate(frame, index)?);
+                self.peek(address)
+            }
+            Term::Call(call) => self.call(frame, call),
+            Term::Parenthesised(inner) => self.evaluate(frame, inner),
+            Term::Unary(op, inner) => {
+                let value = self.evaluate_term(frame, inner)?;
+                Ok(match op {
+                    UnaryOp::Negate => value.wrapping_neg(),
+                    UnaryOp::Not => !value,
+                    UnaryOp::ShiftLeft => shift_left(value, 1),
+                    UnaryOp::ShiftRight => shift_right(value, 1),
+                })
+            }
+        }
+    }
+
+    // Locals and arguments shadow the class's fields and statics.
+    fn place(&self, frame: &Frame, name: &str) -> Eval<Place> {
+        if let Some(index) = frame.find(name) {
+            return Ok(Place::Frame(index));
+        }
+        if let Some(loaded) = frame.class.as_ref().and_then(|class| self.classes.get(class)) {
+            if let Some(offset) = loaded.fields.iter().position(|(field, _)| field == name) {
+                if frame.kind == Some(SubroutineKind::Function) {
+                    return Err(Interrupt::Error(format!(
+                        "Field {} used in a function of {}",
+                        name, loaded.class.name
+                    )));
+                }
+                return Ok(Place::Field(offset as i16));
+            }
+            if let Some(index) = loaded.statics.iter().position(|(s, _, _)| s == name) {
+                return Ok(Place::Static(loaded.class.name.to_string(), index));
+            }
+        }
+        Err(Interrupt::Error(format!("Undefined variable {}", name)))
+    }
+
+    fn place_type(&self, frame: &Frame, place: &Place) -> Type {
+        match place {
+            Place::Frame(index) => frame.variables[*index].1.clone(),
+            Place::Field(offset) => {
+                let class = &self.classes[frame.class.as_ref().unwrap()];
+                class.fields[*offset as usize].1.clone()
+            }
+            Place::Static(class, index) => self.classes[class].statics[*index].1.clone(),
+        }
+    }
+
+    fn load(&self, frame: &Frame, place: &Place) -> i16 {
+        match place {
+            Place::Frame(index) => frame.variables[*index].2,
+            Place::Field(offset) => {
+                let address = frame.this.wrapping_add(*offset);
+                if address < 0 {
+                    0
+                } else {
+                    self.ram[address as usize]
+                }
+            }
+            Place::Static(class, index) => self.classes[class].statics[*index].2,
+        }
+    }
+
+    fn store(&mut self, frame: &mut Frame, place: &Place, value: i16) -> Eval<()> {
+        match place {
+            Place::Frame(index) => frame.variables[*index].2 = value,
+            Place::Field(offset) => self.poke(frame.this.wrapping_add(*offset), value)?,
+            Place::Static(class, index) => {
+                self.classes.get_mut(class).unwrap().statics[*index].2 = value
+            }
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, frame: &mut Frame, call: &SubroutineCall) -> Eval<i16> {
+        let mut arguments = vec![];
+        let class_name = match &call.receiver {
+            None => {
+                let class_name = match &frame.class {
+                    Some(class_name) => class_name.clone(),
+                    None => {
+                        return Err(Interrupt::Error(format!(
+                            "Subroutine {} called outside of a class",
+                            call.name
+                        )))
+                    }
+                };
+                let kind = self.classes[&class_name]
+                    .class
+                    .subroutines
+                    .iter()
+                    .find(|subroutine| subroutine.name.text == call.name.text)
+                    .map(|subroutine| subroutine.kind);
+                if kind == Some(SubroutineKind::Method) {
+                    if frame.kind == Some(SubroutineKind::Function) {
+                        return Err(Interrupt::Error(format!(
+                            "Method {}.{} called from a function",
+                            class_name, call.name
+                        )));
+                    }
+                    arguments.push(frame.this);
+                }
+                class_name
+            }
+            Some(receiver) => match self.place(frame, receiver) {
+                Ok(place) => match self.place_type(frame, &place) {
+                    Type::Class(class_name) => {
+                        arguments.push(self.load(frame, &place));
+                        class_name.to_string()
+                    }
+                    other => {
+                        return Err(Interrupt::Error(format!(
+                            "Cannot call {} on {} of type {}",
+                            call.name, receiver, other
+                        )))
+                    }
+                },
+                Err(_) => receiver.to_string(),
+            },
+        };
+        for argument in &call.arguments {
+            arguments.push(self.evaluate(frame, argument)?);
+        }
+        self.call_function(&class_name, &call.name, arguments)
+    }
+
+    // Calls class_name.name, with the receiver as the first argument for methods.
+    pub fn call_function(&mut self, class_name: &str, name: &str, arguments: Vec<i16>) -> Eval<i16> {
+        let subroutine = self.classes.get(class_name).and_then(|loaded| {
+            let class = Rc::clone(&loaded.class);
+            let index = class.subroutines.iter().position(|s| s.name == name)?;
+            Some((class, index))
+        });
+        match subroutine {
+            Some((class, index)) => self.invoke(&class, &class.subroutines[index], arguments),
+            None if self.classes.contains_key(class_name) => Err(Interrupt::Error(format!(
+                "Class {} has no subroutine {}",
+                class_name, name
+            ))),
+            None => self.call_builtin(class_name, name, &arguments),
+        }
+    }
+
+    fn invoke(&mut self, class: &Class, subroutine: &Subroutine, arguments: Vec<i16>) -> Eval<i16> {
+        let mut arguments = arguments.into_iter();
+        let this = match subroutine.kind {
+            SubroutineKind::Method => arguments.next().unwrap_or(0),
+            SubroutineKind::Constructor => {
+                let size = self.classes[class.name.as_str()].fields.len() as i16;
+                self.alloc(size)?
+            }
+            SubroutineKind::Function => 0,
+        };
+        let arguments: Vec<i16> = arguments.collect();
+        if arguments.len() != subroutine.parameters.len() {
+            return Err(Interrupt::Error(format!(
+                "{}.{} expects {} arguments, got {}",
+                class.name,
+                subroutine.name,
+                subroutine.parameters.len(),
+                arguments.len()
+            )));
+        }
+
+        let mut frame = Frame {
+            class: Some(class.name.to_string()),
+            kind: Some(subroutine.kind),
+            this,
+            variables: vec![],
+        };
+        for (parameter, value) in subroutine.parameters.iter().zip(arguments) {
+            frame.declare(&parameter.name, parameter.var_type.clone());
+            let last = frame.variables.len() - 1;
+            frame.variables[last].2 = value;
+        }
+        for dec in &subroutine.locals {
+            for name in &dec.names {
+                frame.declare(name, dec.var_type.clone());
+            }
+        }
+
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(Interrupt::Error(format!(
+                "Call stack exceeded {} frames in {}.{}",
+                MAX_CALL_DEPTH, class.name, subroutine.name
+            )));
+        }
+        self.depth += 1;
+        let result = self.execute(&mut frame, &subroutine.statements);
+        self.depth -= 1;
+        Ok(result?.unwrap_or(0))
+    }
+
+    fn call_builtin(&mut self, class_name: &str, name: &str, args: &[i16]) -> Eval<i16> {
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0);
+        let value = match (class_name, name) {
+            ("Math", "init") => 0,
+            ("Math", "abs") => arg(0).wrapping_abs(),
+            ("Math", "multiply") => arg(0).wrapping_mul(arg(1)),
+            ("Math", "divide") => {
+                if arg(1) == 0 {
+                    return self.sys_error(3);
+                }
+                arg(0).wrapping_div(arg(1))
+            }
+            ("Math", "min") => arg(0).min(arg(1)),
+            ("Math", "max") => arg(0).max(arg(1)),
+            ("Math", "sqrt") => {
+                if arg(0) < 0 {
+                    return self.sys_error(4);
+                }
+                (arg(0) as f64).sqrt() as i16
+            }
+
+            ("Memory", "init") => 0,
+            ("Memory", "peek") => self.peek(arg(0))?,
+            ("Memory", "poke") => {
+                self.poke(arg(0), arg(1))?;
+                0
+            }
+            ("Memory", "alloc") => self.alloc(arg(0))?,
+            ("Memory", "deAlloc") => self.de_alloc(arg(0)),
+
+            ("Array", "new") => {
+                if arg(0) <= 0 {
+                    return self.sys_error(2);
+                }
+                self.alloc(arg(0))?
+            }
+            ("Array", "dispose") | ("String", "dispose") => self.de_alloc(arg(0)),
+
+            ("String", "new") => {
+                if arg(0) < 0 {
+                    return self.sys_error(14);
+                }
+                let s = self.alloc(arg(0).wrapping_add(2))?;
+                self.poke(s, arg(0))?;
+                self.poke(s + 1, 0)?;
+                s
+            }
+            ("String", "length") => self.peek(arg(0).wrapping_add(1))?,
+            ("String", "charAt") => {
+                if arg(1) < 0 || arg(1) >= self.peek(arg(0).wrapping_add(1))? {
+                    return self.sys_error(15);
+                }
+                self.peek(arg(0).wrapping_add(2).wrapping_add(arg(1)))?
+            }
+            ("String", "setCharAt") => {
+                if arg(1) < 0 || arg(1) >= self.peek(arg(0).wrapping_add(1))? {
+                    return self.sys_error(16);
+                }
+                self.poke(arg(0).wrapping_add(2).wrapping_add(arg(1)), arg(2))?;
+                0
+            }
+            ("String", "appendChar") => {
+                self.append_char(arg(0), arg(1))?;
+                arg(0)
+            }
+            ("String", "eraseLastChar") => {
+                let length = self.peek(arg(0).wrapping_add(1))?;
+                if length == 0 {
+                    return self.sys_error(18);
+                }
+                self.poke(arg(0).wrapping_add(1), length - 1)?;
+                0
+            }
+            ("String", "intValue") => {
+                let text = self.read_string(arg(0))?;
+                let (negative, digits) = match text.strip_prefix('-') {
+                    Some(digits) => (true, digits),
+                    None => (false, text.as_str()),
+                };
+                let mut value: i16 = 0;
+                for c in digits.chars().take_while(|c| c.is_ascii_digit()) {
+                    value = value.wrapping_mul(10).wrapping_add(c as i16 - '0' as i16);
+                }
+                if negative {
+                    value.wrapping_neg()
+                } else {
+                    value
+                }
+            }
+            ("String", "setInt") => {
+                let s = arg(0);
+                let digits = arg(1).to_string();
+                if digits.len() as i16 > self.peek(s)? {
+                    return self.sys_error(19);
+                }
+                self.poke(s.wrapping_add(1), 0)?;
+                for c in digits.chars() {
+                    self.append_char(s, c as i16)?;
+                }
+                0
+            }
+            ("String", "newLine") => 128,
+            ("String", "backSpace") => 129,
+            ("String", "doubleQuote") => 34,
+
+            ("Output", "init") | ("Output", "moveCursor") => 0,
+            ("Output", "printChar") => {
+                self.print_char(arg(0))?;
+                0
+            }
+            ("Output", "printString") => {
+                let text = self.read_string(arg(0))?;
+                for c in text.chars() {
+                    self.print_char(c as i16)?;
+                }
+                0
+            }
+            ("Output", "printInt") => {
+                write!(self.output, "{}", arg(0)).map_err(|e| e.to_string())?;
+                0
+            }
+            ("Output", "println") => {
+                self.print_char(128)?;
+                0
+            }
+            ("Output", "backSpace") => {
+                self.print_char(129)?;
+                0
+            }
+
+            // There is no display, so drawing is a no-op
+            (
+                "Screen",
+                "init" | "clearScreen" | "setColor" | "drawPixel" | "drawLine" | "drawRectangle"
+                | "drawCircle",
+            ) => 0,
+
+            ("Keyboard", "init") | ("Keyboard", "keyPressed") => 0,
+            ("Keyboard", "readChar") => {
+                let mut byte = [0u8; 1];
+                self.output.flush().map_err(|e| e.to_string())?;
+                match self.input.read(&mut byte).map_err(|e| e.to_string())? {
+                    0 => 0,
+                    _ if byte[0] == b'\n' => 128,
+                    _ => byte[0] as i16,
+                }
+            }
+            ("Keyboard", "readLine") => {
+                let line = self.read_line(arg(0))?;
+                self.new_string(&line)?
+            }
+            ("Keyboard", "readInt") => {
+                let line = self.read_line(arg(0))?;
+                let s = self.new_string(&line)?;
+                let value = self.call_builtin("String", "intValue", &[s])?;
+                self.de_alloc(s);
+                value
+            }
+
+            ("Sys", "init") => {
+                self.call_function("Main", "main", vec![])?;
+                return Err(Interrupt::Halt);
+            }
+            ("Sys", "halt") => return Err(Interrupt::Halt),
+            ("Sys", "error") => return self.sys_error(arg(0)),
+            ("Sys", "wait") => 0,
+
+            _ => {
+                return Err(Interrupt::Error(format!(
+                    "Unknown subroutine {}.{}",
+                    class_name, name
+                )))
+            }
+        };
+        Ok(value)
+    }
+
+    fn sys_error(&mut self, code: i16) -> Eval<i16> {
+        writeln!(self.output, "ERR{}", code).map_err(|e| e.to_string())?;
+        Err(Interrupt::Error(format!("Sys.error({})", code)))
+    }
+
+    fn peek(&mut self, address: i16) -> Eval<i16> {
+        if address < 0 {
+            return self.sys_error(1);
+        }
+        Ok(self.ram[address as usize])
+    }
+
+    fn poke(&mut self, address: i16, value: i16) -> Eval<()> {
+        if address < 0 {
+            self.sys_error(1)?;
+        }
+        self.ram[address as usize] = value;
+        Ok(())
+    }
+
+    // First fit; allocated blocks keep their total size just before the object.
+    fn alloc(&mut self, size: i16) -> Eval<i16> {
+        if size < 0 {
+            return self.sys_error(5);
+        }
+        let needed = size.max(1) as i32 + 1;
+        let mut previous: i16 = 0;
+        let mut block = self.free_list;
+        while block != 0 {
+            let available = self.ram[block as usize] as i32;
+            if available >= needed + 2 {
+                self.ram[block as usize] = (available - needed) as i16;
+                let object = (block as i32 + available - needed) as usize;
+                self.ram[object] = needed as i16;
+                return Ok(object as i16 + 1);
+            }
+            if available >= needed {
+                let next = self.ram[block as usize + 1];
+                if previous == 0 {
+                    self.free_list = next;
+                } else {
+                    self.ram[previous as usize + 1] = next;
+                }
+                return Ok(block + 1);
+            }
+            previous = block;
+            block = self.ram[block as usize + 1];
+        }
+        self.sys_error(6)
+    }
+
+    fn de_alloc(&mut self, object: i16) -> i16 {
+        if object > HEAP_BASE {
+            let block = object - 1;
+            self.ram[block as usize + 1] = self.free_list;
+            self.free_list = block;
+        }
+        0
+    }
+
+    fn new_string(&mut self, value: &str) -> Eval<i16> {
+        let length = value.len() as i16;
+        let s = self.call_builtin("String", "new", &[length])?;
+        for byte in value.bytes() {
+            self.append_char(s, byte as i16)?;
+        }
+        Ok(s)
+    }
+
+    fn append_char(&mut self, s: i16, c: i16) -> Eval<()> {
+        let length = self.peek(s.wrapping_add(1))?;
+        if length >= self.peek(s)? {
+            self.sys_error(17)?;
+        }
+        self.poke(s.wrapping_add(2).wrapping_add(length), c)?;
+        self.poke(s.wrapping_add(1), length + 1)
+    }
+
+    pub fn read_string(&mut self, s: i16) -> Eval<String> {
+        let length = self.peek(s.wrapping_add(1))?;
+        let mut text = String::new();
+        for i in 0..length.max(0) {
+            let c = self.peek(s.wrapping_add(2).wrapping_add(i))?;
+            text.push(char::from_u32(c as u16 as u32).unwrap_or('?'));
+        }
+        Ok(text)
+    }
+
+    fn print_char(&mut self, c: i16) -> Eval<()> {
+        let result = match c {
+            128 => writeln!(self.output),
+            129 => write!(self.output, "\u{8}"),
+            _ => write!(self.output, "{}", char::from_u32(c as u16 as u32).unwrap_or('?')),
+        };
+        result.map_err(|e| Interrupt::Error(e.to_string()))
+    }
+
+    fn read_line(&mut self, message: i16) -> Eval<String> {
+        let message = self.read_string(message)?;
+        write!(self.output, "{}", message).map_err(|e| e.to_string())?;
+        self.output.flush().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        self.input.read_line(&mut line).map_err(|e| e.to_string())?;
+        Ok(line.trim_end_matches(['\n', '\r']).to_string())
+    }
+}
+
+fn truth(value: bool) -> i16 {
+    if value {
+        TRUE
+    } else {
+        FALSE
+    }
+}
+
+fn shift_left(value: i16, amount: i16) -> i16 {
+    if (0..16).contains(&amount) {
+        ((value as u16) << amount) as i16
+    } else {
+        0
+    }
+}
+
+// Arithmetic shift, keeping the sign like the C runtime does
+fn shift_right(value: i16, amount: i16) -> i16 {
+    if (0..16).contains(&amount) {
+        value >> amount
+    } else if value < 0 {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parse_classes;
+    use crate::c_backend;
+    use std::fs;
+    use std::process::Command;
+
+    fn run(source: &str, input: &str) -> (Eval<()>, String) {
+        let mut interpreter = Interpreter::new(input.as_bytes(), Vec::new());
+        for class in parse_classes(source.to_string()).unwrap() {
+            interpreter.load_class(class);
+        }
+        let result = interpreter.run_main();
+        let output = String::from_utf8(interpreter.output().clone()).unwrap();
+        (result, output)
+    }
+
+    // Translates the program to C, then compiles and runs it with the system's
+    // C compiler, returning what it printed
+    fn run_c(name: &str, source: &str) -> String {
+        let program = c_backend::transpile(&parse_classes(source.to_string()).unwrap()).unwrap();
+        let dir = std::env::temp_dir().join(format!("jack-interpreter-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let (c_path, binary) = (dir.join("program.c"), dir.join("program"));
+        fs::write(&c_path, program).unwrap();
+        let compiled = Command::new("cc").arg("-o").arg(&binary).arg(&c_path).output().expect("Failed to run cc");
+        assert!(compiled.status.success(), "{}", String::from_utf8_lossy(&compiled.stderr));
+        let output = Command::new(&binary).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // Runs the program both ways, checking they agree on what it prints
+    fn run_both(name: &str, source: &str) -> String {
+        let (result, output) = run(source, "");
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, run_c(name, source));
+        output
+    }
+
+    #[test]
+    fn test_objects_and_arrays() {
+        let (result, output) = run(
+            "class Main {
+function void main() {
+var Point p; var Array a; var int i;
+let p = Point.new(3, 4);
+let a = Array.new(3);
+let a[0] = p.sum(); let a[1] = 32767 + 1; let a[2] = ^3 + (-16 # 2);
+while (i < 3) { do Output.printInt(a[i]); do Output.printChar(32); let i = i + 1; }
+do Output.printString(\"done\");
+return;
+}
+}
+class Point {
+field int x, y;
+constructor Point new(int ax, int ay) { let x = ax; let y = ay; return this; }
+method int sum() { return x + y; }
+}",
+            "",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, "7 -32768 2 done");
+    }
+
+    #[test]
+    fn test_method_calls_sibling_method() {
+        let output = run_both(
+            "sibling",
+            "class Main {
+function void main() { var Counter c; let c = Counter.new(); do Output.printInt(c.value()); return; }
+}
+class Counter {
+field int count;
+constructor Counter new() { let count = 2; return this; }
+method int helper() { return count; }
+method int value() { return helper(); }
+}",
+        );
+        assert_eq!(output, "2");
+    }
+
+    #[test]
+    fn test_constructor_calls_method() {
+        let output = run_both(
+            "constructor",
+            "class Main {
+function void main() { var Box b; let b = Box.new(); do Output.printInt(b.get()); return; }
+}
+class Box {
+field int v;
+constructor Box new() { do init(); return this; }
+method void init() { let v = 9; return; }
+method int get() { return v; }
+}",
+        );
+        assert_eq!(output, "9");
+    }
+
+    #[test]
+    fn test_sys_error_and_input() {
+        let (result, output) = run(
+            "class Main {
+function void main() {
+var int n;
+let n = Keyboard.readInt(\"n? \");
+do Output.printInt(n * 2);
+do Output.printInt(n / 0);
+return;
+}
+}",
+            "21\n",
+        );
+        assert_eq!(result, Err(Interrupt::Error("Sys.error(3)".to_string())));
+        assert_eq!(output, "n? 42ERR3\n");
+    }
+}
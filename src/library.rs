@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use crate::ast::parse_classes;
+
+// Library directories hold classes shared between projects, given with -L or
+// in the project file. Their classes are parsed along with the project's own
+// so that whole-program checks see every class, which makes a class declared
+// both in the project and in a library, or in two libraries, an error: there
+// is no telling which of the two is meant.
+
+pub const LIBRARY_FLAG: &str = "-L";
+
+// Removes the -L <directory> and -L<directory> arguments, returning the
+// directories in the order given.
+pub fn take_library_paths(args: &mut Vec<String>) -> Result<Vec<String>, String> {
+    let mut libraries = vec![];
+    let mut rest = vec![];
+    let mut taken = std::mem::take(args).into_iter();
+    while let Some(arg) = taken.next() {
+        if arg == LIBRARY_FLAG {
+            match taken.next() {
+                Some(directory) => libraries.push(directory),
+                None => return Err(format!("{} needs a directory", LIBRARY_FLAG)),
+            }
+        } else if let Some(directory) = arg.strip_prefix(LIBRARY_FLAG) {
+            libraries.push(directory.to_string());
+        } else {
+            rest.push(arg);
+        }
+    }
+    *args = rest;
+    Ok(libraries)
+}
+
+// Fails at the first class declared in two of the sources. Sources that do
+// not parse are left to the checks that report syntax errors.
+pub fn check_class_names(sources: &[(PathBuf, String)]) -> Result<(), String> {
+    let mut declared: Vec<(String, &Path)> = vec![];
+    for (path, source) in sources {
+        for class in parse_classes(source.clone()).unwrap_or_default() {
+            if let Some((_, first)) = declared.iter().find(|(name, _)| *name == class.name.text) {
+                return Err(format!(
+                    "Class {} is declared in both {} and {}",
+                    class.name.text,
+                    first.display(),
+                    path.display()
+                ));
+            }
+            declared.push((class.name.text, path));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_library_paths() {
+        let mut args: Vec<String> = ["JackAnalyzer", "run", "-L", "lib", "src", "-L../shared"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(take_library_paths(&mut args), Ok(vec!["lib".to_string(), "../shared".to_string()]));
+        assert_eq!(args, vec!["JackAnalyzer", "run", "src"]);
+        let mut args = vec!["JackAnalyzer".to_string(), "-L".to_string()];
+        assert!(take_library_paths(&mut args).is_err());
+    }
+
+    #[test]
+    fn test_check_class_names() {
+        let source = |path: &str, text: &str| (PathBuf::from(path), text.to_string());
+        let mut sources = vec![
+            source("src/Main.jack", "class Main { }"),
+            source("src/Broken.jack", "class Broken {"),
+            source("lib/Math2.jack", "class Math2 { }"),
+        ];
+        assert_eq!(check_class_names(&sources), Ok(()));
+        sources.push(source("shared/Main.jack", "class Main { }"));
+        assert_eq!(
+            check_class_names(&sources),
+            Err("Class Main is declared in both src/Main.jack and shared/Main.jack".to_string())
+        );
+    }
+}
@@ -0,0 +1,561 @@
+use std::collections::HashMap;
+
+use crate::ast::{Class, Statement, StatementKind, Subroutine, SubroutineKind, Term};
+use crate::cfg::Cfg;
+use crate::diagnostics::{check_source, Diagnostic, Severity};
+use crate::span::Span;
+use crate::symbols::{Project, SymbolKind};
+use crate::tokeniser::tokenise_with_recovery;
+use crate::toml::Document;
+use crate::tokens::TokenType;
+
+// Named checks over a parsed project. Each rule reports at a severity taken
+// from the [lint] section of the project file, where a rule can also be turned
+// "off". Findings that can be fixed without changing what the program does
+// carry the edits that fix them.
+
+pub struct Rule {
+    pub name: &'static str,
+    // Stable, unlike the name, which is only for people
+    pub code: &'static str,
+    // None when the rule is off unless configured
+    pub default: Option<Severity>,
+    pub description: &'static str,
+}
+
+const fn rule(name: &'static str, code: &'static str, default: Severity, description: &'static str) -> Rule {
+    Rule {
+        name,
+        code,
+        default: Some(default),
+        description,
+    }
+}
+
+// New rules take the next code, and codes are never reused
+pub const RULES: [Rule; 9] = [
+    rule("unused-local", "J0101", Severity::Warning, "A local variable is never used"),
+    rule("unused-parameter", "J0102", Severity::Warning, "A parameter is never used"),
+    rule("unused-field", "J0103", Severity::Warning, "A field or static is never used"),
+    rule("shadowed-field", "J0104", Severity::Warning, "A local or parameter hides a field or static"),
+    rule("unreachable-code", "J0105", Severity::Warning, "Statements can never run"),
+    rule("empty-body", "J0106", Severity::Warning, "An if or while has an empty body"),
+    rule("class-name-case", "J0107", Severity::Warning, "A class name is not in PascalCase"),
+    rule("magic-number", "J0108", Severity::Info, "An unnamed integer constant"),
+    rule("missing-return", "J0109", Severity::Error, "A subroutine can end without returning"),
+];
+
+// Syntax errors are always reported, and cannot be configured
+pub const SYNTAX: &str = "syntax";
+
+// Integer constants that are not magic numbers
+const PLAIN_NUMBERS: [u16; 3] = [0, 1, 2];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintConfig {
+    severities: HashMap<&'static str, Option<Severity>>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            severities: RULES.iter().map(|rule| (rule.name, rule.default)).collect(),
+        }
+    }
+}
+
+impl LintConfig {
+    // Reads rule = "off" | "info" | "warning" | "error" lines from [lint].
+    pub fn from_document(document: &Document) -> Result<Self, String> {
+        let mut config = LintConfig::default();
+        for entry in document.section("lint") {
+            let rule = RULES
+                .iter()
+                .map(|rule| rule.name)
+                .find(|rule| *rule == entry.key)
+                .ok_or_else(|| format!("line {}: unknown lint rule {}", entry.line, entry.key))?;
+            let severity = match entry.value.as_str() {
+                Some("off") => None,
+                Some(level) if Severity::new(level).is_some() => Severity::new(level),
+                _ => {
+                    return Err(format!(
+                        "line {}: the level of {} must be \"off\", \"info\", \"warning\" or \"error\", not {}",
+                        entry.line, rule, entry.value
+                    ))
+                }
+            };
+            config.severities.insert(rule, severity);
+        }
+        Ok(config)
+    }
+
+    pub fn severity(&self, rule: &str) -> Option<Severity> {
+        self.severities.get(rule).copied().flatten()
+    }
+}
+
+// Replaces the span with the text.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub file: usize,
+    pub rule: &'static str,
+    pub code: &'static str,
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+    // Empty when the finding has no safe fix
+    pub fix: Vec<Edit>,
+}
+
+impl Finding {
+    // A lexical or syntax error in the file, which has no fix
+    pub fn syntax(file: usize, diagnostic: Diagnostic) -> Finding {
+        Finding {
+            file,
+            rule: SYNTAX,
+            code: diagnostic.code,
+            severity: diagnostic.severity,
+            span: diagnostic.span,
+            message: diagnostic.message,
+            fix: vec![],
+        }
+    }
+}
+
+// Runs every rule that is not off, returning the findings in source order.
+pub fn lint(project: &Project, config: &LintConfig) -> Vec<Finding> {
+    let mut linter = Linter {
+        project,
+        config,
+        findings: vec![],
+    };
+    for (file, source) in project.files.iter().enumerate() {
+        for diagnostic in check_source(&source.text) {
+            linter.findings.push(Finding::syntax(file, diagnostic));
+        }
+        for class in &source.classes {
+            linter.class(file, class);
+        }
+        linter.magic_numbers(file);
+    }
+    linter.unused_and_shadowed();
+    let mut findings = linter.findings;
+    findings.sort_by_key(|finding| (finding.file, finding.span));
+    findings
+}
+
+// Applies the edits to the text, skipping repeated edits and any edit that
+// overlaps one before it.
+pub fn apply_fixes(text: &str, edits: &[&Edit]) -> String {
+    let mut edits = edits.to_vec();
+    edits.sort();
+    edits.dedup();
+    let mut output = String::new();
+    let mut copied = 0;
+    for edit in edits {
+        if edit.span.start < copied {
+            continue;
+        }
+        output.push_str(&text[copied..edit.span.start]);
+        output.push_str(&edit.replacement);
+        copied = edit.span.end;
+    }
+    output.push_str(&text[copied..]);
+    output
+}
+
+struct Linter<'a> {
+    project: &'a Project,
+    config: &'a LintConfig,
+    findings: Vec<Finding>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, file: usize, rule: &'static str, span: Span, message: String, fix: Vec<Edit>) {
+        if let Some(severity) = self.config.severity(rule) {
+            let code = RULES.iter().find(|known| known.name == rule).unwrap().code;
+            self.findings.push(Finding {
+                file,
+                rule,
+                code,
+                severity,
+                span,
+                message,
+                fix,
+            });
+        }
+    }
+
+    fn class(&mut self, file: usize, class: &Class) {
+        let name = class.name.as_str();
+        let pascal_case = name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('_');
+        if !pascal_case {
+            self.report(
+                file,
+                "class-name-case",
+                class.name.span,
+                format!("Class name {} is not in PascalCase", name),
+                vec![],
+            );
+        }
+        for subroutine in &class.subroutines {
+            self.statements(file, &subroutine.statements);
+            let cfg = Cfg::new(class, subroutine);
+            self.unreachable_code(file, &cfg);
+            if cfg.falls_off_end {
+                let fix = match subroutine.return_type {
+                    None => self.insert_return(file, subroutine),
+                    Some(_) => vec![],
+                };
+                self.report(
+                    file,
+                    "missing-return",
+                    subroutine.name.span,
+                    format!("{} {} can reach its end without returning", subroutine.kind, subroutine.name),
+                    fix,
+                );
+            }
+        }
+    }
+
+    // Reports each run of statements that only whitespace separates as one finding.
+    fn unreachable_code(&mut self, file: usize, cfg: &Cfg) {
+        let text = &self.project.files[file].text;
+        let mut runs: Vec<Span> = vec![];
+        for span in cfg.unreachable_statements() {
+            match runs.last_mut() {
+                Some(run) if text[run.end..span.start].trim().is_empty() => *run = run.to(span),
+                _ => runs.push(span),
+            }
+        }
+        for span in runs {
+            let fix = vec![self.remove_lines(file, span)];
+            self.report(
+                file,
+                "unreachable-code",
+                span,
+                "Unreachable code after return".to_string(),
+                fix,
+            );
+        }
+    }
+
+    fn statements(&mut self, file: usize, statements: &[Statement]) {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                } => {
+                    let empty_else = else_branch.as_ref().is_some_and(|branch| branch.is_empty());
+                    if then_branch.is_empty() && (else_branch.is_none() || empty_else) {
+                        // Without calls the condition has no effect
+                        let fix = if calls_nothing(&condition.term)
+                            && condition.rest.iter().all(|(_, term)| calls_nothing(term))
+                        {
+                            vec![self.remove_lines(file, statement.span)]
+                        } else {
+                            vec![]
+                        };
+                        self.report(file, "empty-body", statement.span, "Empty if statement".to_string(), fix);
+                    } else if then_branch.is_empty() {
+                        self.report(file, "empty-body", statement.span, "Empty if branch".to_string(), vec![]);
+                    } else if empty_else {
+                        self.report(file, "empty-body", statement.span, "Empty else branch".to_string(), vec![]);
+                    }
+                    self.statements(file, then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.statements(file, else_branch);
+                    }
+                }
+                StatementKind::While { body, .. } => {
+                    if body.is_empty() {
+                        self.report(file, "empty-body", statement.span, "Empty while loop".to_string(), vec![]);
+                    }
+                    self.statements(file, body);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn unused_and_shadowed(&mut self) {
+        let project = self.project;
+        let mut uses = vec![0; project.symbols.len()];
+        for occurrence in &project.occurrences {
+            if !occurrence.is_declaration {
+                uses[occurrence.symbol] += 1;
+            }
+        }
+        let unused = |id: usize| uses[id] == 0;
+
+        for (id, symbol) in project.symbols.iter().enumerate() {
+            let (rule, description) = match symbol.kind {
+                SymbolKind::Local => ("unused-local", "Local"),
+                SymbolKind::Argument => ("unused-parameter", "Parameter"),
+                SymbolKind::Field => ("unused-field", "Field"),
+                SymbolKind::Static => ("unused-field", "Static"),
+                _ => continue,
+            };
+            if unused(id) {
+                let fix = match symbol.kind {
+                    SymbolKind::Local => {
+                        // Every name in the same var declaration
+                        let names: Vec<usize> = (0..project.symbols.len())
+                            .filter(|&other| {
+                                project.symbols[other].file == symbol.file
+                                    && project.symbols[other].declaration == symbol.declaration
+                            })
+                            .collect();
+                        let kept: Vec<&str> = names
+                            .iter()
+                            .filter(|&&other| !unused(other))
+                            .map(|&other| project.symbols[other].name.as_str())
+                            .collect();
+                        if kept.is_empty() {
+                            vec![self.remove_lines(symbol.file, symbol.declaration)]
+                        } else {
+                            let first = project.symbols[names[0]].span;
+                            let last = project.symbols[names[names.len() - 1]].span;
+                            vec![Edit {
+                                span: first.to(last),
+                                replacement: kept.join(", "),
+                            }]
+                        }
+                    }
+                    _ => vec![],
+                };
+                self.report(
+                    symbol.file,
+                    rule,
+                    symbol.span,
+                    format!("{} {} is never used", description, symbol.name),
+                    fix,
+                );
+            }
+
+            if matches!(symbol.kind, SymbolKind::Local | SymbolKind::Argument) {
+                if let Some(shadowed) = project.class_variable(&symbol.class, &symbol.name) {
+                    self.report(
+                        symbol.file,
+                        "shadowed-field",
+                        symbol.span,
+                        format!(
+                            "{} {} shadows {} {} of {}",
+                            description,
+                            symbol.name,
+                            project.symbols[shadowed].kind,
+                            symbol.name,
+                            symbol.class
+                        ),
+                        vec![],
+                    );
+                }
+            }
+        }
+    }
+
+    // Integer constants outside subroutines that only return a constant,
+    // which is how Jack programs name their constants.
+    fn magic_numbers(&mut self, file: usize) {
+        let source = &self.project.files[file];
+        let constants: Vec<Span> = source
+            .classes
+            .iter()
+            .flat_map(|class| &class.subroutines)
+            .filter(|subroutine| is_constant(subroutine))
+            .map(|subroutine| subroutine.span)
+            .collect();
+        let (tokens, _) = tokenise_with_recovery(&source.text);
+        for token in tokens {
+            if let TokenType::IntegerConstant(value) = token.token {
+                let named = constants.iter().any(|span| span.contains(token.span.start));
+                if !named && !PLAIN_NUMBERS.contains(&value) {
+                    self.report(
+                        file,
+                        "magic-number",
+                        token.span,
+                        format!("Magic number {}, consider a function returning it", value),
+                        vec![],
+                    );
+                }
+            }
+        }
+    }
+
+    // Removes the span, along with the rest of its lines when nothing else is on them.
+    fn remove_lines(&self, file: usize, span: Span) -> Edit {
+        let text = &self.project.files[file].text;
+        let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[span.end..].find('\n').map_or(text.len(), |i| span.end + i + 1);
+        let span = if text[line_start..span.start].trim().is_empty()
+            && text[span.end..line_end].trim().is_empty()
+        {
+            Span::new(line_start, line_end)
+        } else {
+            span
+        };
+        Edit {
+            span,
+            replacement: String::new(),
+        }
+    }
+
+    // Adds "return;" before the closing brace of the subroutine, on its own
+    // line when the brace is on its own line.
+    fn insert_return(&self, file: usize, subroutine: &Subroutine) -> Vec<Edit> {
+        let text = &self.project.files[file].text;
+        let brace = subroutine.span.end - 1;
+        let line_start = text[..brace].rfind('\n').map_or(0, |i| i + 1);
+        let replacement = if text[line_start..brace].trim().is_empty() {
+            let indent = match subroutine.statements.last() {
+                Some(last) => {
+                    let last_line = text[..last.span.start].rfind('\n').map_or(0, |i| i + 1);
+                    &text[last_line..last.span.start]
+                }
+                None => "",
+            };
+            let indent = if indent.trim().is_empty() && !indent.is_empty() {
+                indent.to_string()
+            } else {
+                format!("{}    ", &text[line_start..brace])
+            };
+            return vec![Edit {
+                span: Span::new(line_start, line_start),
+                replacement: format!("{}return;\n", indent),
+            }];
+        } else {
+            "return; ".to_string()
+        };
+        vec![Edit {
+            span: Span::new(brace, brace),
+            replacement,
+        }]
+    }
+}
+
+fn calls_nothing(term: &Term) -> bool {
+    match term {
+        Term::Call(_) => false,
+        Term::ArrayEntry(_, index) => {
+            calls_nothing(&index.term) && index.rest.iter().all(|(_, term)| calls_nothing(term))
+        }
+        Term::Parenthesised(inner) => {
+            calls_nothing(&inner.term) && inner.rest.iter().all(|(_, term)| calls_nothing(term))
+        }
+        Term::Unary(_, inner) => calls_nothing(inner),
+        _ => true,
+    }
+}
+
+// A subroutine without arguments or locals that returns an integer constant.
+fn is_constant(subroutine: &Subroutine) -> bool {
+    let returns_constant = |term: &Term| match term {
+        Term::IntegerConstant(_) => true,
+        Term::Unary(_, inner) => matches!(**inner, Term::IntegerConstant(_)),
+        _ => false,
+    };
+    subroutine.kind != SubroutineKind::Constructor
+        && subroutine.parameters.is_empty()
+        && subroutine.locals.is_empty()
+        && matches!(
+            subroutine.statements.as_slice(),
+            [Statement { kind: StatementKind::Return(Some(value)), .. }]
+                if value.rest.is_empty() && returns_constant(&value.term)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const SOURCE: &str = "class game_board {
+  field int size, unused;
+  static int count;
+
+  function int WIDTH() { return 512; }
+
+  method void resize(int size, int scale) {
+    var int a, b, c;
+    let b = 3;
+    if (b > 0) {}
+    return;
+    let b = 1;
+  }
+
+  method int area() {
+    if (size > 0) { return size * size; }
+  }
+
+  method void clear() {
+    while (count) {}
+    let count = 0;
+  }
+}
+";
+
+    fn findings(config: &LintConfig) -> Vec<(String, &'static str, String)> {
+        let project = Project::new(vec![(PathBuf::from("Board.jack"), SOURCE.to_string())]);
+        lint(&project, config)
+            .into_iter()
+            .map(|finding| {
+                let text = SOURCE[finding.span.start..finding.span.end].to_string();
+                (text, finding.rule, finding.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let findings = findings(&LintConfig::default());
+        let expected = [
+            ("game_board", "class-name-case", "Class name game_board is not in PascalCase"),
+            ("unused", "unused-field", "Field unused is never used"),
+            ("size", "unused-parameter", "Parameter size is never used"),
+            ("size", "shadowed-field", "Parameter size shadows field size of game_board"),
+            ("scale", "unused-parameter", "Parameter scale is never used"),
+            ("a", "unused-local", "Local a is never used"),
+            ("c", "unused-local", "Local c is never used"),
+            ("3", "magic-number", "Magic number 3, consider a function returning it"),
+            ("if (b > 0) {}", "empty-body", "Empty if statement"),
+            ("let b = 1;", "unreachable-code", "Unreachable code after return"),
+            ("area", "missing-return", "method area can reach its end without returning"),
+            ("clear", "missing-return", "method clear can reach its end without returning"),
+            ("while (count) {}", "empty-body", "Empty while loop"),
+        ];
+        let expected: Vec<(String, &str, String)> = expected
+            .iter()
+            .map(|(text, rule, message)| (text.to_string(), *rule, message.to_string()))
+            .collect();
+        assert_eq!(findings, expected);
+    }
+
+    #[test]
+    fn test_config_and_fixes() {
+        let document = Document::parse(
+            "[lint]\nclass-name-case = \"off\"\nmagic-number = \"off\"\nunused-field = \"error\"\n",
+        )
+        .unwrap();
+        let config = LintConfig::from_document(&document).unwrap();
+        assert_eq!(config.severity("unused-field"), Some(Severity::Error));
+        assert!(!findings(&config).iter().any(|(_, rule, _)| *rule == "magic-number"));
+        assert!(LintConfig::from_document(&Document::parse("[lint]\nfoo = \"off\"").unwrap()).is_err());
+
+        let project = Project::new(vec![(PathBuf::from("Board.jack"), SOURCE.to_string())]);
+        let findings = lint(&project, &config);
+        let edits: Vec<&Edit> = findings.iter().flat_map(|finding| &finding.fix).collect();
+        let fixed = apply_fixes(SOURCE, &edits);
+        assert!(fixed.contains("    var int b;\n    let b = 3;\n    return;\n  }\n"));
+        assert!(fixed.contains("    let count = 0;\n    return;\n  }\n"));
+        // area returns a value, so there is no safe fix
+        assert!(fixed.contains("return size * size; }\n  }\n"));
+    }
+}
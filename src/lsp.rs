@@ -0,0 +1,742 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::ast::{parse_tokens, Class, ClassVarKind, SubroutineKind};
+use crate::completion::{complete, CompletionKind};
+use crate::cst::{Document, TextEdit};
+use crate::diagnostics::{check_parse, Severity};
+use crate::json::Json;
+use crate::span::{LineIndex, Position, Span};
+use crate::symbols::{signature, Project, SymbolKind};
+use crate::tokeniser::tokenise_with_recovery;
+
+// A Language Server Protocol server speaking JSON-RPC over a pair of streams.
+// Documents are synchronised by edits, which reparse only the class member
+// they fall inside where they can; each change republishes the tokeniser and
+// parser diagnostics for that document. Queries about names
+// resolve against the open documents together with the other Jack files in the
+// queried document's directory.
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const SYNC_INCREMENTAL: i64 = 2;
+
+// LSP DiagnosticSeverity values
+const SEVERITY_ERROR: i64 = 1;
+const SEVERITY_WARNING: i64 = 2;
+const SEVERITY_INFORMATION: i64 = 3;
+
+// LSP SymbolKind values
+const SYMBOL_CLASS: i64 = 5;
+const SYMBOL_METHOD: i64 = 6;
+const SYMBOL_FIELD: i64 = 8;
+const SYMBOL_CONSTRUCTOR: i64 = 9;
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_VARIABLE: i64 = 13;
+
+// LSP CompletionItemKind values
+const COMPLETION_METHOD: i64 = 2;
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_CONSTRUCTOR: i64 = 4;
+const COMPLETION_FIELD: i64 = 5;
+const COMPLETION_VARIABLE: i64 = 6;
+const COMPLETION_CLASS: i64 = 7;
+const COMPLETION_KEYWORD: i64 = 14;
+
+pub fn serve<R: BufRead, W: Write>(input: R, output: W) -> Result<(), String> {
+    let mut server = Server {
+        input,
+        output,
+        documents: HashMap::new(),
+    };
+    server.run()
+}
+
+struct Server<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    documents: HashMap<String, Document>,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    fn run(&mut self) -> Result<(), String> {
+        while let Some(body) = self.read_message()? {
+            // A body that is not JSON has no id to answer to, so the error goes
+            // back with a null id and the server carries on
+            let message = match parse_body(body) {
+                Ok(message) => message,
+                Err(error) => {
+                    self.write_message(&error_response(Json::Null, PARSE_ERROR, error))?;
+                    continue;
+                }
+            };
+            let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+            let params = message.get("params").cloned().unwrap_or(Json::Null);
+            match message.get("id") {
+                Some(id) => {
+                    let response = match self.handle_request(method, &params) {
+                        Ok(result) => Json::object(vec![
+                            ("jsonrpc", "2.0".into()),
+                            ("id", id.clone()),
+                            ("result", result),
+                        ]),
+                        Err((code, error)) => error_response(id.clone(), code, error),
+                    };
+                    self.write_message(&response)?;
+                }
+                None if method == "exit" => return Ok(()),
+                None => self.handle_notification(method, &params)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        match method {
+            "initialize" => Ok(Json::object(vec![
+                (
+                    "capabilities",
+                    Json::object(vec![
+                        (
+                            "textDocumentSync",
+                            Json::object(vec![
+                                ("openClose", true.into()),
+                                ("change", SYNC_INCREMENTAL.into()),
+                            ]),
+                        ),
+                        ("documentSymbolProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("referencesProvider", true.into()),
+                        ("hoverProvider", true.into()),
+                        (
+                            "completionProvider",
+                            Json::object(vec![("triggerCharacters", vec![".".into()].into())]),
+                        ),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object(vec![("name", "JackAnalyzer".into())]),
+                ),
+            ])),
+            "shutdown" => Ok(Json::Null),
+            "textDocument/documentSymbol" => {
+                let uri = document_uri(params);
+                Ok(match self.documents.get(uri) {
+                    Some(document) => document_symbols(&document.text),
+                    None => Json::Array(vec![]),
+                })
+            }
+            "textDocument/definition" => {
+                let (project, file, offset) = self.query(params);
+                Ok(
+                    match file.and_then(|file| project.definition(file, offset)) {
+                        Some(symbol) => self.location(&project, symbol.file, symbol.span),
+                        None => Json::Null,
+                    },
+                )
+            }
+            "textDocument/references" => {
+                let include_declaration =
+                    params.at(&["context", "includeDeclaration"]) != Some(&Json::Bool(false));
+                let (project, file, offset) = self.query(params);
+                let references = match file {
+                    Some(file) => project.references(file, offset),
+                    None => vec![],
+                };
+                Ok(Json::Array(
+                    references
+                        .into_iter()
+                        .filter(|occurrence| include_declaration || !occurrence.is_declaration)
+                        .map(|occurrence| self.location(&project, occurrence.file, occurrence.span))
+                        .collect(),
+                ))
+            }
+            "textDocument/hover" => {
+                let (project, file, offset) = self.query(params);
+                Ok(match file.and_then(|file| project.hover(file, offset)) {
+                    Some(text) => Json::object(vec![(
+                        "contents",
+                        Json::object(vec![("kind", "markdown".into()), ("value", text.into())]),
+                    )]),
+                    None => Json::Null,
+                })
+            }
+            "textDocument/completion" => {
+                let (project, file, offset) = self.query(params);
+                let completions = match file {
+                    Some(file) => complete(&project, file, offset),
+                    None => vec![],
+                };
+                Ok(Json::Array(
+                    completions
+                        .into_iter()
+                        .map(|completion| {
+                            Json::object(vec![
+                                ("label", completion.label.into()),
+                                ("kind", completion_kind(completion.kind).into()),
+                                ("detail", completion.detail.into()),
+                            ])
+                        })
+                        .collect(),
+                ))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unsupported method {}", method))),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Json) -> Result<(), String> {
+        let uri = document_uri(params).to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params.at(&["textDocument", "text"]).and_then(Json::as_str);
+                self.documents
+                    .insert(uri.clone(), Document::new(text.unwrap_or("").to_string()));
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didChange" => {
+                // Changes apply in order, each to the text the one before left;
+                // a change without a range replaces the whole text
+                let changes = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .unwrap_or_default();
+                for change in changes {
+                    let Some(text) = change.get("text").and_then(Json::as_str) else {
+                        continue;
+                    };
+                    match (self.documents.get_mut(&uri), change.get("range")) {
+                        (Some(document), Some(range)) => {
+                            let index = LineIndex::new(&document.text);
+                            let offset = |key| match range.get(key) {
+                                Some(position) => index.offset(json_position(position)),
+                                None => 0,
+                            };
+                            let (start, end) = (offset("start"), offset("end"));
+                            let edit = TextEdit {
+                                span: Span::new(start.min(end), end.max(start)),
+                                text: text.to_string(),
+                            };
+                            document.edit(&edit);
+                        }
+                        _ => {
+                            self.documents.insert(uri.clone(), Document::new(text.to_string()));
+                        }
+                    }
+                }
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> Result<(), String> {
+        let diagnostics = match self.documents.get(uri) {
+            Some(document) => {
+                let index = LineIndex::new(&document.text);
+                check_parse(&document.parse)
+                    .into_iter()
+                    .map(|diagnostic| {
+                        Json::object(vec![
+                            ("range", range(&index, diagnostic.span)),
+                            ("severity", severity(diagnostic.severity).into()),
+                            ("code", diagnostic.code.into()),
+                            ("source", "jack".into()),
+                            ("message", diagnostic.message.into()),
+                        ])
+                    })
+                    .collect()
+            }
+            None => vec![],
+        };
+        let notification = Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::object(vec![
+                    ("uri", uri.into()),
+                    ("diagnostics", Json::Array(diagnostics)),
+                ]),
+            ),
+        ]);
+        self.write_message(&notification)
+    }
+
+    // Builds the project around the queried document and finds the document
+    // and the byte offset of the queried position in it.
+    fn query(&self, params: &Json) -> (Project, Option<usize>, usize) {
+        let path = uri_to_path(document_uri(params));
+        let mut sources: Vec<(PathBuf, String)> = self
+            .documents
+            .iter()
+            .map(|(uri, document)| (uri_to_path(uri), document.text.clone()))
+            .collect();
+        if let Some(Ok(entries)) = path.parent().map(fs::read_dir) {
+            let mut on_disk: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|file| {
+                    file.extension()
+                        .is_some_and(|extension| extension == "jack")
+                })
+                .filter(|file| sources.iter().all(|(open, _)| open != file))
+                .collect();
+            on_disk.sort();
+            for file in on_disk {
+                if let Ok(text) = fs::read_to_string(&file) {
+                    sources.push((file, text));
+                }
+            }
+        }
+        sources.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let project = Project::new(sources);
+        let file = project.file_index(&path);
+        let offset = match (file, params.get("position")) {
+            (Some(file), Some(position)) => {
+                LineIndex::new(&project.files[file].text).offset(json_position(position))
+            }
+            _ => 0,
+        };
+        (project, file, offset)
+    }
+
+    fn location(&self, project: &Project, file: usize, span: Span) -> Json {
+        let file = &project.files[file];
+        // Prefer the URI the editor used for an open document
+        let uri = self
+            .documents
+            .keys()
+            .find(|uri| uri_to_path(uri) == file.path)
+            .cloned()
+            .unwrap_or_else(|| path_to_uri(&file.path));
+        Json::object(vec![
+            ("uri", uri.into()),
+            ("range", range(&LineIndex::new(&file.text), span)),
+        ])
+    }
+
+    // Reads the body of one "Content-Length" framed message, or None at the end
+    // of input.
+    fn read_message(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let mut content_length = None;
+        loop {
+            let mut header = String::new();
+            if self
+                .input
+                .read_line(&mut header)
+                .map_err(|e| e.to_string())?
+                == 0
+            {
+                return Ok(None);
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    content_length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+        let content_length = content_length.ok_or("Message without a Content-Length header")?;
+        let mut body = vec![0; content_length];
+        self.input
+            .read_exact(&mut body)
+            .map_err(|e| e.to_string())?;
+        Ok(Some(body))
+    }
+
+    fn write_message(&mut self, message: &Json) -> Result<(), String> {
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .and_then(|_| self.output.flush())
+        .map_err(|e| e.to_string())
+    }
+}
+
+fn parse_body(body: Vec<u8>) -> Result<Json, String> {
+    let body = String::from_utf8(body).map_err(|e| e.to_string())?;
+    Json::parse(&body)
+}
+
+fn error_response(id: Json, code: i64, message: String) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        (
+            "error",
+            Json::object(vec![
+                ("code", code.into()),
+                ("message", message.into()),
+            ]),
+        ),
+    ])
+}
+
+fn document_uri(params: &Json) -> &str {
+    params
+        .at(&["textDocument", "uri"])
+        .and_then(Json::as_str)
+        .unwrap_or("")
+}
+
+// An LSP Position, {"line": 0, "character": 0}
+fn json_position(position: &Json) -> Position {
+    let number = |key| match position.get(key) {
+        Some(Json::Number(value)) => *value as usize,
+        _ => 0,
+    };
+    Position {
+        line: number("line"),
+        column: number("character"),
+    }
+}
+
+fn completion_kind(kind: CompletionKind) -> i64 {
+    match kind {
+        CompletionKind::Keyword => COMPLETION_KEYWORD,
+        CompletionKind::Symbol(SymbolKind::Class) => COMPLETION_CLASS,
+        CompletionKind::Symbol(SymbolKind::Field) => COMPLETION_FIELD,
+        CompletionKind::Symbol(SymbolKind::Static | SymbolKind::Argument | SymbolKind::Local) => {
+            COMPLETION_VARIABLE
+        }
+        CompletionKind::Symbol(SymbolKind::Constructor) => COMPLETION_CONSTRUCTOR,
+        CompletionKind::Symbol(SymbolKind::Function) => COMPLETION_FUNCTION,
+        CompletionKind::Symbol(SymbolKind::Method) => COMPLETION_METHOD,
+    }
+}
+
+fn severity(severity: Severity) -> i64 {
+    match severity {
+        Severity::Error => SEVERITY_ERROR,
+        Severity::Warning => SEVERITY_WARNING,
+        Severity::Info => SEVERITY_INFORMATION,
+    }
+}
+
+// Converts a file URI to a path, decoding percent escapes.
+fn uri_to_path(uri: &str) -> PathBuf {
+    let encoded = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut bytes = vec![];
+    let mut i = 0;
+    while i < encoded.len() {
+        let escaped = encoded
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) if encoded[i] == b'%' => {
+                bytes.push(byte);
+                i += 3;
+            }
+            _ => {
+                bytes.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+    format!("file://{}", encode_uri_path(&path.to_string_lossy()))
+}
+
+// Percent-encodes every byte that may not appear in the path of a URI.
+pub fn encode_uri_path(path: &str) -> String {
+    let mut uri = String::new();
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+fn range(index: &LineIndex, span: Span) -> Json {
+    let position = |offset: usize| {
+        let position = index.position(offset);
+        Json::object(vec![
+            ("line", position.line.into()),
+            ("character", position.column.into()),
+        ])
+    };
+    Json::object(vec![
+        ("start", position(span.start)),
+        ("end", position(span.end)),
+    ])
+}
+
+// Classes with their fields, statics and subroutines as children. Documents
+// that do not parse have no symbols.
+fn document_symbols(text: &str) -> Json {
+    let (tokens, _) = tokenise_with_recovery(text);
+    let classes = match parse_tokens(&tokens) {
+        Ok(classes) => classes,
+        Err(_) => return Json::Array(vec![]),
+    };
+    let index = LineIndex::new(text);
+    Json::Array(
+        classes
+            .iter()
+            .map(|class| class_symbol(&index, class))
+            .collect(),
+    )
+}
+
+fn class_symbol(index: &LineIndex, class: &Class) -> Json {
+    let mut children = vec![];
+    for dec in &class.class_vars {
+        let (kind, detail) = match dec.kind {
+            ClassVarKind::Field => (SYMBOL_FIELD, format!("field {}", dec.var_type)),
+            ClassVarKind::Static => (SYMBOL_VARIABLE, format!("static {}", dec.var_type)),
+        };
+        for name in &dec.names {
+            children.push(symbol(
+                index,
+                name,
+                &detail,
+                kind,
+                dec.span,
+                name.span,
+                vec![],
+            ));
+        }
+    }
+    for subroutine in &class.subroutines {
+        let kind = match subroutine.kind {
+            SubroutineKind::Constructor => SYMBOL_CONSTRUCTOR,
+            SubroutineKind::Function => SYMBOL_FUNCTION,
+            SubroutineKind::Method => SYMBOL_METHOD,
+        };
+        let detail = signature(subroutine);
+        children.push(symbol(
+            index,
+            &subroutine.name,
+            &detail,
+            kind,
+            subroutine.span,
+            subroutine.name.span,
+            vec![],
+        ));
+    }
+    symbol(
+        index,
+        &class.name,
+        "class",
+        SYMBOL_CLASS,
+        class.span,
+        class.name.span,
+        children,
+    )
+}
+
+fn symbol(
+    index: &LineIndex,
+    name: &str,
+    detail: &str,
+    kind: i64,
+    span: Span,
+    selection: Span,
+    children: Vec<Json>,
+) -> Json {
+    Json::object(vec![
+        ("name", name.into()),
+        ("detail", detail.into()),
+        ("kind", kind.into()),
+        ("range", range(index, span)),
+        ("selectionRange", range(index, selection)),
+        ("children", Json::Array(children)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(message: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", message.len(), message)
+    }
+
+    // Splits the server's output back into messages.
+    fn messages(output: &[u8]) -> Vec<Json> {
+        let mut input = output;
+        let mut server = Server {
+            input: &mut input,
+            output: Vec::new(),
+            documents: HashMap::new(),
+        };
+        let mut messages = vec![];
+        while let Some(body) = server.read_message().unwrap() {
+            messages.push(parse_body(body).unwrap());
+        }
+        messages
+    }
+
+    #[test]
+    fn test_incremental_changes() {
+        let uri = "file:///no-such-directory/Main.jack";
+        let mut input: &[u8] = &[];
+        let mut server = Server {
+            input: &mut input,
+            output: Vec::new(),
+            documents: HashMap::new(),
+        };
+        let open = format!(
+            r#"{{"textDocument":{{"uri":"{}","text":"class Main {{\n  function void main() {{\n    let x = 1;\n  }}\n}}"}}}}"#,
+            uri
+        );
+        server
+            .handle_notification("textDocument/didOpen", &Json::parse(&open).unwrap())
+            .unwrap();
+        let change = format!(
+            r#"{{"textDocument":{{"uri":"{}"}},"contentChanges":[
+                {{"range":{{"start":{{"line":2,"character":12}},"end":{{"line":2,"character":13}}}},"text":"x + 2"}},
+                {{"range":{{"start":{{"line":2,"character":9}},"end":{{"line":2,"character":9}}}},"text":"y"}}]}}"#,
+            uri
+        );
+        server
+            .handle_notification("textDocument/didChange", &Json::parse(&change).unwrap())
+            .unwrap();
+        assert_eq!(
+            server.documents[uri].text,
+            "class Main {\n  function void main() {\n    let xy = x + 2;\n  }\n}"
+        );
+        let diagnostics = messages(&server.output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].at(&["params", "diagnostics"]), Some(&Json::Array(vec![])));
+    }
+
+    #[test]
+    fn test_malformed_message() {
+        let script = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+        ];
+        let input: String = script.iter().map(|message| frame(message)).collect();
+        let mut output = vec![];
+        serve(input.as_bytes(), &mut output).unwrap();
+        let messages = messages(&output);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].get("id"), Some(&Json::Null));
+        assert_eq!(
+            messages[0].at(&["error", "code"]),
+            Some(&Json::from(PARSE_ERROR))
+        );
+        assert_eq!(messages[1].get("id"), Some(&Json::from(2_i64)));
+    }
+
+    #[test]
+    fn test_scripted_session() {
+        let uri = "file:///no-such-directory/Main.jack";
+        let broken = r#"class Main {\n  function void main() {\n    let x = ;\n  }\n}"#;
+        let fixed = r#"class Main {\n  field int x;\n  method int get() { return x; }\n}"#;
+        let at = |line, character| {
+            format!(
+                r#"{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}}}"#,
+                uri, line, character
+            )
+        };
+        let script = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#.to_string(),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{}","languageId":"jack","version":1,"text":"{}"}}}}}}"#,
+                uri, broken
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{}","version":2}},"contentChanges":[{{"text":"{}"}}]}}}}"#,
+                uri, fixed
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/documentSymbol","params":{}}}"#,
+                at(0, 0)
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{}}}"#,
+                at(2, 28)
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":4,"method":"textDocument/hover","params":{}}}"#,
+                at(2, 28)
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":5,"method":"textDocument/completion","params":{}}}"#,
+                at(2, 29)
+            ),
+            r#"{"jsonrpc":"2.0","id":6,"method":"textDocument/formatting","params":{}}"#
+                .to_string(),
+            r#"{"jsonrpc":"2.0","id":7,"method":"shutdown"}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+        ];
+        let input: String = script.iter().map(|message| frame(message)).collect();
+        let mut output = vec![];
+        serve(input.as_bytes(), &mut output).unwrap();
+        let messages = messages(&output);
+        assert_eq!(messages.len(), 9);
+
+        let capabilities = messages[0].at(&["result", "capabilities"]).unwrap();
+        assert_eq!(
+            capabilities.get("documentSymbolProvider"),
+            Some(&Json::Bool(true))
+        );
+
+        let diagnostics = messages[1].at(&["params", "diagnostics"]).unwrap();
+        assert_eq!(
+            diagnostics.to_string(),
+            r#"[{"range":{"start":{"line":2,"character":12},"end":{"line":2,"character":13}},"severity":1,"code":"J0002","source":"jack","message":"Unexpected token Symbol(SemiColon) when compiling term"}]"#
+        );
+        let diagnostics = messages[2].at(&["params", "diagnostics"]).unwrap();
+        assert_eq!(diagnostics, &Json::Array(vec![]));
+
+        let symbols = messages[3].get("result").unwrap().as_array().unwrap();
+        assert_eq!(symbols[0].get("name").unwrap().as_str(), Some("Main"));
+        let children = symbols[0].get("children").unwrap().as_array().unwrap();
+        assert_eq!(
+            children[0].get("detail").unwrap().as_str(),
+            Some("field int")
+        );
+        assert_eq!(
+            children[1].get("detail").unwrap().as_str(),
+            Some("method int get()")
+        );
+        assert_eq!(children[1].get("kind"), Some(&Json::from(SYMBOL_METHOD)));
+
+        assert_eq!(
+            messages[4].get("result").unwrap().to_string(),
+            r#"{"uri":"file:///no-such-directory/Main.jack","range":{"start":{"line":1,"character":12},"end":{"line":1,"character":13}}}"#
+        );
+        assert_eq!(
+            messages[5]
+                .at(&["result", "contents", "value"])
+                .unwrap()
+                .as_str(),
+            Some("```jack\nfield int x\n```\n\nfield of Main\n")
+        );
+        assert_eq!(
+            messages[6].get("result").unwrap().to_string(),
+            r#"[{"label":"x","kind":5,"detail":"field int x"}]"#
+        );
+        assert_eq!(
+            messages[7].at(&["error", "code"]),
+            Some(&Json::from(METHOD_NOT_FOUND))
+        );
+        assert_eq!(messages[8].get("result"), Some(&Json::Null));
+    }
+}
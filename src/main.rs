@@ -1,68 +1,1044 @@
 use std::{
     env,
     fs::{self, OpenOptions},
-    io::Write,
+    io::{self, Read, Write},
     path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
 };
 
+mod ast;
+mod c_backend;
+mod cache;
+mod cfg;
+mod completion;
+mod cst;
+mod diagnostics;
+mod diff;
+#[cfg(test)]
+mod generate;
+mod glob;
+mod graph;
+mod interpreter;
+mod json;
+mod library;
+mod lint;
+mod lsp;
+mod manifest;
+mod output;
+mod parse_tree;
 mod parser;
+mod pool;
+mod rename;
+mod report;
+mod repl;
+mod span;
+mod symbols;
 mod token_stream;
 mod tokeniser;
 mod tokens;
+mod toml;
+mod watch;
 
-static JACK_FILE_EXTENSION: &'static str = "jack";
-static XML_FILE_EXTENSION: &'static str = "xml";
+static JACK_FILE_EXTENSION: &str = "jack";
+static C_FILE_EXTENSION: &str = "c";
+static PROJECT_FILE_NAME: &str = "jack.toml";
+static STDIN_PATH: &str = "-";
+static STDIN_NAME: &str = "<stdin>";
+static SUBCOMMANDS: [&str; 18] = [
+    "lsp", "repl", "build", "clean", "watch", "tokens", "compare", "c", "run", "definition",
+    "references", "hover", "complete", "rename", "lint", "cfg", "graph", "syntax",
+];
+static USAGE: &str = "  JackAnalyzer [c|run|repl] <input path>
+  JackAnalyzer [tokens] <input path>... [--format xml|json|sexp] [--threads <n>]
+               [--force] [--overwrite] [--include <glob>]... [--exclude <glob>]...
+               [--output-dir <directory>] [--suffix <suffix>] [--extension <extension>]
+               [--compile-libraries] [--diagnostics text|json|sarif]
+  JackAnalyzer [tokens] - [--format xml|json|sexp] [--diagnostics text|json|sarif]
+  JackAnalyzer build [<project directory>] [--force] [--overwrite] [--diagnostics text|json|sarif]
+  JackAnalyzer clean <input path>
+  JackAnalyzer watch <input path> [--interval <milliseconds>]
+  JackAnalyzer compare <expected xml> <actual xml>
+  JackAnalyzer lsp
+  JackAnalyzer [definition|references|hover|complete] <file> <line>:<column>
+  JackAnalyzer rename <file> <line>:<column> <new name> [--in-place]
+  JackAnalyzer lint <input path> [--fix] [--format text|json|sarif]
+  JackAnalyzer cfg <file> [<class>.<subroutine>]
+  JackAnalyzer syntax <file> [<line>:<column>]
+  JackAnalyzer graph <input path> [--classes] [--json]
+Commands that read whole programs take -L <library directory> to add shared classes";
+// Jack programs recurse through the interpreter's own Rust stack
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        panic!("Invalid usage, please use: JackAnalyzer <input path>")
-    }
-    let argument_path = env::args().nth(1).expect("No path provided");
-    let argument_path = fs::canonicalize(&argument_path).expect("Invalid path provided");
-
-    let files_to_compile: Vec<PathBuf> = if argument_path.is_dir() {
-        fs::read_dir(&argument_path)
-            .expect("Failed to read directory")
-            .filter_map(|entry| entry.ok().map(|e| e.path()))
-            .collect()
+    let mut args: Vec<String> = env::args().collect();
+    let libraries: Vec<PathBuf> = match library::take_library_paths(&mut args) {
+        Ok(libraries) => libraries.into_iter().map(PathBuf::from).collect(),
+        Err(e) => panic!("{}, please use:\n{}", e, USAGE),
+    };
+    match args.len() {
+        2 if args[1] == "lsp" => start_language_server(),
+        2 if args[1] == "repl" => start_repl(None, &libraries),
+        3 if args[1] == "repl" => start_repl(Some(&args[2]), &libraries),
+        2 if args[1] == "build" => build_project(None, &[], &libraries),
+        // A leading flag means the project is found from the current directory
+        n if n >= 3 && args[1] == "build" && args[2].starts_with("--") => {
+            build_project(None, &args[2..], &libraries)
+        }
+        n if n >= 3 && args[1] == "build" => build_project(Some(&args[2]), &args[3..], &libraries),
+        3 if args[1] == "clean" => clean_cache(&args[2]),
+        n if n >= 3 && args[1] == "watch" => watch_files(&args[2], &args[3..]),
+        n if n >= 3 && args[1] == "tokens" => compile_command(&args[2..], &libraries, output::Kind::Tokens),
+        4 if args[1] == "compare" => compare_files(&args[2], &args[3]),
+        3 if args[1] == "c" => transpile_to_c(&args[2], &libraries),
+        3 if args[1] == "run" => run_program(&args[2], &libraries),
+        4 if ["definition", "references", "hover", "complete"].contains(&args[1].as_str()) => {
+            query_position(&args[1], &args[2], &args[3])
+        }
+        5 if args[1] == "rename" => rename_symbol(&args[2], &args[3], &args[4], false),
+        6 if args[1] == "rename" && args[5] == "--in-place" => {
+            rename_symbol(&args[2], &args[3], &args[4], true)
+        }
+        n if n >= 3 && args[1] == "lint" => lint_files(&args[2], &args[3..], &libraries),
+        3 if args[1] == "cfg" => print_control_flow(&args[2], None),
+        4 if args[1] == "cfg" => print_control_flow(&args[2], Some(&args[3])),
+        3 if args[1] == "syntax" => print_syntax_tree(&args[2], None),
+        4 if args[1] == "syntax" => print_syntax_tree(&args[2], Some(&args[3])),
+        n if n >= 3
+            && args[1] == "graph"
+            && args[3..].iter().all(|flag| flag == "--classes" || flag == "--json") =>
+        {
+            let flags = &args[3..];
+            let classes = flags.contains(&"--classes".to_string());
+            print_graph(&args[2], classes, flags.contains(&"--json".to_string()), &libraries)
+        }
+        n if n >= 2 && !SUBCOMMANDS.contains(&args[1].as_str()) => {
+            compile_command(&args[1..], &libraries, output::Kind::ParseTree(parse_tree::Format::Xml))
+        }
+        _ => panic!("Invalid usage, please use:\n{}", USAGE),
+    }
+}
+
+fn collect_input_files(argument_path: &str) -> Vec<PathBuf> {
+    find_input_files(argument_path, &glob::Filter::default())
+}
+
+// The input file, or the Jack files in the input directory and the
+// directories nested in it that the filter accepts, in path order. Hidden
+// directories, such as the build cache, are left out
+fn find_input_files(argument_path: &str, filter: &glob::Filter) -> Vec<PathBuf> {
+    let argument_path = fs::canonicalize(argument_path).expect("Invalid path provided");
+
+    if argument_path.is_dir() {
+        let mut files = vec![];
+        walk_directory(&argument_path, &argument_path, filter, &mut files);
+        files.sort();
+        files
     } else {
         vec![argument_path]
+    }
+}
+
+fn walk_directory(root: &Path, directory: &Path, filter: &glob::Filter, files: &mut Vec<PathBuf>) {
+    let entries = fs::read_dir(directory).expect("Failed to read directory");
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        // Symbolic links to directories are not followed, so there are no cycles
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if !entry.file_name().to_string_lossy().starts_with('.') {
+                walk_directory(root, &path, filter, files);
+            }
+        } else if is_jack_file(&path) {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            if filter.accepts(&relative) {
+                files.push(path);
+            }
+        }
+    }
+}
+
+fn is_jack_file(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => extension.to_str().unwrap_or("").to_lowercase() == JACK_FILE_EXTENSION,
+        None => false,
+    }
+}
+
+// Writes the output of the kind for every file on a pool of threads,
+// reporting the errors in the order of the files followed by a summary, and
+// exiting with an error if any failed. Files whose output is up to date in the
+// build cache are skipped unless --force is given. Lexical and syntax errors are
+// reported as text, or with --diagnostics as JSON lines or a SARIF log
+fn compile_command(args: &[String], libraries: &[PathBuf], mut kind: output::Kind) {
+    let mut argument_paths = vec![];
+    let mut threads = pool::default_threads();
+    let mut force = false;
+    let mut overwrite = false;
+    let mut filter = glob::Filter::default();
+    let mut compile_libraries = false;
+    let mut output_dir = None;
+    let mut suffix = None;
+    let mut extension = None;
+    let mut diagnostics = report::Format::Text;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" if kind != output::Kind::Tokens => match args.next() {
+                Some(name) => match parse_tree::Format::new(name) {
+                    Some(format) => kind = output::Kind::ParseTree(format),
+                    None => panic!("Unknown format {}, please use:\n{}", name, USAGE),
+                },
+                None => panic!("Invalid usage, please use:\n{}", USAGE),
+            },
+            "--diagnostics" => diagnostics = diagnostics_format(args.next()),
+            "--threads" => match args.next().and_then(|count| count.parse::<usize>().ok()) {
+                Some(count) if count > 0 => threads = count,
+                _ => panic!("Invalid usage, please use:\n{}", USAGE),
+            },
+            "--force" => force = true,
+            "--overwrite" => overwrite = true,
+            "--compile-libraries" => compile_libraries = true,
+            "--include" | "--exclude" => match args.next() {
+                Some(pattern) if arg == "--include" => filter.include.push(pattern.clone()),
+                Some(pattern) => filter.exclude.push(pattern.clone()),
+                None => panic!("Invalid usage, please use:\n{}", USAGE),
+            },
+            "--output-dir" | "--suffix" | "--extension" => match args.next() {
+                Some(value) if arg == "--output-dir" => output_dir = Some(env::current_dir().unwrap().join(value)),
+                Some(value) if arg == "--suffix" => suffix = Some(value.clone()),
+                Some(value) => extension = Some(value.trim_start_matches('.').to_string()),
+                None => panic!("Invalid usage, please use:\n{}", USAGE),
+            },
+            flag if flag.starts_with("--") => panic!("Invalid usage, please use:\n{}", USAGE),
+            _ => argument_paths.push(arg.as_str()),
+        }
+    }
+    if argument_paths.contains(&STDIN_PATH) {
+        if argument_paths.len() > 1 {
+            panic!("Invalid usage, please use:\n{}", USAGE);
+        }
+        return compile_stdin(kind, diagnostics);
+    }
+
+    let mut naming = kind.default_naming();
+    naming.suffix = suffix.unwrap_or(naming.suffix);
+    naming.extension = extension.unwrap_or(naming.extension);
+    let mut build = Build::new(kind, naming, output_dir);
+    for argument_path in argument_paths {
+        let source_dir = source_directory(argument_path);
+        for input_path in find_input_files(argument_path, &filter) {
+            if is_jack_file(&input_path) {
+                build.add(input_path, &source_dir);
+            }
+        }
+    }
+    build.add_libraries(libraries, compile_libraries);
+    build.run(threads, force, overwrite, &manifest::EXTENSIONS, diagnostics);
+}
+
+fn diagnostics_format(name: Option<&String>) -> report::Format {
+    match name.and_then(|name| report::Format::new(name)) {
+        Some(format) => format,
+        None => panic!("Invalid usage, please use:\n{}", USAGE),
+    }
+}
+
+// Builds the project described by the project file in the input directory, or
+// the current directory, or the closest of their ancestors that has one
+fn build_project(argument_path: Option<&str>, flags: &[String], libraries: &[PathBuf]) {
+    let mut force = false;
+    let mut overwrite = false;
+    let mut diagnostics = report::Format::Text;
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--force" => force = true,
+            "--overwrite" => overwrite = true,
+            "--diagnostics" => diagnostics = diagnostics_format(flags.next()),
+            _ => panic!("Invalid usage, please use:\n{}", USAGE),
+        }
+    }
+    let start = argument_path.map_or_else(|| PathBuf::from("."), PathBuf::from);
+    let Some(path) = find_project_file(&start) else {
+        eprintln!("No {} in {} or its ancestors", PROJECT_FILE_NAME, start.display());
+        process::exit(1);
     };
+    let manifest = load_manifest(&path);
+    let kind = output::Kind::ParseTree(manifest.target);
+    let mut naming = kind.default_naming();
+    naming.suffix = manifest.suffix.clone().unwrap_or(naming.suffix);
+    naming.extension = manifest.extension.clone().unwrap_or(naming.extension);
+    let mut build = Build::new(kind, naming, manifest.output.clone());
+    for source_dir in &manifest.sources {
+        let source_dir = fs::canonicalize(source_dir).unwrap_or_else(|_| {
+            eprintln!("{}: no source directory {}", path.display(), source_dir.display());
+            process::exit(1);
+        });
+        for input_path in collect_input_files(&source_dir.to_string_lossy()) {
+            build.add(input_path, &source_dir);
+        }
+    }
+    let mut libraries = libraries.to_vec();
+    libraries.extend(manifest.libraries.iter().cloned());
+    build.add_libraries(&libraries, manifest.compile_libraries);
+    build.run(pool::default_threads(), force, overwrite, &manifest.extensions, diagnostics);
+}
+
+fn load_manifest(path: &Path) -> manifest::Manifest {
+    match manifest::Manifest::load(path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+// Files to write an output for, each with its output path and the index of
+// the build cache of its source directory, and the files in library
+// directories
+struct Build {
+    kind: output::Kind,
+    naming: output::Naming,
+    output_dir: Option<PathBuf>,
+    caches: Vec<(PathBuf, cache::Cache)>,
+    jobs: Vec<(PathBuf, PathBuf, usize)>,
+    library_files: Vec<PathBuf>,
+}
+
+impl Build {
+    fn new(kind: output::Kind, naming: output::Naming, output_dir: Option<PathBuf>) -> Build {
+        Build {
+            kind,
+            naming,
+            output_dir,
+            caches: vec![],
+            jobs: vec![],
+            library_files: vec![],
+        }
+    }
+
+    fn add(&mut self, input_path: PathBuf, source_dir: &Path) {
+        if self.jobs.iter().any(|(added, ..)| *added == input_path) {
+            return;
+        }
+        let output_path = self
+            .naming
+            .output_path(&input_path, source_dir, self.output_dir.as_deref());
+        let index = match self.caches.iter().position(|(dir, _)| dir == source_dir) {
+            Some(index) => index,
+            None => {
+                let cache = cache::Cache::load(source_dir);
+                self.caches.push((source_dir.to_path_buf(), cache));
+                self.caches.len() - 1
+            }
+        };
+        self.jobs.push((input_path, output_path, index));
+    }
+
+    // Adds the classes in the library directories to the whole-program check,
+    // and when they are compiled as well, to the files to write outputs for
+    fn add_libraries(&mut self, libraries: &[PathBuf], compile: bool) {
+        for library in libraries {
+            let library = find_library(library);
+            for input_path in collect_input_files(&library.to_string_lossy()) {
+                if compile {
+                    self.add(input_path.clone(), &library);
+                }
+                self.library_files.push(input_path);
+            }
+        }
+    }
 
-    compile_files(files_to_compile);
+    fn run(
+        mut self,
+        threads: usize,
+        force: bool,
+        overwrite: bool,
+        extensions: &[manifest::Extension],
+        diagnostics: report::Format,
+    ) {
+        if !self.library_files.is_empty() {
+            let mut program: Vec<PathBuf> = self.jobs.iter().map(|(input_path, ..)| input_path.clone()).collect();
+            for library_file in &self.library_files {
+                if !program.contains(library_file) {
+                    program.push(library_file.clone());
+                }
+            }
+            check_class_names(&program);
+        }
+        // The hash of the source and the output when the file was compiled, or
+        // none when its output was up to date
+        let results: Vec<Result<Option<(u64, String)>, Failure>> =
+            pool::map(&self.jobs, threads, |(input_path, output_path, cache)| {
+                let contents = fs::read_to_string(input_path).map_err(|e| e.to_string())?;
+                let hash = source_hash(&contents, self.kind, extensions);
+                let cache = &self.caches[*cache].1;
+                if !force && cache.is_fresh(output_path, hash) {
+                    return Ok(None);
+                }
+                manifest::check_extensions(&contents, extensions)?;
+                let output = match self.kind.render(contents.clone()) {
+                    Ok(output) => output,
+                    Err(e) => return Err(Failure::new(contents, e, self.kind)),
+                };
+                let unchanged = fs::read_to_string(output_path).is_ok_and(|existing| existing == output);
+                if !overwrite && !unchanged && !cache.is_generated(output_path) {
+                    return Err(Failure::Message(format!(
+                        "{} was not written by JackAnalyzer, use --overwrite to replace it",
+                        output_path.display()
+                    )));
+                }
+                write_output(output_path, &output)?;
+                Ok(Some((hash, output)))
+            });
+
+        let mut failed = 0;
+        let mut unchanged = 0;
+        let mut invalid = vec![];
+        let total = results.len();
+        for ((input_path, output_path, cache), result) in self.jobs.iter().zip(results) {
+            let cache = &mut self.caches[*cache].1;
+            match result {
+                Ok(Some((hash, output))) => cache.insert(output_path, hash, &output),
+                Ok(None) => unchanged += 1,
+                Err(failure) => {
+                    match failure {
+                        Failure::Diagnostics(contents, found) => invalid.push((input_path.clone(), contents, found)),
+                        Failure::Message(e) => eprintln!("{}: {}", input_path.display(), e),
+                    }
+                    cache.invalidate(output_path);
+                    failed += 1;
+                }
+            }
+        }
+        print_diagnostics(invalid, diagnostics);
+        for (_, cache) in &self.caches {
+            if let Err(e) = cache.save() {
+                eprintln!("Failed to save the build cache: {}", e);
+            }
+        }
+        print!("Compiled {} of {} files", total - failed - unchanged, total);
+        if unchanged > 0 {
+            print!(", {} unchanged", unchanged);
+        }
+        if failed > 0 {
+            print!(", {} failed", failed);
+        }
+        println!();
+        if failed > 0 {
+            process::exit(1);
+        }
+    }
 }
 
-fn compile_files(input_paths: Vec<PathBuf>) {
+// Compiles the Jack source read from standard input, writing the output to
+// standard output
+fn compile_stdin(kind: output::Kind, diagnostics: report::Format) {
+    let mut contents = String::new();
+    io::stdin()
+        .read_to_string(&mut contents)
+        .expect("Failed to read standard input");
+    match kind.render(contents.clone()) {
+        Ok(output) => print!("{}", output),
+        Err(e) => {
+            match Failure::new(contents, e, kind) {
+                Failure::Diagnostics(contents, found) => {
+                    // Named relative to the current directory, as a file there
+                    let path = env::current_dir().unwrap_or_default().join(STDIN_NAME);
+                    print_diagnostics(vec![(path, contents, found)], diagnostics);
+                }
+                Failure::Message(e) => eprintln!("{}: {}", STDIN_NAME, e),
+            }
+            process::exit(1);
+        }
+    }
+}
+
+// Why a file was not compiled: the lexical and syntax errors in its source,
+// or a message when it has none
+enum Failure {
+    Diagnostics(String, Vec<diagnostics::Diagnostic>),
+    Message(String),
+}
+
+impl Failure {
+    // Finds the errors that stopped the source compiling to the kind, falling
+    // back on the message. Only lexical errors stop tokens being written
+    fn new(source: String, message: String, kind: output::Kind) -> Failure {
+        let mut found = diagnostics::check_source(&source);
+        if kind == output::Kind::Tokens {
+            found.retain(|diagnostic| diagnostic.code == diagnostics::LEXICAL_ERROR);
+        }
+        if found.is_empty() {
+            Failure::Message(message)
+        } else {
+            Failure::Diagnostics(source, found)
+        }
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Failure {
+        Failure::Message(message)
+    }
+}
+
+// Writes the errors of each source to standard error in the format, with
+// paths relative to the current directory. A SARIF log is written even when
+// there are none
+fn print_diagnostics(sources: Vec<(PathBuf, String, Vec<diagnostics::Diagnostic>)>, format: report::Format) {
+    let mut files = vec![];
+    let mut findings = vec![];
+    for (file, (path, source, found)) in sources.into_iter().enumerate() {
+        files.push((path, source));
+        findings.extend(found.into_iter().map(|diagnostic| lint::Finding::syntax(file, diagnostic)));
+    }
+    let project = symbols::Project::new(files);
+    let base = env::current_dir().unwrap_or_default();
+    eprint!("{}", report::render(&project, &findings, &base, format));
+}
+
+// The hash the build cache keeps of a source, which covers what was written
+// for it, since outputs of two kinds may share a name, and the language
+// extensions it was compiled with
+fn source_hash(contents: &str, kind: output::Kind, extensions: &[manifest::Extension]) -> u64 {
+    let extension_names: Vec<&str> = extensions.iter().map(|extension| extension.name()).collect();
+    cache::hash(&[contents, kind.name(), &extension_names.join(",")])
+}
+
+fn write_output(output_path: &Path, output: &str) -> Result<(), String> {
+    if let Some(output_dir) = output_path.parent() {
+        fs::create_dir_all(output_dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(output_path, output).map_err(|e| e.to_string())
+}
+
+// Removes the build cache of the input directory, or of the input file's
+// directory
+fn clean_cache(argument_path: &str) {
+    let source_dir = source_directory(argument_path);
+    match cache::clean(&source_dir) {
+        Ok(true) => println!("Removed {}", source_dir.join(cache::CACHE_DIR_NAME).display()),
+        Ok(false) => println!("No build cache in {}", source_dir.display()),
+        Err(e) => {
+            eprintln!("Failed to remove the build cache: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// The input directory, or the directory of the input file
+fn source_directory(argument_path: &str) -> PathBuf {
+    let argument_path = fs::canonicalize(argument_path).expect("Invalid path provided");
+    if argument_path.is_dir() {
+        argument_path
+    } else {
+        argument_path.parent().unwrap_or(&argument_path).to_path_buf()
+    }
+}
+
+// Checks every class under the input path, then polls the files for changes
+// and checks again just the classes that were added or modified, printing
+// their diagnostics
+fn watch_files(argument_path: &str, flags: &[String]) {
+    let interval = match flags {
+        [] => DEFAULT_WATCH_INTERVAL,
+        [flag, milliseconds] if flag == "--interval" => match milliseconds.parse::<u64>() {
+            Ok(milliseconds) if milliseconds > 0 => Duration::from_millis(milliseconds),
+            _ => panic!("Invalid usage, please use:\n{}", USAGE),
+        },
+        _ => panic!("Invalid usage, please use:\n{}", USAGE),
+    };
+    let mut snapshot = watch::Snapshot::default();
+    loop {
+        let input_paths: Vec<PathBuf> = collect_input_files(argument_path)
+            .into_iter()
+            .filter(|path| is_jack_file(path))
+            .collect();
+        let newer = watch::Snapshot::take(&input_paths);
+        let changes = snapshot.changes(&newer);
+        if !changes.is_empty() {
+            for input_path in &changes.removed {
+                println!("{}: removed", input_path.display());
+            }
+            for input_path in &changes.modified {
+                check_file(input_path);
+            }
+            println!("Watching {} files for changes", input_paths.len());
+        }
+        snapshot = newer;
+        thread::sleep(interval);
+    }
+}
+
+// Prints the lexical and syntax errors in the file, or that it has none
+fn check_file(input_path: &Path) {
+    let contents = match fs::read_to_string(input_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            println!("{}: {}", input_path.display(), e);
+            return;
+        }
+    };
+    let found = diagnostics::check_source(&contents);
+    if found.is_empty() {
+        println!("{}: ok", input_path.display());
+        return;
+    }
+    let index = span::LineIndex::new(&contents);
+    for diagnostic in found {
+        let position = index.position(diagnostic.span.start);
+        println!(
+            "{}:{}:{}: {}[{}]: {}",
+            input_path.display(),
+            position.line + 1,
+            position.column + 1,
+            diagnostic.severity,
+            diagnostic.code,
+            diagnostic.message
+        );
+    }
+}
+
+// Compares two parse tree or token XML files, ignoring whitespace like the
+// course's TextComparer, and reports the path of the first element that differs
+fn compare_files(expected_path: &str, actual_path: &str) {
+    let read = |path: &str| {
+        let text = fs::read_to_string(path).expect("Should have been able to read file");
+        match parse_tree::from_xml(&text) {
+            Ok(nodes) => nodes,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(2);
+            }
+        }
+    };
+    match parse_tree::compare(&read(expected_path), &read(actual_path)) {
+        None => println!("Comparison ended successfully"),
+        Some(divergence) => {
+            let describe = |node: Option<String>| node.unwrap_or_else(|| "nothing".to_string());
+            println!("Files differ at {}", divergence.path);
+            println!("  expected: {}", describe(divergence.expected));
+            println!("  found:    {}", describe(divergence.found));
+            process::exit(1);
+        }
+    }
+}
+
+// The library directory, exiting with an error if there is no such directory
+fn find_library(library: &Path) -> PathBuf {
+    match fs::canonicalize(library) {
+        Ok(library) if library.is_dir() => library,
+        _ => {
+            eprintln!("No library directory {}", library.display());
+            process::exit(1);
+        }
+    }
+}
+
+// The input files followed by the Jack files in the library directories that
+// are not input files already
+fn with_library_files(mut input_paths: Vec<PathBuf>, libraries: &[PathBuf]) -> Vec<PathBuf> {
+    for library in libraries {
+        for input_path in collect_input_files(&find_library(library).to_string_lossy()) {
+            if !input_paths.contains(&input_path) {
+                input_paths.push(input_path);
+            }
+        }
+    }
+    input_paths
+}
+
+// Exits with an error if two of the files declare the same class
+fn check_class_names(input_paths: &[PathBuf]) {
+    let sources: Vec<(PathBuf, String)> = input_paths
+        .iter()
+        .map(|path| {
+            let contents = fs::read_to_string(path).expect("Should have been able to read file");
+            (path.clone(), contents)
+        })
+        .collect();
+    if let Err(e) = library::check_class_names(&sources) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_input_files(input_paths: &[PathBuf]) -> Vec<ast::Class> {
+    check_class_names(input_paths);
+    let mut classes = vec![];
     for input_path in input_paths {
-        if let Some(extension) = input_path.extension() {
-            if extension.to_str().unwrap_or("").to_lowercase() != JACK_FILE_EXTENSION {
-                continue;
+        let contents: String =
+            fs::read_to_string(input_path).expect("Should have been able to read file");
+        match ast::parse_classes(contents) {
+            Ok(mut parsed) => classes.append(&mut parsed),
+            Err(e) => panic!("Tried to parse {:?}, but got error {:?}", input_path, e),
+        }
+    }
+    classes
+}
+
+// Interprets the classes under the input path, starting from Main.main
+fn run_program(argument_path: &str, libraries: &[PathBuf]) {
+    let classes = parse_input_files(&with_library_files(collect_input_files(argument_path), libraries));
+    let runner = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(move || {
+            let stdin = io::stdin();
+            let mut interpreter = interpreter::Interpreter::new(stdin.lock(), io::stdout());
+            for class in classes {
+                interpreter.load_class(class);
+            }
+            interpreter.run_main()
+        })
+        .expect("Failed to start the interpreter");
+    if let Err(interpreter::Interrupt::Error(e)) = runner.join().expect("Interpreter panicked") {
+        eprintln!("Runtime error: {}", e);
+        process::exit(1);
+    }
+}
+
+// Starts an interactive session, with the classes under the input path loaded
+fn start_repl(argument_path: Option<&str>, libraries: &[PathBuf]) {
+    let input_paths = match argument_path {
+        Some(argument_path) => collect_input_files(argument_path),
+        None => vec![],
+    };
+    let classes = parse_input_files(&with_library_files(input_paths, libraries));
+    let session = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(move || {
+            let stdin = io::stdin();
+            let mut interpreter = interpreter::Interpreter::new(stdin.lock(), io::stdout());
+            for class in classes {
+                interpreter.load_class(class);
+            }
+            repl::run(&mut interpreter)
+        })
+        .expect("Failed to start the REPL");
+    if let Err(e) = session.join().expect("REPL panicked") {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+// Serves the Language Server Protocol over stdin and stdout
+fn start_language_server() {
+    let stdin = io::stdin();
+    if let Err(e) = lsp::serve(stdin.lock(), io::stdout()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+// Answers a query about the name at <line>:<column> (both counted from 1) in a
+// file, resolving names across all the Jack files in the file's directory
+fn query_position(query: &str, file_path: &str, position: &str) {
+    let (project, file, offset) = load_project_at(file_path, position);
+    let found = match query {
+        "definition" => project.definition(file, offset).map(|symbol| {
+            println!(
+                "{}: {}",
+                describe_location(&project, symbol.file, symbol.span),
+                symbol.detail
+            )
+        }),
+        "references" => {
+            let references = project.references(file, offset);
+            for occurrence in &references {
+                println!("{}", describe_location(&project, occurrence.file, occurrence.span));
+            }
+            (!references.is_empty()).then_some(())
+        }
+        "complete" => {
+            for completion in completion::complete(&project, file, offset) {
+                println!("{}\t{}", completion.label, completion.detail);
+            }
+            Some(())
+        }
+        _ => project.hover(file, offset).map(|text| print!("{}", text)),
+    };
+    if found.is_none() {
+        eprintln!("No declared name at {}", position);
+        process::exit(1);
+    }
+}
+
+// Renames the name at <line>:<column> in a file throughout the Jack files in
+// the file's directory, printing a unified diff or changing the files in place
+fn rename_symbol(file_path: &str, position: &str, new_name: &str, in_place: bool) {
+    let (project, file, offset) = load_project_at(file_path, position);
+    let changes = match rename::rename(&project, file, offset, new_name) {
+        Ok(changes) => changes,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    for change in changes {
+        if in_place {
+            write_to_file(&change.new_path, vec![change.new_text]);
+            if change.new_path != change.path {
+                fs::remove_file(&change.path).expect("Failed to remove the renamed file");
             }
         } else {
-            continue;
+            print!(
+                "{}",
+                diff::unified_diff(
+                    &change.path.display().to_string(),
+                    &change.new_path.display().to_string(),
+                    &change.old_text,
+                    &change.new_text
+                )
+            );
+        }
+    }
+}
+
+// Prints the control-flow graphs of the subroutines in a file, or of the one
+// named <class>.<subroutine>, in the Graphviz DOT language
+fn print_control_flow(file_path: &str, subroutine: Option<&str>) {
+    let source = fs::read_to_string(file_path).expect("Should have been able to read file");
+    let classes = match ast::parse_classes(source.clone()) {
+        Ok(classes) => classes,
+        Err(e) => panic!("Tried to parse {:?}, but got error {:?}", file_path, e),
+    };
+    let mut graphs = cfg::build_all(&classes);
+    if let Some(subroutine) = subroutine {
+        graphs.retain(|graph| graph.name == subroutine);
+        if graphs.is_empty() {
+            eprintln!("No subroutine {} in {}", subroutine, file_path);
+            process::exit(1);
+        }
+    }
+    print!("{}", cfg::to_dot(&graphs, &source));
+}
+
+// Prints the lossless syntax tree of a file, or with a position the token at
+// <line>:<column> and the nodes enclosing it, reporting any syntax errors
+fn print_syntax_tree(file_path: &str, position: Option<&str>) {
+    let source = fs::read_to_string(file_path).expect("Should have been able to read file");
+    let parse = cst::parse(&source);
+    let root = parse.syntax();
+    let index = span::LineIndex::new(&source);
+    match position {
+        None => print!("{}", cst::dump(&root)),
+        Some(position) => {
+            let offset = index.offset(parse_position(position));
+            let token = match root.token_at_offset(offset) {
+                Some(token) => token,
+                None => {
+                    eprintln!("No token at {}", position);
+                    process::exit(1);
+                }
+            };
+            let span = token.span();
+            println!("{:?}@{}..{} {:?}", token.kind(), span.start, span.end, token.text());
+            for node in token.parent().ancestors() {
+                let span = node.span();
+                println!("{:?}@{}..{}", node.kind(), span.start, span.end);
+            }
         }
+    }
+    let diagnostics = diagnostics::check_parse(&parse);
+    for diagnostic in &diagnostics {
+        let position = index.position(diagnostic.span.start);
+        eprintln!("{}:{}:{}: error: {}", file_path, position.line + 1, position.column + 1, diagnostic.message);
+    }
+    if !diagnostics.is_empty() {
+        process::exit(1);
+    }
+}
 
-        let input_file = PathBuf::from(&input_path);
-        let output_path = create_vm_file_path(&input_path).unwrap();
+// Prints the call graph of the classes under the input path, or with --classes
+// the graph of which classes depend on which, in the Graphviz DOT language,
+// reporting recursion and the subroutines Main.main never reaches. With --json
+// both graphs and the findings are printed as one JSON object instead
+fn print_graph(argument_path: &str, classes: bool, json: bool, libraries: &[PathBuf]) {
+    let input_paths = with_library_files(collect_input_files(argument_path), libraries);
+    let graphs = graph::ProgramGraphs::new(&parse_input_files(&input_paths));
+    if json {
+        println!("{}", graphs.to_json());
+        return;
+    }
+    let unused = graphs.unused().unwrap_or_default();
+    if classes {
+        print!("{}", graphs.classes.to_dot("classes", &[]));
+    } else {
+        print!("{}", graphs.calls.to_dot("calls", &unused));
+    }
+    for cycle in graphs.calls.cycles() {
+        let names: Vec<&str> = cycle.iter().map(|&node| graphs.calls.nodes[node].name.as_str()).collect();
+        eprintln!("Recursion: {}", names.join(" -> "));
+    }
+    for node in unused {
+        eprintln!("Unused: {} is not reachable from Main.main", graphs.calls.nodes[node].name);
+    }
+}
+
+// Lints every class under the input path with the [lint] settings of the
+// nearest jack.toml, exiting with an error if any finding is an error. With
+// --fix the safe fixes are written back before the remaining findings are shown,
+// as text or, with --format, as JSON lines or a SARIF log
+fn lint_files(argument_path: &str, flags: &[String], libraries: &[PathBuf]) {
+    let mut fix = false;
+    let mut format = report::Format::Text;
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--fix" => fix = true,
+            "--format" => match flags.next().and_then(|name| report::Format::new(name)) {
+                Some(name) => format = name,
+                None => panic!("Invalid usage, please use:\n{}", USAGE),
+            },
+            _ => panic!("Invalid usage, please use:\n{}", USAGE),
+        }
+    }
+    let mut libraries = libraries.to_vec();
+    let config = match find_project_file(Path::new(argument_path)) {
+        Some(path) => {
+            let manifest = load_manifest(&path);
+            libraries.extend(manifest.libraries);
+            manifest.lint
+        }
+        None => lint::LintConfig::default(),
+    };
+    // The library classes are linted with the project's, but only the
+    // findings in the project's own files are reported
+    let project_files = collect_input_files(argument_path).len();
+    let input_paths = with_library_files(collect_input_files(argument_path), &libraries);
+    check_class_names(&input_paths);
+    let lint_project = |project: &symbols::Project| {
+        let mut findings = lint::lint(project, &config);
+        findings.retain(|finding| finding.file < project_files);
+        findings
+    };
+    let load = || {
+        let sources = input_paths
+            .iter()
+            .map(|path| {
+                let contents = fs::read_to_string(path).expect("Should have been able to read file");
+                (path.clone(), contents)
+            })
+            .collect();
+        symbols::Project::new(sources)
+    };
 
-        compile_file(input_file, &output_path);
+    let mut project = load();
+    let mut findings = lint_project(&project);
+    if fix && findings.iter().any(|finding| !finding.fix.is_empty()) {
+        for (file, source) in project.files.iter().enumerate() {
+            let edits: Vec<&lint::Edit> = findings
+                .iter()
+                .filter(|finding| finding.file == file)
+                .flat_map(|finding| &finding.fix)
+                .collect();
+            if !edits.is_empty() {
+                write_to_file(&source.path, vec![lint::apply_fixes(&source.text, &edits)]);
+            }
+        }
+        project = load();
+        findings = lint_project(&project);
+    }
+    let base = env::current_dir().expect("Failed to read the current directory");
+    print!("{}", report::render(&project, &findings, &base, format));
+    if findings
+        .iter()
+        .any(|finding| finding.severity == diagnostics::Severity::Error)
+    {
+        process::exit(1);
     }
 }
 
-fn compile_file(input_path: PathBuf, output_path: &PathBuf) {
-    let contents: String =
-        fs::read_to_string(&input_path).expect("Should have been able to read file");
+// The project file in the input directory, or the directory of the input file,
+// or the closest of their ancestors that has one
+fn find_project_file(argument_path: &Path) -> Option<PathBuf> {
+    let argument_path = fs::canonicalize(argument_path).ok()?;
+    let start = if argument_path.is_dir() {
+        argument_path.as_path()
+    } else {
+        argument_path.parent()?
+    };
+    start
+        .ancestors()
+        .map(|directory| directory.join(PROJECT_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+// Loads the Jack files in the directory of a file and finds the byte offset of
+// <line>:<column> (both counted from 1) in the file
+fn load_project_at(file_path: &str, position: &str) -> (symbols::Project, usize, usize) {
+    let file_path = fs::canonicalize(file_path).expect("Invalid path provided");
+    let directory = file_path.parent().expect("Invalid path provided");
+    let sources = collect_input_files(&directory.to_string_lossy())
+        .into_iter()
+        .map(|path| {
+            let contents = fs::read_to_string(&path).expect("Should have been able to read file");
+            (path, contents)
+        })
+        .collect();
+    let project = symbols::Project::new(sources);
+    let file = project
+        .file_index(&file_path)
+        .expect("Only positions in .jack files are supported");
+
+    let index = span::LineIndex::new(&project.files[file].text);
+    let offset = index.offset(parse_position(position));
+    (project, file, offset)
+}
 
-    // Parse the file
-    let xml = parser::parse(contents);
-    // Append the output
-    match xml {
-        Ok(code) => write_to_file(output_path, vec![code]),
-        Err(e) => panic!("Tried to compile, but got error {:?}", e),
+// <line>:<column>, both counted from 1
+fn parse_position(position: &str) -> span::Position {
+    let (line, column) = match position.split_once(':') {
+        Some((line, column)) => (line.parse::<usize>(), column.parse::<usize>()),
+        None => panic!("Invalid position {}, expected <line>:<column>", position),
+    };
+    match (line, column) {
+        (Ok(line), Ok(column)) if line > 0 && column > 0 => span::Position {
+            line: line - 1,
+            column: column - 1,
+        },
+        _ => panic!("Invalid position {}, expected <line>:<column>", position),
     }
 }
 
+// <path>:<line>:<column>, counting lines and columns from 1
+fn describe_location(project: &symbols::Project, file: usize, span: span::Span) -> String {
+    let file = &project.files[file];
+    let position = span::LineIndex::new(&file.text).position(span.start);
+    format!(
+        "{}:{}:{}",
+        file.path.display(),
+        position.line + 1,
+        position.column + 1
+    )
+}
+
+// Translates every class under the input path into one C program, written next
+// to the input as <directory name>.c or <file name>.c
+fn transpile_to_c(argument_path: &str, libraries: &[PathBuf]) {
+    let classes = parse_input_files(&with_library_files(collect_input_files(argument_path), libraries));
+
+    let program = match c_backend::transpile(&classes) {
+        Ok(program) => program,
+        Err(e) => panic!("Tried to translate to C, but got error {:?}", e),
+    };
+
+    let argument_path = fs::canonicalize(argument_path).expect("Invalid path provided");
+    let output_path = if argument_path.is_dir() {
+        let name = argument_path.file_name().unwrap_or_default().to_owned();
+        argument_path.join(name).with_extension(C_FILE_EXTENSION)
+    } else {
+        argument_path.with_extension(C_FILE_EXTENSION)
+    };
+    write_to_file(&output_path, vec![program]);
+}
+
 fn write_to_file(path: &PathBuf, s: Vec<String>) {
     let mut file = OpenOptions::new()
         .write(true)
@@ -71,16 +1047,6 @@ fn write_to_file(path: &PathBuf, s: Vec<String>) {
         .open(path)
         .unwrap();
     for line in s {
-        file.write(line.as_bytes()).unwrap();
-    }
-}
-
-fn create_vm_file_path(input: &Path) -> Result<PathBuf, String> {
-    if !input.is_file() {
-        return Err(format!("Input path {:?} is not a file", input));
+        file.write_all(line.as_bytes()).unwrap();
     }
-    // Input is a file, change its extension to XML_FILE_EXTENSION
-    let mut new_file_path = input.to_path_buf();
-    new_file_path.set_extension(XML_FILE_EXTENSION);
-    Ok(new_file_path)
 }
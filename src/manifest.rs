@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lint::LintConfig;
+use crate::parse_tree::Format;
+use crate::span::LineIndex;
+use crate::tokeniser::tokenise_with_recovery;
+use crate::tokens::{Symbol, TokenType};
+use crate::toml::{Document, Value};
+
+// The project file, jack.toml, which says how to build a project so that
+// everyone builds it the same way:
+//
+//     [build]
+//     sources = ["src"]          # directories of the project's classes
+//     libraries = ["lib"]        # directories of shared classes
+//     compile-libraries = false  # whether to write outputs for them too
+//     output = "build"           # where outputs go, instead of next to the sources
+//     target = "xml"             # what to write: xml, json or sexp
+//     suffix = ""                # added to the name of each output
+//     extension = "xml"          # of each output, by default the target
+//     extensions = ["shift-operators"]
+//
+//     [lint]
+//     magic-number = "off"
+//
+// Directories are relative to the directory of the project file. Every key may
+// be left out: the sources are then the project directory itself, and all
+// language extensions are enabled.
+
+// Language features beyond the course's Jack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extension {
+    // The binary and unary ^ (shift left) and # (shift right) operators
+    ShiftOperators,
+}
+
+pub const EXTENSIONS: [Extension; 1] = [Extension::ShiftOperators];
+
+impl Extension {
+    pub fn new(name: &str) -> Option<Extension> {
+        EXTENSIONS.iter().copied().find(|extension| extension.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Extension::ShiftOperators => "shift-operators",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    // The directory of the project file
+    pub root: PathBuf,
+    pub sources: Vec<PathBuf>,
+    pub libraries: Vec<PathBuf>,
+    pub compile_libraries: bool,
+    pub output: Option<PathBuf>,
+    pub target: Format,
+    pub suffix: Option<String>,
+    pub extension: Option<String>,
+    pub extensions: Vec<Extension>,
+    pub lint: LintConfig,
+}
+
+impl Manifest {
+    // The settings of a directory without a project file
+    pub fn new(root: &Path) -> Manifest {
+        Manifest {
+            root: root.to_path_buf(),
+            sources: vec![root.to_path_buf()],
+            libraries: vec![],
+            compile_libraries: false,
+            output: None,
+            target: Format::Xml,
+            suffix: None,
+            extension: None,
+            extensions: EXTENSIONS.to_vec(),
+            lint: LintConfig::default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let root = path.parent().unwrap_or(Path::new("."));
+        Manifest::from_document(root, &Document::parse(&text)?)
+    }
+
+    pub fn from_document(root: &Path, document: &Document) -> Result<Manifest, String> {
+        let mut manifest = Manifest::new(root);
+        for entry in document.section("build") {
+            let error = |expected: &str| format!("line {}: {} must be {}, not {}", entry.line, entry.key, expected, entry.value);
+            match entry.key.as_str() {
+                "sources" | "libraries" => {
+                    let directories = strings(&entry.value)
+                        .ok_or_else(|| error("an array of directories"))?
+                        .into_iter()
+                        .map(|directory| root.join(directory))
+                        .collect();
+                    if entry.key == "sources" {
+                        manifest.sources = directories;
+                    } else {
+                        manifest.libraries = directories;
+                    }
+                }
+                "compile-libraries" => match entry.value {
+                    Value::Boolean(compile) => manifest.compile_libraries = compile,
+                    _ => return Err(error("true or false")),
+                },
+                "output" => {
+                    let directory = entry.value.as_str().ok_or_else(|| error("a directory"))?;
+                    manifest.output = Some(root.join(directory));
+                }
+                "target" => {
+                    let target = entry.value.as_str().ok_or_else(|| error("a string"))?;
+                    manifest.target = match Format::new(target) {
+                        Some(format) => format,
+                        None if ["vm", "asm", "hack"].contains(&target) => {
+                            return Err(format!(
+                                "line {}: target {} is not supported yet, use xml, json or sexp",
+                                entry.line, target
+                            ))
+                        }
+                        None => return Err(error("xml, json or sexp")),
+                    };
+                }
+                "suffix" => {
+                    let suffix = entry.value.as_str().ok_or_else(|| error("a string"))?;
+                    manifest.suffix = Some(suffix.to_string());
+                }
+                "extension" => {
+                    let extension = entry.value.as_str().ok_or_else(|| error("a string"))?;
+                    manifest.extension = Some(extension.trim_start_matches('.').to_string());
+                }
+                "extensions" => {
+                    manifest.extensions = strings(&entry.value)
+                        .ok_or_else(|| error("an array of extension names"))?
+                        .into_iter()
+                        .map(|name| {
+                            Extension::new(name)
+                                .ok_or_else(|| format!("line {}: unknown extension {}", entry.line, name))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(format!("line {}: unknown build setting {}", entry.line, entry.key)),
+            }
+        }
+        manifest.lint = LintConfig::from_document(document)?;
+        Ok(manifest)
+    }
+}
+
+fn strings(value: &Value) -> Option<Vec<&str>> {
+    match value {
+        Value::Array(values) => values.iter().map(|value| value.as_str()).collect(),
+        _ => None,
+    }
+}
+
+// Fails at the first use of a language extension that is not enabled.
+pub fn check_extensions(source: &str, enabled: &[Extension]) -> Result<(), String> {
+    if enabled.contains(&Extension::ShiftOperators) {
+        return Ok(());
+    }
+    let (tokens, _) = tokenise_with_recovery(source);
+    let shift = tokens.iter().find(|token| {
+        matches!(
+            token.token,
+            TokenType::Symbol(Symbol::ShiftLeft | Symbol::ShiftRight)
+        )
+    });
+    match shift {
+        Some(token) => {
+            let position = LineIndex::new(source).position(token.span.start);
+            Err(format!(
+                "line {}: the {} operator needs the {} extension",
+                position.line + 1,
+                &source[token.span.start..token.span.end],
+                Extension::ShiftOperators.name()
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Severity;
+
+    #[test]
+    fn test_manifest() {
+        let root = Path::new("/work/game");
+        let document = Document::parse(
+            "[build]
+sources = [\"src\", \"extra\"]
+libraries = [\"../shared\"]
+compile-libraries = true
+output = \"build\"
+target = \"json\"
+extension = \".tree\"
+extensions = []
+
+[lint]
+magic-number = \"error\"
+",
+        )
+        .unwrap();
+        let manifest = Manifest::from_document(root, &document).unwrap();
+        assert_eq!(manifest.sources, vec![root.join("src"), root.join("extra")]);
+        assert_eq!(manifest.libraries, vec![root.join("../shared")]);
+        assert!(manifest.compile_libraries);
+        assert_eq!(manifest.output, Some(root.join("build")));
+        assert_eq!(manifest.target, Format::Json);
+        assert_eq!(manifest.suffix, None);
+        assert_eq!(manifest.extension, Some("tree".to_string()));
+        assert!(manifest.extensions.is_empty());
+        assert_eq!(manifest.lint.severity("magic-number"), Some(Severity::Error));
+
+        assert_eq!(
+            Manifest::from_document(root, &Document::default()).unwrap(),
+            Manifest::new(root)
+        );
+        let error = |text: &str| Manifest::from_document(root, &Document::parse(text).unwrap()).unwrap_err();
+        assert_eq!(
+            error("[build]\ntarget = \"vm\""),
+            "line 2: target vm is not supported yet, use xml, json or sexp"
+        );
+        assert_eq!(error("[build]\nsources = \"src\""), "line 2: sources must be an array of directories, not \"src\"");
+        assert_eq!(error("[build]\nextensions = [\"goto\"]"), "line 2: unknown extension goto");
+        assert_eq!(error("[build]\nsource = []"), "line 2: unknown build setting source");
+    }
+
+    #[test]
+    fn test_check_extensions() {
+        let source = "class A {\n  function int f(int x) { return x ^ 2; }\n}";
+        assert_eq!(check_extensions(source, &EXTENSIONS), Ok(()));
+        assert_eq!(
+            check_extensions(source, &[]),
+            Err("line 2: the ^ operator needs the shift-operators extension".to_string())
+        );
+        assert_eq!(check_extensions("class A { /* x # 2 */ }", &[]), Ok(()));
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+
+use crate::parse_tree::{self, Format};
+use crate::parser::parse;
+use crate::tokeniser::{format_tokens_for_display, tokenise};
+
+// What is written for each source file, and what the output files are called.
+// An output is named after its source, <name><suffix>.<extension>, and is
+// written next to the source or, given an output directory, at the same place
+// relative to the output directory as the source is to its source directory.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    ParseTree(Format),
+    // The course's tokeniser output
+    Tokens,
+}
+
+impl Kind {
+    pub fn render(&self, source: String) -> Result<String, String> {
+        match self {
+            Kind::ParseTree(format) => parse(source).and_then(|xml| parse_tree::render(&xml, *format)),
+            Kind::Tokens => {
+                tokenise(source).map(|tokens| format!("<tokens>\n{}</tokens>\n", format_tokens_for_display(tokens)))
+            }
+        }
+    }
+
+    // Distinguishes the kinds, and the formats of parse trees, in the build cache
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::ParseTree(format) => format.extension(),
+            Kind::Tokens => "tokens",
+        }
+    }
+
+    // The course's names: Name.xml for the parse tree and NameT.xml for the
+    // tokens
+    pub fn default_naming(&self) -> Naming {
+        match self {
+            Kind::ParseTree(format) => Naming {
+                suffix: String::new(),
+                extension: format.extension().to_string(),
+            },
+            Kind::Tokens => Naming {
+                suffix: "T".to_string(),
+                extension: "xml".to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Naming {
+    pub suffix: String,
+    pub extension: String,
+}
+
+impl Naming {
+    pub fn output_path(&self, input_path: &Path, source_dir: &Path, output_dir: Option<&Path>) -> PathBuf {
+        let mut file_name = input_path.file_stem().unwrap_or_default().to_os_string();
+        file_name.push(&self.suffix);
+        file_name.push(".");
+        file_name.push(&self.extension);
+        let path = input_path.with_file_name(file_name);
+        match output_dir {
+            Some(output_dir) => output_dir.join(path.strip_prefix(source_dir).unwrap_or(&path)),
+            None => path,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_path() {
+        let input = Path::new("/work/src/game/Square.jack");
+        let source_dir = Path::new("/work/src");
+        let tokens = Kind::Tokens.default_naming();
+        assert_eq!(tokens.output_path(input, source_dir, None), Path::new("/work/src/game/SquareT.xml"));
+        let json = Kind::ParseTree(Format::Json).default_naming();
+        assert_eq!(
+            json.output_path(input, source_dir, Some(Path::new("/work/build"))),
+            Path::new("/work/build/game/Square.json")
+        );
+        let naming = Naming {
+            suffix: ".tree".to_string(),
+            extension: "txt".to_string(),
+        };
+        assert_eq!(
+            naming.output_path(input, source_dir, Some(Path::new("out"))),
+            Path::new("out/game/Square.tree.txt")
+        );
+
+        // Outputs of different kinds named alike must not share a cache entry
+        assert_ne!(Kind::Tokens.name(), Kind::ParseTree(Format::Xml).name());
+        assert_ne!(Kind::ParseTree(Format::Json).name(), Kind::ParseTree(Format::Xml).name());
+
+        assert_eq!(
+            Kind::Tokens.render("let x;".to_string()).unwrap(),
+            "<tokens>\n<keyword> let </keyword>\n<identifier> x </identifier>\n<symbol> ; </symbol>\n</tokens>\n"
+        );
+    }
+}
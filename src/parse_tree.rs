@@ -0,0 +1,294 @@
+use crate::json::Json;
+
+// The parse tree that parser.rs writes as XML, read back into nodes so that it
+// can be written in other formats. The XML has one tag per line: an element is
+// an opening and a closing tag around its children, and a token is a single
+// line such as "<keyword> class </keyword>".
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Xml,
+    Json,
+    Sexp,
+}
+
+impl Format {
+    pub fn new(name: &str) -> Option<Format> {
+        match name {
+            "xml" => Some(Format::Xml),
+            "json" => Some(Format::Json),
+            "sexp" => Some(Format::Sexp),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Xml => "xml",
+            Format::Json => "json",
+            Format::Sexp => "sexp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Element { tag: String, children: Vec<Node> },
+    // The token text with XML escapes undone
+    Token { kind: String, text: String },
+}
+
+// Reads the tree out of the parser's XML, returning the top-level elements.
+pub fn from_xml(xml: &str) -> Result<Vec<Node>, String> {
+    // The open elements, innermost last, each with the children read so far
+    let mut open: Vec<(String, Vec<Node>)> = vec![(String::new(), vec![])];
+    for (index, line) in xml.lines().enumerate() {
+        let error = |message: &str| format!("line {}: {}", index + 1, message);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (tag, rest) = line
+            .strip_prefix('<')
+            .and_then(|line| line.split_once('>'))
+            .ok_or_else(|| error("expected a tag"))?;
+        if let Some(closed) = tag.strip_prefix('/') {
+            match open.pop() {
+                Some((tag, children)) if !open.is_empty() && tag == closed => {
+                    let element = Node::Element { tag, children };
+                    open.last_mut().unwrap().1.push(element);
+                }
+                _ => return Err(error(&format!("unexpected </{}>", closed))),
+            }
+        } else if rest.is_empty() {
+            open.push((tag.to_string(), vec![]));
+        } else {
+            let text = rest
+                .strip_suffix(&format!("</{}>", tag))
+                .ok_or_else(|| error(&format!("expected </{}> at the end of the line", tag)))?;
+            // The text is written with a space either side
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            let text = text.strip_suffix(' ').unwrap_or(text);
+            open.last_mut().unwrap().1.push(Node::Token {
+                kind: tag.to_string(),
+                text: unescape(text),
+            });
+        }
+    }
+    match open.pop() {
+        Some((_, nodes)) if open.is_empty() => Ok(nodes),
+        Some((tag, _)) => Err(format!("<{}> is never closed", tag)),
+        None => unreachable!("the top level is never closed"),
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+// Writes the parser's XML in the format, one top-level node after another.
+pub fn render(xml: &str, format: Format) -> Result<String, String> {
+    if format == Format::Xml {
+        return Ok(xml.to_string());
+    }
+    let mut output = String::new();
+    for node in from_xml(xml)? {
+        match format {
+            Format::Json => output.push_str(&to_json(&node).to_string()),
+            _ => write_sexp(&node, 0, &mut output),
+        }
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+// {"type": <tag>, "children": [...]} for elements and
+// {"type": <kind>, "value": <text>} for tokens
+pub fn to_json(node: &Node) -> Json {
+    match node {
+        Node::Element { tag, children } => Json::object(vec![
+            ("type", tag.as_str().into()),
+            (
+                "children",
+                children.iter().map(to_json).collect::<Vec<Json>>().into(),
+            ),
+        ]),
+        Node::Token { kind, text } => Json::object(vec![
+            ("type", kind.as_str().into()),
+            ("value", text.as_str().into()),
+        ]),
+    }
+}
+
+// (<tag> <children>...) for elements and (<kind> "<text>") for tokens, with
+// each child on its own line and the closing parentheses gathered at the end.
+fn write_sexp(node: &Node, depth: usize, output: &mut String) {
+    match node {
+        Node::Element { tag, children } => {
+            output.push('(');
+            output.push_str(tag);
+            for child in children {
+                output.push('\n');
+                output.push_str(&"  ".repeat(depth + 1));
+                write_sexp(child, depth + 1, output);
+            }
+            output.push(')');
+        }
+        Node::Token { kind, text } => {
+            let text = text.replace('\\', "\\\\").replace('"', "\\\"");
+            output.push_str(&format!("({} \"{}\")", kind, text));
+        }
+    }
+}
+
+// Where two trees first differ: the path to the element, such as
+// class/subroutineDec[2]/subroutineBody/statements/letStatement[1], and the
+// expected and found nodes, either of which may be missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub path: String,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+}
+
+// Compares the trees the way the course's TextComparer compares files, with
+// whitespace ignored, returning the first difference in document order.
+pub fn compare(expected: &[Node], found: &[Node]) -> Option<Divergence> {
+    compare_children("", expected, found)
+}
+
+fn compare_children(parent: &str, expected: &[Node], found: &[Node]) -> Option<Divergence> {
+    for i in 0..expected.len().max(found.len()) {
+        let (reference, siblings) = match expected.get(i) {
+            Some(node) => (node, expected),
+            None => (&found[i], found),
+        };
+        let path = format!("{}{}", parent, step(reference, i, siblings));
+        match (expected.get(i), found.get(i)) {
+            (
+                Some(Node::Element { tag, children }),
+                Some(Node::Element {
+                    tag: found_tag,
+                    children: found_children,
+                }),
+            ) if tag == found_tag => {
+                if let Some(divergence) = compare_children(&format!("{}/", path), children, found_children) {
+                    return Some(divergence);
+                }
+            }
+            (Some(Node::Token { kind, text }), Some(Node::Token { kind: found_kind, text: found_text }))
+                if kind == found_kind && without_whitespace(text) == without_whitespace(found_text) => {}
+            (expected, found) => {
+                return Some(Divergence {
+                    path,
+                    expected: expected.map(describe),
+                    found: found.map(describe),
+                })
+            }
+        }
+    }
+    None
+}
+
+// The name of the node in a path, indexed from 1 among the siblings with the
+// same name when there are several.
+fn step(node: &Node, position: usize, siblings: &[Node]) -> String {
+    let own = name(node);
+    let same = |sibling: &&Node| name(sibling) == own;
+    if siblings.iter().filter(same).count() > 1 {
+        let index = siblings[..position].iter().filter(same).count() + 1;
+        format!("{}[{}]", own, index)
+    } else {
+        own.to_string()
+    }
+}
+
+fn name(node: &Node) -> &str {
+    match node {
+        Node::Element { tag, .. } => tag,
+        Node::Token { kind, .. } => kind,
+    }
+}
+
+fn describe(node: &Node) -> String {
+    match node {
+        Node::Element { tag, .. } => format!("<{}>", tag),
+        Node::Token { kind, text } => format!("<{}> {} </{}>", kind, text, kind),
+    }
+}
+
+fn without_whitespace(text: &str) -> String {
+    text.split_whitespace().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_formats() {
+        let xml = parse("class A { function void f() { do g(x < \"a\\\"); return; } }".to_string())
+            .unwrap();
+        let nodes = from_xml(&xml).unwrap();
+        assert_eq!(nodes.len(), 1);
+        let json = render(&xml, Format::Json).unwrap();
+        assert!(json.starts_with(
+            r#"{"type":"class","children":[{"type":"keyword","value":"class"},{"type":"identifier","value":"A"},"#
+        ));
+        assert!(json.contains(r#"{"type":"symbol","value":"<"}"#));
+        assert_eq!(Json::parse(json.trim()).unwrap(), to_json(&nodes[0]));
+
+        let sexp = render(&xml, Format::Sexp).unwrap();
+        assert!(sexp.starts_with("(class\n  (keyword \"class\")\n  (identifier \"A\")\n  (symbol \"{\")\n  (subroutineDec\n"));
+        assert!(sexp.contains("\n    (parameterList)\n"));
+        assert!(sexp.contains("(symbol \"<\")"));
+        assert!(sexp.contains("(stringConstant \"a\\\\\")"));
+        assert!(sexp.ends_with("(symbol \"}\"))\n"));
+        assert_eq!(render(&xml, Format::Xml).unwrap(), xml);
+
+        assert_eq!(
+            from_xml("<class>\n</statements>\n").unwrap_err(),
+            "line 2: unexpected </statements>"
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected = from_xml(
+            &parse("class A { function void f() { return; } method void g() { let x = 1; do h(); let y = 2; } }".to_string())
+                .unwrap(),
+        )
+        .unwrap();
+        let same = from_xml(&parse("class  A{function void f(){return;}method void g(){let x=1;do h();let y=2;}}".to_string()).unwrap())
+            .unwrap();
+        assert_eq!(compare(&expected, &same), None);
+
+        let changed = from_xml(
+            &parse("class A { function void f() { return; } method void g() { let x = 1; do h(); do y(); } }".to_string())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            compare(&expected, &changed),
+            Some(Divergence {
+                path: "class/subroutineDec[2]/subroutineBody/statements/letStatement[2]".to_string(),
+                expected: Some("<letStatement>".to_string()),
+                found: Some("<doStatement>".to_string()),
+            })
+        );
+
+        let shorter = from_xml(&parse("class A { function void f() { return; } }".to_string()).unwrap()).unwrap();
+        assert_eq!(
+            compare(&expected, &shorter),
+            Some(Divergence {
+                path: "class/subroutineDec[2]".to_string(),
+                expected: Some("<subroutineDec>".to_string()),
+                found: Some("<symbol> } </symbol>".to_string()),
+            })
+        );
+    }
+}
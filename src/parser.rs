@@ -3,16 +3,12 @@ use crate::tokeniser::tokenise;
 use crate::tokens::{Keyword, Symbol, TokenType};
 
 pub fn parse(input_data: String) -> Result<String, String>{
-    let tokens = tokenise(input_data);
+    let tokens = tokenise(input_data).map_err(|err| format!("ERROR: {}", err))?;
     let mut output = String::new();
     let mut token_stream: TokenStream = TokenStream::new(&tokens);
-    // dbg!(&tokens);
     while let Some(token) = token_stream.peek() {
         if let Err(err) = match token.token.clone() {
-            TokenType::Keyword(keyword) => match keyword {
-                Keyword::Class => compile_class(&mut token_stream, &mut output),
-                _ => return Err("Compilation call to something not the class at the top level".to_string()),
-            },
+            TokenType::Keyword(Keyword::Class) => compile_class(&mut token_stream, &mut output),
             _ => return Err("Compilation call to something not the class at the top level".to_string()),
         } {
             return Err(format!("ERROR: {}", err));
@@ -106,14 +102,16 @@ fn compile_class_var_dec(stream: &mut TokenStream, output: &mut String) -> Resul
 }
 
 // Helper to parse a keyword from a list of valid keywords
-fn parse_keyword(
+pub(crate) fn parse_keyword(
     stream: &mut TokenStream,
     valid_keywords: &[Keyword],
 ) -> Result<TokenType, String> {
-    if let Some(token) = stream.advance_prev() {
+    if let Some(token) = stream.peek() {
         if let TokenType::Keyword(keyword) = &token.token {
-            if valid_keywords.contains(&keyword) {
-                return Ok(token.token.clone());
+            if valid_keywords.contains(keyword) {
+                let token = token.token.clone();
+                stream.advance();
+                return Ok(token);
             }
         }
         Err(format!(
@@ -126,28 +124,32 @@ fn parse_keyword(
 }
 
 // Helper to parse a type (int, char, boolean, or class name)
-fn parse_type(stream: &mut TokenStream, allow_void: bool) -> Result<TokenType, String> {
-    if let Some(token) = stream.advance_prev() {
-        match &token.token {
+pub(crate) fn parse_type(stream: &mut TokenStream, allow_void: bool) -> Result<TokenType, String> {
+    if let Some(token) = stream.peek() {
+        let token = match &token.token {
             TokenType::Keyword(Keyword::Int)
             | TokenType::Keyword(Keyword::Char)
             | TokenType::Keyword(Keyword::Boolean)
-            | TokenType::Identifier(_) => Ok(token.token.clone()),
+            | TokenType::Identifier(_) => token.token.clone(),
 
-            TokenType::Keyword(Keyword::Void) if allow_void => Ok(token.token.clone()),
+            TokenType::Keyword(Keyword::Void) if allow_void => token.token.clone(),
 
-            _ => Err(format!("Expected a type, found {:?}", token.token)),
-        }
+            _ => return Err(format!("Expected a type, found {:?}", token.token)),
+        };
+        stream.advance();
+        Ok(token)
     } else {
         Err("Unexpected end of tokens".to_string())
     }
 }
 
 // Helper to parse an identifier
-fn parse_identifier(stream: &mut TokenStream) -> Result<TokenType, String> {
-    if let Some(token) = stream.advance_prev() {
+pub(crate) fn parse_identifier(stream: &mut TokenStream) -> Result<TokenType, String> {
+    if let Some(token) = stream.peek() {
         if let TokenType::Identifier(_) = token.token {
-            Ok(token.token.clone())
+            let token = token.token.clone();
+            stream.advance();
+            Ok(token)
         } else {
             Err(format!("Expected an identifier, found {:?}", token.token))
         }
@@ -160,7 +162,7 @@ fn parse_identifier(stream: &mut TokenStream) -> Result<TokenType, String> {
 fn parse_operator(stream: &mut TokenStream, valid_symbols: &[Symbol]) -> Result<TokenType, String> {
     if let Some(token) = stream.peek() {
         if let TokenType::Symbol(symbol) = &token.token {
-            if valid_symbols.contains(&symbol) {
+            if valid_symbols.contains(symbol) {
                 return Ok(token.token.clone());
             }
         }
@@ -298,10 +300,7 @@ fn compile_statements(stream: &mut TokenStream, output: &mut String) -> Result<(
             TokenType::Keyword(Keyword::If) => compile_if(stream, output)?,
             TokenType::Keyword(Keyword::While) => compile_while(stream, output)?,
             TokenType::Keyword(Keyword::Do) => compile_do(stream, output)?,
-            TokenType::Keyword(Keyword::Return) => {
-                compile_return(stream, output)?;
-                break;
-            }
+            TokenType::Keyword(Keyword::Return) => compile_return(stream, output)?,
 
             _ => break,
         }
@@ -319,7 +318,7 @@ fn compile_do(stream: &mut TokenStream, output: &mut String) -> Result<(), Strin
     stream.expect(&TokenType::Keyword(Keyword::Do))?;
     write_token(&Keyword::Do, output);
 
-    compile_subroutine_call_full(stream, output)?;
+    compile_subroutine_call(stream, output)?;
 
     stream.expect(&TokenType::Symbol(Symbol::SemiColon))?;
     write_token(&Symbol::SemiColon, output);
@@ -328,12 +327,10 @@ fn compile_do(stream: &mut TokenStream, output: &mut String) -> Result<(), Strin
     Ok(())
 }
 
-// Compiles a subroutine call.
-fn compile_subroutine_call_full(stream: &mut TokenStream, output: &mut String) -> Result<(), String> {
-    // A subroutine call can be of the form:
-    // subroutineName(expressionList) OR
-    // className|varName.subroutineName(expressionList)
-
+// Compiles a subroutine call, of the form
+// subroutineName(expressionList) OR
+// className|varName.subroutineName(expressionList)
+fn compile_subroutine_call(stream: &mut TokenStream, output: &mut String) -> Result<(), String> {
     // Start by checking for an identifier (class/var/subroutine name)
     if let Some(token) = stream.peek() {
         if let TokenType::Identifier(identifier) = &token.token {
@@ -346,16 +343,6 @@ fn compile_subroutine_call_full(stream: &mut TokenStream, output: &mut String) -
         return Err("Unexpected end of tokens while parsing subroutine call".to_string());
     }
 
-    // Delegate to `compile_subroutine_call_simple` to handle the rest
-    compile_subroutine_call_simple(stream, output)?;
-
-    Ok(())
-}
-
-// Compile a subroutine when the token that may or may not come before a potential
-// dot has already been compiled
-fn compile_subroutine_call_simple(stream: &mut TokenStream, output: &mut String) -> Result<(), String> {
-
     // Look for a '.' or '(' to determine the form of the subroutine call
     if let Some(token) = stream.peek() {
         match &token.token {
@@ -548,7 +535,7 @@ fn compile_expression(stream: &mut TokenStream, output: &mut String) -> Result<(
 
     compile_term(stream, output)?;
 
-    let op = parse_operator(
+    while let Ok(operator) = parse_operator(
         stream,
         &[
             Symbol::Plus,
@@ -563,15 +550,10 @@ fn compile_expression(stream: &mut TokenStream, output: &mut String) -> Result<(
             Symbol::ShiftLeft,
             Symbol::ShiftRight,
         ],
-    );
-
-    match op {
-        Ok(operator) => {
-            stream.expect(&operator)?;
-            write_token(&operator, output);
-            compile_term(stream, output)?;
-        }
-        Err(_) => {}
+    ) {
+        stream.expect(&operator)?;
+        write_token(&operator, output);
+        compile_term(stream, output)?;
     }
 
     write_close_tag(TAG, output);
@@ -594,18 +576,13 @@ fn compile_term(stream: &mut TokenStream, output: &mut String) -> Result<(), Str
             // Handle constants (integer and string literals)
             TokenType::IntegerConstant(_) | TokenType::StringConstant(_) => {
                 write_token(&token.token, output);
-                stream.advance().unwrap();
+                stream.advance();
             }
 
             // Handle keyword constants (true, false, null, this)
-            TokenType::Keyword(keyword)
-                if matches!(
-                    keyword,
-                    Keyword::True | Keyword::False | Keyword::Null | Keyword::This
-                ) =>
-            {
+            TokenType::Keyword(Keyword::True | Keyword::False | Keyword::Null | Keyword::This) => {
                 write_token(&token.token, output);
-                stream.advance().unwrap();
+                stream.advance();
             }
 
             // Handle unary operators followed by a term (-term | ~term | ^term | #term)
@@ -615,14 +592,14 @@ fn compile_term(stream: &mut TokenStream, output: &mut String) -> Result<(), Str
             | TokenType::Symbol(Symbol::ShiftRight) => {
                 let operator = token.token.clone(); // Save the operator
                 write_token(&operator, output);
-                stream.advance().unwrap();
+                stream.advance();
                 compile_term(stream, output)?; // Compile the term
             }
 
             // Handle expressions in parentheses: (expression)
             TokenType::Symbol(Symbol::BracketLeft) => {
                 write_token(&token.token, output); // Write '('
-                stream.advance().unwrap();
+                stream.advance();
                 compile_expression(stream, output)?; // Compile the inner expression
                 stream.expect(&TokenType::Symbol(Symbol::BracketRight))?;
                 write_token(&TokenType::Symbol(Symbol::BracketRight), output); // Write ')'
@@ -630,29 +607,27 @@ fn compile_term(stream: &mut TokenStream, output: &mut String) -> Result<(), Str
 
             // Handle identifiers (variable, array entry, or subroutine call)
             TokenType::Identifier(_) => {
-                let identifier = token.token.clone(); // Save the identifier
-                write_token(&identifier, output);
-                stream.advance().unwrap(); // Advance past the identifier
-
-                if let Some(next_token) = stream.peek() {
-                    match next_token.token {
-                        TokenType::Symbol(Symbol::BracketSquareLeft) => {
-                            // Array entry: varName[expression]
-                            write_token(&TokenType::Symbol(Symbol::BracketSquareLeft), output);
-                            stream.advance().unwrap(); // Consume '['
-                            compile_expression(stream, output)?; // Compile the expression
-                            stream.expect(&TokenType::Symbol(Symbol::BracketSquareRight))?;
-                            write_token(&TokenType::Symbol(Symbol::BracketSquareRight), output);
-                        }
-                        TokenType::Symbol(Symbol::BracketLeft)
-                        | TokenType::Symbol(Symbol::Period) => {
-                            // Subroutine call: subroutineName(expressionList) or
-                            // className.varName.subroutineName(expressionList)
-                            compile_subroutine_call_simple(stream, output)?;
-                        }
-                        _ => {
-                            // Otherwise, it's just a variable (nothing more to process)
-                        }
+                match stream.peek_nth(1).map(|next_token| &next_token.token) {
+                    Some(TokenType::Symbol(Symbol::BracketLeft))
+                    | Some(TokenType::Symbol(Symbol::Period)) => {
+                        // Subroutine call: subroutineName(expressionList) or
+                        // className|varName.subroutineName(expressionList)
+                        compile_subroutine_call(stream, output)?;
+                    }
+                    Some(TokenType::Symbol(Symbol::BracketSquareLeft)) => {
+                        // Array entry: varName[expression]
+                        write_token(&token.token, output);
+                        stream.advance(); // Advance past the identifier
+                        write_token(&TokenType::Symbol(Symbol::BracketSquareLeft), output);
+                        stream.advance(); // Consume '['
+                        compile_expression(stream, output)?; // Compile the expression
+                        stream.expect(&TokenType::Symbol(Symbol::BracketSquareRight))?;
+                        write_token(&TokenType::Symbol(Symbol::BracketSquareRight), output);
+                    }
+                    _ => {
+                        // Otherwise, it's just a variable
+                        write_token(&token.token, output);
+                        stream.advance();
                     }
                 }
             }
@@ -721,7 +696,7 @@ mod tests {
     #[test]
     fn test_do() {
         let raw_jack = String::from("do Hello.world();");
-        let tokens = tokenise(raw_jack);
+        let tokens = tokenise(raw_jack).unwrap();
         let mut token_stream: TokenStream = TokenStream::new(&tokens);
         let mut output = String::new();
         let comp = compile_do(&mut token_stream, &mut output);
@@ -831,7 +806,7 @@ function void foo() { return; }
 </class>
 ",
         );
-        let tokens = tokenise(raw_jack);
+        let tokens = tokenise(raw_jack).unwrap();
         let mut token_stream = TokenStream::new(&tokens);
         let mut output = String::new();
         let comp = compile_class(&mut token_stream, &mut output);
@@ -2164,7 +2139,7 @@ class Square {
 <symbol> } </symbol>
 </class>
 ";
-        let tokens = tokenise(raw_jack.to_string());
+        let tokens = tokenise(raw_jack.to_string()).unwrap();
         let mut token_stream = TokenStream::new(&tokens);
         let mut output = String::new();
         let comp = compile_class(&mut token_stream, &mut output);
@@ -2178,4 +2153,31 @@ class Square {
             "Output of compilation does not match the expected output"
         );
     }
+
+    #[test]
+    fn test_lexical_errors() {
+        let result = parse(String::from("class A {\n  function void f() { let x = 99999 $; }\n}"));
+        assert_eq!(result, Err("ERROR: line 2: Integer constant 99999 is larger than 32767".to_string()));
+        assert!(crate::ast::parse_classes(String::from("class A { \"open }")).is_err());
+    }
+
+    #[test]
+    fn test_error_messages_leave_out_spans() {
+        assert_eq!(
+            parse(String::from("class A function")),
+            Err("ERROR: Expected Symbol(BracketCurlyLeft), found Keyword(Function)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let result = parse(String::from("class A { function int f() { return 1"));
+        assert_eq!(result, Err("ERROR: Unexpected end of tokens".to_string()));
+        let tokens = tokenise(String::from("a.b(x, y[1])")).unwrap();
+        let mut token_stream = TokenStream::new(&tokens);
+        let mut output = String::new();
+        compile_term(&mut token_stream, &mut output).unwrap();
+        assert_eq!(token_stream.peek(), None);
+        assert!(output.starts_with("<term>\n<identifier> a </identifier>\n<symbol> . </symbol>\n"));
+    }
 }
@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// A fixed number of scoped threads taking items off a shared counter. Results
+// come back in the order of the items, whatever order they finish in, so that
+// the output does not depend on the number of threads.
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+pub fn map<T, R, F>(items: &[T], threads: usize, job: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = job(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is taken by a thread"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_results_keep_the_order_of_the_items() {
+        let items: Vec<u64> = (0..40).collect();
+        for threads in [0, 1, 3, 64] {
+            let results = map(&items, threads, |&item| {
+                // Early items take longest, so they finish last
+                thread::sleep(Duration::from_micros((40 - item) * 50));
+                item * item
+            });
+            assert_eq!(results, items.iter().map(|item| item * item).collect::<Vec<u64>>());
+        }
+        assert!(map(&[] as &[u64], 4, |&item| item).is_empty());
+    }
+}
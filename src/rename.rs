@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::ast::parse_classes;
+use crate::span::{LineIndex, Span};
+use crate::symbols::{Project, SymbolKind};
+use crate::tokens::Keyword;
+
+// Renaming a declared name everywhere it is used. The renamed project is
+// resolved again and must resolve every name exactly as before, which catches
+// every way a rename can go wrong: a new name that is already declared in the
+// same scope, one that a local would shadow, or one that captures a reference
+// to another declaration.
+
+// The new contents of a file changed by a rename. A class declared in a file
+// named after it also moves the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub new_path: PathBuf,
+    pub old_text: String,
+    pub new_text: String,
+}
+
+pub fn rename(
+    project: &Project,
+    file: usize,
+    offset: usize,
+    new_name: &str,
+) -> Result<Vec<FileChange>, String> {
+    let occurrence = project
+        .occurrence_at(file, offset)
+        .ok_or("There is no declared name at the position")?;
+    let symbol = &project.symbols[occurrence.symbol];
+    // The references in a file that does not parse cannot be found, and
+    // renaming without them would leave the project inconsistent
+    for file in &project.files {
+        if let Err(e) = parse_classes(file.text.clone()) {
+            return Err(format!("Cannot rename while {} has errors: {}", file.path.display(), e));
+        }
+    }
+    if !is_identifier(new_name) {
+        return Err(format!("{} is not a valid Jack identifier", new_name));
+    }
+    if symbol.name == new_name {
+        return Ok(vec![]);
+    }
+
+    // The spans to replace in each file, in source order
+    let mut edits: Vec<Vec<Span>> = vec![vec![]; project.files.len()];
+    for occurrence in project.occurrences_of(occurrence.symbol) {
+        edits[occurrence.file].push(occurrence.span);
+    }
+    let new_texts: Vec<String> = project
+        .files
+        .iter()
+        .zip(&edits)
+        .map(|(file, spans)| replace(&file.text, spans, new_name))
+        .collect();
+
+    let renamed = Project::new(
+        project
+            .files
+            .iter()
+            .zip(&new_texts)
+            .map(|(file, text)| (file.path.clone(), text.clone()))
+            .collect(),
+    );
+    let delta = new_name.len() as isize - symbol.name.len() as isize;
+    let moved = |file: usize, span: Span| move_span(&edits[file], span, delta);
+    let expected: HashSet<(usize, Span, usize, Span)> = project
+        .occurrences
+        .iter()
+        .map(|occurrence| {
+            let declaration = &project.symbols[occurrence.symbol];
+            (
+                occurrence.file,
+                moved(occurrence.file, occurrence.span),
+                declaration.file,
+                moved(declaration.file, declaration.span),
+            )
+        })
+        .collect();
+    let actual: HashSet<(usize, Span, usize, Span)> = renamed
+        .occurrences
+        .iter()
+        .map(|occurrence| {
+            let declaration = &renamed.symbols[occurrence.symbol];
+            (
+                occurrence.file,
+                occurrence.span,
+                declaration.file,
+                declaration.span,
+            )
+        })
+        .collect();
+    if let Some(&(file, span, _, _)) = actual.symmetric_difference(&expected).min() {
+        let source = &renamed.files[file];
+        let position = LineIndex::new(&source.text).position(span.start);
+        return Err(format!(
+            "Renaming {} to {} would change what {} at {}:{}:{} refers to",
+            symbol.name,
+            new_name,
+            &source.text[span.start..span.end],
+            source.path.display(),
+            position.line + 1,
+            position.column + 1
+        ));
+    }
+
+    let mut changes = vec![];
+    for (index, (source, new_text)) in project.files.iter().zip(new_texts).enumerate() {
+        if edits[index].is_empty() {
+            continue;
+        }
+        let mut new_path = source.path.clone();
+        if symbol.kind == SymbolKind::Class
+            && index == symbol.file
+            && source
+                .path
+                .file_stem()
+                .is_some_and(|stem| *stem == *symbol.name)
+        {
+            new_path.set_file_name(format!("{}.jack", new_name));
+            if project.file_index(&new_path).is_some() || new_path.exists() {
+                return Err(format!("{} already exists", new_path.display()));
+            }
+        }
+        changes.push(FileChange {
+            path: source.path.clone(),
+            new_path,
+            old_text: source.text.clone(),
+            new_text,
+        });
+    }
+    Ok(changes)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && Keyword::new(name).is_none()
+}
+
+fn replace(text: &str, spans: &[Span], new_name: &str) -> String {
+    let mut output = String::new();
+    let mut copied = 0;
+    for span in spans {
+        output.push_str(&text[copied..span.start]);
+        output.push_str(new_name);
+        copied = span.end;
+    }
+    output.push_str(&text[copied..]);
+    output
+}
+
+// Where a span ends up once every edit in its file changes length by delta.
+fn move_span(edits: &[Span], span: Span, delta: isize) -> Span {
+    let before = edits.iter().filter(|edit| edit.start < span.start).count() as isize;
+    let start = (span.start as isize + before * delta) as usize;
+    if edits.contains(&span) {
+        Span::new(start, (span.end as isize + (before + 1) * delta) as usize)
+    } else {
+        Span::new(start, (span.end as isize + before * delta) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COUNTER: &str = "class Counter {
+  field int count;
+  constructor Counter new() { let count = 0; return this; }
+  method int next(int step) { let count = count + step; return count; }
+}
+";
+
+    const MAIN: &str = "class Main {
+  function void main() {
+    var Counter counter;
+    let counter = Counter.new();
+    do Output.printInt(counter.next(1));
+    return;
+  }
+}
+";
+
+    fn project() -> Project {
+        Project::new(vec![
+            (PathBuf::from("/p/Counter.jack"), COUNTER.to_string()),
+            (PathBuf::from("/p/Main.jack"), MAIN.to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_rename_across_files() {
+        let project = project();
+        let changes = rename(&project, 1, MAIN.find("next").unwrap(), "advance").unwrap();
+        assert_eq!(changes.len(), 2);
+        assert!(changes[0].new_text.contains("method int advance(int step)"));
+        assert!(changes[1].new_text.contains("counter.advance(1)"));
+
+        let changes = rename(&project, 0, COUNTER.find("Counter").unwrap(), "Tally").unwrap();
+        assert_eq!(changes[0].new_path, PathBuf::from("/p/Tally.jack"));
+        assert!(changes[0].new_text.contains("constructor Tally new()"));
+        assert!(changes[1].new_text.contains("let counter = Tally.new();"));
+        assert_eq!(changes[1].new_path, PathBuf::from("/p/Main.jack"));
+    }
+
+    #[test]
+    fn test_rename_refuses_conflicts() {
+        let project = project();
+        let count = COUNTER.find("count").unwrap();
+        // The argument would shadow the field inside next
+        assert_eq!(
+            rename(&project, 0, count, "step").unwrap_err(),
+            "Renaming count to step would change what step at /p/Counter.jack:4:35 refers to"
+        );
+        assert!(rename(&project, 0, count, "while").is_err());
+        assert!(rename(&project, 0, count, "total").is_ok());
+        // References in a file that does not parse would be missed
+        let broken = Project::new(vec![
+            (PathBuf::from("/p/Counter.jack"), COUNTER.to_string()),
+            (PathBuf::from("/p/Main.jack"), MAIN.replace("return;", "return")),
+        ]);
+        assert_eq!(
+            rename(&broken, 0, COUNTER.find("next").unwrap(), "advance").unwrap_err(),
+            "Cannot rename while /p/Main.jack has errors: Unexpected token Symbol(BracketCurlyRight) when compiling term"
+        );
+        // The name of a field in another class is free for a local
+        let counter = MAIN.find("counter").unwrap();
+        let changes = rename(&project, 1, counter, "count").unwrap();
+        assert_eq!(
+            changes[0].new_text.matches("count").count(),
+            MAIN.matches("counter").count()
+        );
+    }
+}
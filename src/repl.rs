@@ -0,0 +1,252 @@
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use crate::ast::{parse_classes, parse_expression, parse_statements, parse_var_dec};
+use crate::interpreter::{Frame, Interpreter, Interrupt};
+use crate::token_stream::TokenStream;
+use crate::tokeniser::tokenise;
+use crate::tokens::{Keyword, TokenType};
+
+// An interactive loop over the interpreter. Each entry is a var declaration,
+// a class, one or more statements, or an expression whose value is printed.
+// Entries continue over several lines until their brackets are balanced and,
+// for statements and declarations, until they end in ";" or "}". An empty line
+// submits whatever has been typed so far.
+
+const PROMPT: &str = "jack> ";
+const CONTINUATION_PROMPT: &str = "...> ";
+const HELP: &str = "Enter Jack statements, expressions, var declarations or classes.
+:load <path>   load the classes in a .jack file or directory
+:vars          list the declared variables
+:help          show this message
+:quit          leave the REPL
+";
+
+pub fn run<R: BufRead, W: Write>(interpreter: &mut Interpreter<R, W>) -> Result<(), String> {
+    let mut frame = Frame::top_level();
+    loop {
+        let entry = match read_entry(interpreter)? {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+
+        let result = match entry.split_once(char::is_whitespace).unwrap_or((entry, "")) {
+            (":quit" | ":q", _) => return Ok(()),
+            (":help", _) => Ok(HELP.to_string()),
+            (":vars", _) => Ok(frame
+                .variables()
+                .map(|(name, var_type, value)| format!("{} {} = {}\n", var_type, name, value))
+                .collect()),
+            (":load", path) => load(interpreter, Path::new(path.trim())),
+            (command, _) if command.starts_with(':') => {
+                Err(format!("Unknown command {}, try :help", command))
+            }
+            _ => evaluate_entry(interpreter, &mut frame, entry),
+        };
+        let output = interpreter.output();
+        match result {
+            Ok(message) => write!(output, "{}", message),
+            Err(e) => writeln!(output, "error: {}", e),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+}
+
+// Reads lines until the entry is complete, or returns None at the end of input.
+fn read_entry<R: BufRead, W: Write>(
+    interpreter: &mut Interpreter<R, W>,
+) -> Result<Option<String>, String> {
+    let mut entry = String::new();
+    loop {
+        let prompt = if entry.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        write!(interpreter.output(), "{}", prompt).map_err(|e| e.to_string())?;
+        interpreter.output().flush().map_err(|e| e.to_string())?;
+
+        let mut line = String::new();
+        if interpreter.input().read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Ok(if entry.is_empty() { None } else { Some(entry) });
+        }
+        if line.trim().is_empty() && !entry.is_empty() {
+            return Ok(Some(entry));
+        }
+        entry.push_str(&line);
+        if is_complete(&entry) {
+            return Ok(Some(entry));
+        }
+    }
+}
+
+fn is_complete(entry: &str) -> bool {
+    const STATEMENT_STARTS: [&str; 6] = ["var", "let", "do", "if", "while", "return"];
+    let entry = entry.trim();
+    let first_word = entry
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or("");
+    if nesting_depth(entry) > 0 {
+        return false;
+    }
+    !STATEMENT_STARTS.contains(&first_word) || entry.ends_with(';') || entry.ends_with('}')
+}
+
+fn nesting_depth(source: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    for c in source.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '{' | '(' | '[' if !in_string => depth += 1,
+            '}' | ')' | ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+fn load<R: BufRead, W: Write>(
+    interpreter: &mut Interpreter<R, W>,
+    path: &Path,
+) -> Result<String, String> {
+    let files = if path.is_dir() {
+        let mut files: Vec<_> = fs::read_dir(path)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|file| file.extension().is_some_and(|extension| extension == "jack"))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut loaded = vec![];
+    for file in files {
+        let contents =
+            fs::read_to_string(&file).map_err(|e| format!("Failed to read {:?}: {}", file, e))?;
+        for class in parse_classes(contents).map_err(|e| format!("{:?}: {}", file, e))? {
+            loaded.push(class.name.to_string());
+            interpreter.load_class(class);
+        }
+    }
+    Ok(format!("loaded {}\n", loaded.join(", ")))
+}
+
+fn evaluate_entry<R: BufRead, W: Write>(
+    interpreter: &mut Interpreter<R, W>,
+    frame: &mut Frame,
+    entry: &str,
+) -> Result<String, String> {
+    let tokens = tokenise(entry.to_string())?;
+    let mut stream = TokenStream::new(&tokens);
+    let first = match stream.peek() {
+        Some(token) => token.token.clone(),
+        None => return Ok(String::new()),
+    };
+
+    let message = match first {
+        TokenType::Keyword(Keyword::Class) => {
+            let classes = parse_classes(entry.to_string())?;
+            let names: Vec<String> = classes.iter().map(|class| class.name.to_string()).collect();
+            for class in classes {
+                interpreter.load_class(class);
+            }
+            return Ok(format!("loaded {}\n", names.join(", ")));
+        }
+        TokenType::Keyword(Keyword::Var) => {
+            let mut declared = vec![];
+            while stream.peek().is_some() {
+                let dec = parse_var_dec(&mut stream)?;
+                for name in &dec.names {
+                    frame.declare(name, dec.var_type.clone());
+                }
+                declared.extend(dec.names.iter().map(|name| name.to_string()));
+            }
+            return Ok(format!("declared {}\n", declared.join(", ")));
+        }
+        TokenType::Keyword(
+            Keyword::Let | Keyword::Do | Keyword::If | Keyword::While | Keyword::Return,
+        ) => {
+            let statements = parse_statements(&mut stream)?;
+            expect_end(&stream)?;
+            match interpreter.execute(frame, &statements) {
+                Ok(Some(value)) => format!("= {}\n", value),
+                Ok(None) => String::new(),
+                Err(interrupt) => return Err(describe(interrupt)),
+            }
+        }
+        _ => {
+            let expression = parse_expression(&mut stream)?;
+            expect_end(&stream)?;
+            match interpreter.evaluate(frame, &expression) {
+                Ok(value) => format!("= {}\n", value),
+                Err(interrupt) => return Err(describe(interrupt)),
+            }
+        }
+    };
+    Ok(message)
+}
+
+fn expect_end(stream: &TokenStream) -> Result<(), String> {
+    match stream.peek() {
+        Some(token) => Err(format!("Unexpected {:?} after the end of the input", token.token)),
+        None => Ok(()),
+    }
+}
+
+fn describe(interrupt: Interrupt) -> String {
+    match interrupt {
+        Interrupt::Halt => "program halted".to_string(),
+        Interrupt::Error(e) => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let input = "var int x, y;
+let x = 6;
+let y = x * 7
+;
+x + y # 1
+class Counter {
+  field int n;
+  constructor Counter new() { let n = 0; return this; }
+  method int next() { let n = n + 1; return n; }
+}
+var Counter c;
+let c = Counter.new();
+do Output.printInt(c.next() + c.next());
+:vars
+nope
+:quit
+";
+        let mut interpreter = Interpreter::new(input.as_bytes(), Vec::new());
+        run(&mut interpreter).unwrap();
+        let output = String::from_utf8(interpreter.output().clone()).unwrap();
+        let expected = "jack> declared x, y
+jack> jack> ...> jack> = 24
+jack> ...> ...> ...> ...> loaded Counter
+jack> declared c
+jack> jack> 3jack> int x = 6
+int y = 42
+Counter c = 2
+jack> error: Undefined variable nope
+jack> ";
+        // The object address depends on the allocator, so only check its shape
+        let c_line = output.lines().find(|line| line.starts_with("Counter c = ")).unwrap();
+        let output = output.replace(c_line, "Counter c = 2");
+        assert_eq!(output, expected);
+    }
+}
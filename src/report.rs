@@ -0,0 +1,240 @@
+use std::path::Path;
+
+use crate::diagnostics::{Severity, LEXICAL_ERROR, SYNTAX_ERROR};
+use crate::json::Json;
+use crate::lint::{Finding, RULES};
+use crate::lsp::{encode_uri_path, path_to_uri};
+use crate::span::{LineIndex, Span};
+use crate::symbols::Project;
+
+// Writes lint findings, syntax errors included, for people or for tools: as
+// text, as one JSON object per line, or as a SARIF 2.1.0 log. Paths are given
+// relative to the base directory when they are inside it.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    JsonLines,
+    Sarif,
+}
+
+impl Format {
+    pub fn new(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::JsonLines),
+            "sarif" => Some(Format::Sarif),
+            _ => None,
+        }
+    }
+}
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+pub fn render(project: &Project, findings: &[Finding], base: &Path, format: Format) -> String {
+    match format {
+        Format::Text => findings
+            .iter()
+            .map(|finding| {
+                let (start, _) = region(project, finding.file, finding.span);
+                format!(
+                    "{}:{}:{}: {}[{}]: {}\n",
+                    relative_path(project, finding.file, base),
+                    start.0,
+                    start.1,
+                    finding.severity,
+                    finding.code,
+                    finding.message
+                )
+            })
+            .collect(),
+        Format::JsonLines => findings
+            .iter()
+            .map(|finding| format!("{}\n", json_line(project, finding, base)))
+            .collect(),
+        Format::Sarif => format!("{}\n", sarif(project, findings, base)),
+    }
+}
+
+fn json_line(project: &Project, finding: &Finding, base: &Path) -> Json {
+    let ((start_line, start_column), (end_line, end_column)) =
+        region(project, finding.file, finding.span);
+    Json::object(vec![
+        ("file", relative_path(project, finding.file, base).into()),
+        (
+            "span",
+            Json::object(vec![
+                ("start", finding.span.start.into()),
+                ("end", finding.span.end.into()),
+                ("startLine", start_line.into()),
+                ("startColumn", start_column.into()),
+                ("endLine", end_line.into()),
+                ("endColumn", end_column.into()),
+            ]),
+        ),
+        ("severity", finding.severity.to_string().into()),
+        ("code", finding.code.into()),
+        ("message", finding.message.as_str().into()),
+    ])
+}
+
+pub fn sarif(project: &Project, findings: &[Finding], base: &Path) -> Json {
+    // (code, name, description, default level) of every rule
+    let mut rules = vec![
+        (LEXICAL_ERROR, "lexical-error", "The source cannot be split into tokens", Some(Severity::Error)),
+        (SYNTAX_ERROR, "syntax-error", "The tokens do not follow the Jack grammar", Some(Severity::Error)),
+    ];
+    rules.extend(
+        RULES
+            .iter()
+            .map(|rule| (rule.code, rule.name, rule.description, rule.default)),
+    );
+    let descriptors: Vec<Json> = rules
+        .iter()
+        .map(|(code, name, description, default)| {
+            let level = default.map_or("none", level);
+            Json::object(vec![
+                ("id", (*code).into()),
+                ("name", (*name).into()),
+                ("shortDescription", Json::object(vec![("text", (*description).into())])),
+                ("defaultConfiguration", Json::object(vec![("level", level.into())])),
+            ])
+        })
+        .collect();
+
+    let results: Vec<Json> = findings
+        .iter()
+        .map(|finding| {
+            let rule_index = rules
+                .iter()
+                .position(|(code, ..)| *code == finding.code)
+                .expect("every finding comes from a known rule");
+            let ((start_line, start_column), (end_line, end_column)) =
+                region(project, finding.file, finding.span);
+            let path = &project.files[finding.file].path;
+            let uri = match path.strip_prefix(base) {
+                Ok(relative) => encode_uri_path(&relative.to_string_lossy().replace('\\', "/")),
+                Err(_) => path_to_uri(path),
+            };
+            let location = Json::object(vec![(
+                "physicalLocation",
+                Json::object(vec![
+                    ("artifactLocation", Json::object(vec![("uri", uri.into())])),
+                    (
+                        "region",
+                        Json::object(vec![
+                            ("startLine", start_line.into()),
+                            ("startColumn", start_column.into()),
+                            ("endLine", end_line.into()),
+                            ("endColumn", end_column.into()),
+                            ("byteOffset", finding.span.start.into()),
+                            ("byteLength", (finding.span.end - finding.span.start).into()),
+                        ]),
+                    ),
+                ]),
+            )]);
+            Json::object(vec![
+                ("ruleId", finding.code.into()),
+                ("ruleIndex", rule_index.into()),
+                ("level", level(finding.severity).into()),
+                ("message", Json::object(vec![("text", finding.message.as_str().into())])),
+                ("locations", vec![location].into()),
+            ])
+        })
+        .collect();
+
+    let driver = Json::object(vec![
+        ("name", "JackAnalyzer".into()),
+        ("version", env!("CARGO_PKG_VERSION").into()),
+        ("rules", descriptors.into()),
+    ]);
+    Json::object(vec![
+        ("$schema", SARIF_SCHEMA.into()),
+        ("version", "2.1.0".into()),
+        (
+            "runs",
+            vec![Json::object(vec![
+                ("tool", Json::object(vec![("driver", driver)])),
+                ("results", results.into()),
+            ])]
+            .into(),
+        ),
+    ])
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "note",
+    }
+}
+
+fn relative_path(project: &Project, file: usize, base: &Path) -> String {
+    let path = &project.files[file].path;
+    path.strip_prefix(base).unwrap_or(path).display().to_string()
+}
+
+// The (line, column) of the start and end of the span, counted from 1, with
+// columns in UTF-16 code units as both SARIF and LSP count them.
+fn region(project: &Project, file: usize, span: Span) -> ((usize, usize), (usize, usize)) {
+    let index = LineIndex::new(&project.files[file].text);
+    let start = index.position(span.start);
+    let end = index.position(span.end);
+    ((start.line + 1, start.column + 1), (end.line + 1, end.column + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::{lint, LintConfig};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_formats() {
+        let project = Project::new(vec![
+            (
+                PathBuf::from("/work/src/Main.jack"),
+                "class Main {\n  function void main() { /* é */ var int x; return; }\n}\n".to_string(),
+            ),
+            (PathBuf::from("/other/Bad.jack"), "class Bad { ? field }".to_string()),
+        ]);
+        let findings = lint(&project, &LintConfig::default());
+        let base = Path::new("/work");
+
+        assert_eq!(
+            render(&project, &findings, base, Format::Text),
+            "src/Main.jack:2:42: warning[J0101]: Local x is never used
+/other/Bad.jack:1:13: error[J0001]: Unexpected character '?'
+/other/Bad.jack:1:21: error[J0002]: Expected a type, found Symbol(BracketCurlyRight)
+"
+        );
+        let lines = render(&project, &findings, base, Format::JsonLines);
+        assert_eq!(
+            lines.lines().next().unwrap(),
+            r#"{"file":"src/Main.jack","span":{"start":55,"end":56,"startLine":2,"startColumn":42,"endLine":2,"endColumn":43},"severity":"warning","code":"J0101","message":"Local x is never used"}"#
+        );
+
+        let log = Json::parse(&render(&project, &findings, base, Format::Sarif)).unwrap();
+        assert_eq!(log.get("version").unwrap().as_str(), Some("2.1.0"));
+        let run = &log.get("runs").unwrap().as_array().unwrap()[0];
+        let rules = run.at(&["tool", "driver", "rules"]).unwrap().as_array().unwrap();
+        assert_eq!(rules.len(), 2 + RULES.len());
+        let results = run.get("results").unwrap().as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].get("ruleIndex").unwrap().to_string(), "2");
+        let location = &results[0].get("locations").unwrap().as_array().unwrap()[0];
+        assert_eq!(
+            location.at(&["physicalLocation", "artifactLocation", "uri"]).unwrap().as_str(),
+            Some("src/Main.jack")
+        );
+        assert_eq!(
+            results[2].at(&["locations"]).unwrap().as_array().unwrap()[0]
+                .at(&["physicalLocation", "artifactLocation", "uri"])
+                .unwrap()
+                .as_str(),
+            Some("file:///other/Bad.jack")
+        );
+        assert_eq!(results[1].get("level").unwrap().as_str(), Some("error"));
+    }
+}
@@ -0,0 +1,105 @@
+// Byte ranges into a source file, and their conversion to line/column positions.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    // The smallest span covering both spans.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+}
+
+// Zero-based line and column of a byte offset. Columns are counted in UTF-16
+// code units, which is what editors speaking LSP expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+pub struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        for (offset, c) in source.char_indices() {
+            if c == '\n' {
+                line_starts.push(offset + 1);
+            }
+        }
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.source.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = self.source[line_start..floor_char_boundary(self.source, offset)]
+            .encode_utf16()
+            .count();
+        Position { line, column }
+    }
+
+    // The byte offset of a position, clamped to the end of its line.
+    pub fn offset(&self, position: Position) -> usize {
+        let line_start = match self.line_starts.get(position.line) {
+            Some(start) => *start,
+            None => return self.source.len(),
+        };
+        let mut units = 0;
+        for (offset, c) in self.source[line_start..].char_indices() {
+            if units >= position.column || c == '\n' {
+                return line_start + offset;
+            }
+            units += c.len_utf16();
+        }
+        self.source.len()
+    }
+}
+
+fn floor_char_boundary(source: &str, mut offset: usize) -> usize {
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions_round_trip() {
+        let source = "class A {\n  // é\n  field int x;\n}";
+        let index = LineIndex::new(source);
+        let field = source.find("field").unwrap();
+        assert_eq!(index.position(field), Position { line: 2, column: 2 });
+        assert_eq!(index.offset(Position { line: 2, column: 2 }), field);
+        let after_accent = source.find('é').unwrap() + 'é'.len_utf8();
+        assert_eq!(
+            index.position(after_accent),
+            Position { line: 1, column: 6 }
+        );
+    }
+}
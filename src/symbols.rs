@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{
+    parse_tokens, Class, ClassVarKind, Expression, Name, Statement, StatementKind, Subroutine,
+    SubroutineCall, SubroutineKind, Term, Type,
+};
+use crate::span::Span;
+use crate::tokeniser::tokenise_with_recovery;
+
+// Name resolution over a whole project. Every declaration becomes a Symbol and
+// every place a name appears, declarations included, becomes an Occurrence
+// pointing at the symbol it resolves to. Names that resolve to nothing, such
+// as calls into the Jack OS, have no occurrence.
+
+pub type SymbolId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Class,
+    Static,
+    Field,
+    Constructor,
+    Function,
+    Method,
+    Argument,
+    Local,
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolKind::Class => write!(f, "class"),
+            SymbolKind::Static => write!(f, "static"),
+            SymbolKind::Field => write!(f, "field"),
+            SymbolKind::Constructor => write!(f, "constructor"),
+            SymbolKind::Function => write!(f, "function"),
+            SymbolKind::Method => write!(f, "method"),
+            SymbolKind::Argument => write!(f, "argument"),
+            SymbolKind::Local => write!(f, "var"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // The declaration as Jack source, e.g. "method int size(int scale)"
+    pub detail: String,
+    // The type of a variable or the return type of a subroutine
+    pub var_type: Option<Type>,
+    pub file: usize,
+    // The declared name
+    pub span: Span,
+    // The whole declaration the name is part of
+    pub declaration: Span,
+    pub class: String,
+    // The subroutine declaring an argument or local
+    pub subroutine: Option<String>,
+    pub doc: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occurrence {
+    pub file: usize,
+    pub span: Span,
+    pub symbol: SymbolId,
+    pub is_declaration: bool,
+}
+
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub text: String,
+    // Empty when the file does not parse
+    pub classes: Vec<Class>,
+}
+
+pub struct Project {
+    pub files: Vec<SourceFile>,
+    pub symbols: Vec<Symbol>,
+    pub occurrences: Vec<Occurrence>,
+    classes: HashMap<String, SymbolId>,
+    variables: HashMap<(String, String), SymbolId>,
+    subroutines: HashMap<(String, String), SymbolId>,
+}
+
+impl Project {
+    pub fn new(sources: Vec<(PathBuf, String)>) -> Self {
+        let files = sources
+            .into_iter()
+            .map(|(path, text)| {
+                let (tokens, _) = tokenise_with_recovery(&text);
+                let classes = parse_tokens(&tokens).unwrap_or_default();
+                SourceFile {
+                    path,
+                    text,
+                    classes,
+                }
+            })
+            .collect();
+        let mut project = Project {
+            files,
+            symbols: vec![],
+            occurrences: vec![],
+            classes: HashMap::new(),
+            variables: HashMap::new(),
+            subroutines: HashMap::new(),
+        };
+        project.resolve();
+        project
+    }
+
+    pub fn file_index(&self, path: &Path) -> Option<usize> {
+        self.files.iter().position(|file| file.path == path)
+    }
+
+    pub fn class(&self, name: &str) -> Option<SymbolId> {
+        self.classes.get(name).copied()
+    }
+
+    // A field or static of the class
+    pub fn class_variable(&self, class: &str, name: &str) -> Option<SymbolId> {
+        self.variables
+            .get(&(class.to_string(), name.to_string()))
+            .copied()
+    }
+
+    pub fn subroutine(&self, class: &str, name: &str) -> Option<SymbolId> {
+        self.subroutines
+            .get(&(class.to_string(), name.to_string()))
+            .copied()
+    }
+
+    // The occurrence whose name covers the offset, including its end so that a
+    // cursor just after a name still finds it.
+    pub fn occurrence_at(&self, file: usize, offset: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|occurrence| occurrence.file == file && occurrence.span.contains(offset))
+    }
+
+    pub fn definition(&self, file: usize, offset: usize) -> Option<&Symbol> {
+        self.occurrence_at(file, offset)
+            .map(|occurrence| &self.symbols[occurrence.symbol])
+    }
+
+    // Every occurrence of the symbol at the offset, in file and source order.
+    pub fn references(&self, file: usize, offset: usize) -> Vec<&Occurrence> {
+        match self.occurrence_at(file, offset) {
+            Some(at) => self.occurrences_of(at.symbol),
+            None => vec![],
+        }
+    }
+
+    pub fn occurrences_of(&self, symbol: SymbolId) -> Vec<&Occurrence> {
+        let mut occurrences: Vec<&Occurrence> = self
+            .occurrences
+            .iter()
+            .filter(|occurrence| occurrence.symbol == symbol)
+            .collect();
+        occurrences.sort_by_key(|occurrence| (occurrence.file, occurrence.span));
+        occurrences
+    }
+
+    // Markdown describing the symbol at the offset: its declaration, where it
+    // is declared, and its doc comment.
+    pub fn hover(&self, file: usize, offset: usize) -> Option<String> {
+        let symbol = self.definition(file, offset)?;
+        let mut text = format!("```jack\n{}\n```\n", symbol.detail);
+        match (&symbol.kind, &symbol.subroutine) {
+            (SymbolKind::Class, _) => {}
+            (_, Some(subroutine)) => text.push_str(&format!(
+                "\n{} in {}.{}\n",
+                symbol.kind, symbol.class, subroutine
+            )),
+            (_, None) => text.push_str(&format!("\n{} of {}\n", symbol.kind, symbol.class)),
+        }
+        if let Some(doc) = &symbol.doc {
+            text.push_str(&format!("\n{}\n", doc));
+        }
+        Some(text)
+    }
+
+    fn resolve(&mut self) {
+        // Declare every class and its members first, as they may be used
+        // before they are declared
+        for file in 0..self.files.len() {
+            for class_index in 0..self.files[file].classes.len() {
+                self.declare_class(file, class_index);
+            }
+        }
+        for file in 0..self.files.len() {
+            for class_index in 0..self.files[file].classes.len() {
+                let class = self.files[file].classes[class_index].clone();
+                // Skip duplicate classes, which were not declared
+                let declared = &self.symbols[self.classes[class.name.as_str()]];
+                if (declared.file, declared.span) != (file, class.name.span) {
+                    continue;
+                }
+                for dec in &class.class_vars {
+                    self.type_reference(file, &dec.var_type);
+                }
+                for subroutine in &class.subroutines {
+                    self.resolve_subroutine(file, &class, subroutine);
+                }
+            }
+        }
+    }
+
+    fn declare_class(&mut self, file: usize, class_index: usize) {
+        let class = self.files[file].classes[class_index].clone();
+        if self.classes.contains_key(class.name.as_str()) {
+            // Only the first of several classes with the same name is resolvable
+            return;
+        }
+        let id = self.declare(
+            file,
+            &class.name,
+            SymbolKind::Class,
+            format!("class {}", class.name),
+            None,
+            class.span,
+            &class.name,
+            None,
+        );
+        self.classes.insert(class.name.to_string(), id);
+
+        for dec in &class.class_vars {
+            let kind = match dec.kind {
+                ClassVarKind::Static => SymbolKind::Static,
+                ClassVarKind::Field => SymbolKind::Field,
+            };
+            for name in &dec.names {
+                let id = self.declare(
+                    file,
+                    name,
+                    kind,
+                    format!("{} {} {}", kind, dec.var_type, name),
+                    Some(dec.var_type.clone()),
+                    dec.span,
+                    &class.name,
+                    None,
+                );
+                self.variables
+                    .insert((class.name.to_string(), name.to_string()), id);
+            }
+        }
+
+        for subroutine in &class.subroutines {
+            let kind = match subroutine.kind {
+                SubroutineKind::Constructor => SymbolKind::Constructor,
+                SubroutineKind::Function => SymbolKind::Function,
+                SubroutineKind::Method => SymbolKind::Method,
+            };
+            let id = self.declare(
+                file,
+                &subroutine.name,
+                kind,
+                signature(subroutine),
+                subroutine.return_type.clone(),
+                subroutine.span,
+                &class.name,
+                None,
+            );
+            self.subroutines
+                .insert((class.name.to_string(), subroutine.name.to_string()), id);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn declare(
+        &mut self,
+        file: usize,
+        name: &Name,
+        kind: SymbolKind,
+        detail: String,
+        var_type: Option<Type>,
+        declaration: Span,
+        class: &str,
+        subroutine: Option<&str>,
+    ) -> SymbolId {
+        let doc = match kind {
+            SymbolKind::Argument => None,
+            _ => doc_comment(&self.files[file].text, declaration.start),
+        };
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            detail,
+            var_type,
+            file,
+            span: name.span,
+            declaration,
+            class: class.to_string(),
+            subroutine: subroutine.map(str::to_string),
+            doc,
+        });
+        let id = self.symbols.len() - 1;
+        self.occur(file, name.span, id, true);
+        id
+    }
+
+    fn occur(&mut self, file: usize, span: Span, symbol: SymbolId, is_declaration: bool) {
+        self.occurrences.push(Occurrence {
+            file,
+            span,
+            symbol,
+            is_declaration,
+        });
+    }
+
+    fn type_reference(&mut self, file: usize, var_type: &Type) {
+        if let Type::Class(name) = var_type {
+            if let Some(&id) = self.classes.get(name.as_str()) {
+                self.occur(file, name.span, id, false);
+            }
+        }
+    }
+
+    fn resolve_subroutine(&mut self, file: usize, class: &Class, subroutine: &Subroutine) {
+        if let Some(return_type) = &subroutine.return_type {
+            self.type_reference(file, return_type);
+        }
+        let mut scope = Scope {
+            file,
+            class: class.name.to_string(),
+            names: HashMap::new(),
+        };
+        for parameter in &subroutine.parameters {
+            self.type_reference(file, &parameter.var_type);
+            let id = self.declare(
+                file,
+                &parameter.name,
+                SymbolKind::Argument,
+                format!("{} {}", parameter.var_type, parameter.name),
+                Some(parameter.var_type.clone()),
+                parameter.name.span,
+                &class.name,
+                Some(&subroutine.name),
+            );
+            scope.names.insert(parameter.name.to_string(), id);
+        }
+        for dec in &subroutine.locals {
+            self.type_reference(file, &dec.var_type);
+            for name in &dec.names {
+                let id = self.declare(
+                    file,
+                    name,
+                    SymbolKind::Local,
+                    format!("var {} {}", dec.var_type, name),
+                    Some(dec.var_type.clone()),
+                    dec.span,
+                    &class.name,
+                    Some(&subroutine.name),
+                );
+                scope.names.insert(name.to_string(), id);
+            }
+        }
+        self.statements(&scope, &subroutine.statements);
+    }
+
+    fn statements(&mut self, scope: &Scope, statements: &[Statement]) {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::Let { name, index, value } => {
+                    self.variable(scope, name);
+                    if let Some(index) = index {
+                        self.expression(scope, index);
+                    }
+                    self.expression(scope, value);
+                }
+                StatementKind::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                } => {
+                    self.expression(scope, condition);
+                    self.statements(scope, then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.statements(scope, else_branch);
+                    }
+                }
+                StatementKind::While { condition, body } => {
+                    self.expression(scope, condition);
+                    self.statements(scope, body);
+                }
+                StatementKind::Do(call) => self.call(scope, call),
+                StatementKind::Return(Some(value)) => self.expression(scope, value),
+                StatementKind::Return(None) => {}
+            }
+        }
+    }
+
+    fn expression(&mut self, scope: &Scope, expression: &Expression) {
+        self.term(scope, &expression.term);
+        for (_, term) in &expression.rest {
+            self.term(scope, term);
+        }
+    }
+
+    fn term(&mut self, scope: &Scope, term: &Term) {
+        match term {
+            Term::Variable(name) => {
+                self.variable(scope, name);
+            }
+            Term::ArrayEntry(name, index) => {
+                self.variable(scope, name);
+                self.expression(scope, index);
+            }
+            Term::Call(call) => self.call(scope, call),
+            Term::Parenthesised(inner) => self.expression(scope, inner),
+            Term::Unary(_, inner) => self.term(scope, inner),
+            Term::IntegerConstant(_) | Term::StringConstant(_) | Term::KeywordConstant(_) => {}
+        }
+    }
+
+    // Resolves a variable name, locals and arguments shadowing class variables.
+    fn variable(&mut self, scope: &Scope, name: &Name) -> Option<SymbolId> {
+        let id = scope
+            .names
+            .get(name.as_str())
+            .copied()
+            .or_else(|| self.class_variable(&scope.class, name))?;
+        self.occur(scope.file, name.span, id, false);
+        Some(id)
+    }
+
+    fn call(&mut self, scope: &Scope, call: &SubroutineCall) {
+        let class = match &call.receiver {
+            None => Some(scope.class.clone()),
+            // A receiver is a variable holding an object, or else a class name
+            Some(receiver) => match self.variable(scope, receiver) {
+                Some(id) => match &self.symbols[id].var_type {
+                    Some(Type::Class(class)) => Some(class.to_string()),
+                    _ => None,
+                },
+                None => {
+                    if let Some(id) = self.class(receiver) {
+                        self.occur(scope.file, receiver.span, id, false);
+                    }
+                    Some(receiver.to_string())
+                }
+            },
+        };
+        if let Some(id) = class.and_then(|class| self.subroutine(&class, &call.name)) {
+            self.occur(scope.file, call.name.span, id, false);
+        }
+        for argument in &call.arguments {
+            self.expression(scope, argument);
+        }
+    }
+}
+
+// The arguments and locals visible inside a subroutine.
+struct Scope {
+    file: usize,
+    class: String,
+    names: HashMap<String, SymbolId>,
+}
+
+pub fn signature(subroutine: &Subroutine) -> String {
+    let return_type = match &subroutine.return_type {
+        Some(return_type) => return_type.to_string(),
+        None => "void".to_string(),
+    };
+    let parameters: Vec<String> = subroutine
+        .parameters
+        .iter()
+        .map(|parameter| format!("{} {}", parameter.var_type, parameter.name))
+        .collect();
+    format!(
+        "{} {} {}({})",
+        subroutine.kind,
+        return_type,
+        subroutine.name,
+        parameters.join(", ")
+    )
+}
+
+// The comment directly above a declaration: either a block comment or a run
+// of line comments, with the comment markers removed.
+fn doc_comment(text: &str, start: usize) -> Option<String> {
+    let before = text[..start].trim_end();
+    if let Some(block) = before.strip_suffix("*/") {
+        let open = block.rfind("/*")?;
+        let lines: Vec<&str> = block[open + 2..]
+            .trim_start_matches('*')
+            .lines()
+            .map(|line| line.trim().trim_start_matches('*').trim())
+            .collect();
+        let doc = lines.join("\n").trim().to_string();
+        return if doc.is_empty() { None } else { Some(doc) };
+    }
+    let mut lines = vec![];
+    for line in before.lines().rev() {
+        match line.trim().strip_prefix("//") {
+            Some(comment) => lines.push(comment.trim_start_matches('/').trim()),
+            None => break,
+        }
+    }
+    lines.reverse();
+    let doc = lines.join("\n").trim().to_string();
+    if doc.is_empty() {
+        None
+    } else {
+        Some(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "/** A square on the screen. */
+class Square {
+  // The length of a side
+  field int size;
+  field int x;
+
+  constructor Square new(int size) {
+    let x = 0;
+    let size = size;
+    return this;
+  }
+
+  method int area() {
+    var int x;
+    let x = size * size;
+    return x;
+  }
+}
+";
+
+    const MAIN: &str = "class Main {
+  function void main() {
+    var Square square;
+    let square = Square.new(3);
+    do Output.printInt(square.area());
+    return;
+  }
+}
+";
+
+    fn project() -> Project {
+        Project::new(vec![
+            (PathBuf::from("Square.jack"), SQUARE.to_string()),
+            (PathBuf::from("Main.jack"), MAIN.to_string()),
+        ])
+    }
+
+    fn nth(text: &str, pattern: &str, n: usize) -> usize {
+        text.match_indices(pattern).nth(n).unwrap().0
+    }
+
+    #[test]
+    fn test_definition_respects_shadowing() {
+        let project = project();
+        // "let size = size" assigns the argument, which shadows the field
+        let size = project.definition(0, nth(SQUARE, "size", 3)).unwrap();
+        assert_eq!(
+            (size.kind, size.span.start),
+            (SymbolKind::Argument, nth(SQUARE, "size", 1))
+        );
+        // "size * size" in the method reads the field
+        let size = project.definition(0, nth(SQUARE, "size", 4)).unwrap();
+        assert_eq!(size.kind, SymbolKind::Field);
+        assert_eq!(size.doc.as_deref(), Some("The length of a side"));
+
+        let area = project.definition(1, MAIN.find("area").unwrap()).unwrap();
+        assert_eq!(area.detail, "method int area()");
+        assert_eq!(area.file, 0);
+    }
+
+    #[test]
+    fn test_references_and_hover() {
+        let project = project();
+        let references = project.references(1, MAIN.find("Square").unwrap());
+        let locations: Vec<(usize, bool)> = references
+            .iter()
+            .map(|occurrence| (occurrence.file, occurrence.is_declaration))
+            .collect();
+        assert_eq!(
+            locations,
+            vec![(0, true), (0, false), (1, false), (1, false)]
+        );
+
+        // The field x is only assigned in the constructor, as area declares its own x
+        assert_eq!(project.references(0, nth(SQUARE, "x", 0)).len(), 2);
+
+        assert_eq!(
+            project.hover(0, nth(SQUARE, "Square", 0)).unwrap(),
+            "```jack\nclass Square\n```\n\nA square on the screen.\n"
+        );
+        assert_eq!(
+            project.hover(1, MAIN.find("square").unwrap()).unwrap(),
+            "```jack\nvar Square square\n```\n\nvar in Main.main\n"
+        );
+        assert!(project.hover(1, MAIN.find("printInt").unwrap()).is_none());
+    }
+}
@@ -1,45 +1,117 @@
+use crate::span::Span;
 use crate::tokens::{Token, TokenType};
 
+// A cursor over a slice of tokens. Parsers can look any number of tokens
+// ahead, and can go back to a checkpoint after trying one alternative to try
+// another, for grammar that a single token of look-ahead cannot decide.
 pub struct TokenStream<'a> {
-    tokens: std::slice::Iter<'a, Token>,
-    current: Option<&'a Token>,
+    tokens: &'a [Token],
+    // The index of the next token
+    position: usize,
 }
 
+// A position in a token stream to rewind to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
 impl<'a> TokenStream<'a> {
     pub fn new(tokens: &'a [Token]) -> Self {
-        let mut stream = Self {
-            tokens: tokens.iter(),
-            current: None,
-        };
-        stream.advance(); // Load the first token
-        stream
+        Self {
+            tokens,
+            position: 0,
+        }
     }
 
+    // Consumes the next token, returning it
     pub fn advance(&mut self) -> Option<&'a Token> {
-        self.current = self.tokens.next();
-        self.current
+        let token = self.peek()?;
+        self.position += 1;
+        Some(token)
+    }
+
+    pub fn peek(&self) -> Option<&'a Token> {
+        self.peek_nth(0)
+    }
+
+    // The token k places after the next one, so peek_nth(0) is peek()
+    pub fn peek_nth(&self, k: usize) -> Option<&'a Token> {
+        self.tokens.get(self.position + k)
     }
 
-    pub fn advance_prev(&mut self) -> Option<&'a Token> {
-        let tmp = self.current;
-        self.current = self.tokens.next();
-        tmp
+    // How many tokens have been consumed
+    pub fn position(&self) -> usize {
+        self.position
     }
 
-    pub fn peek(&self) -> Option<&Token> {
-        self.current
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.position)
+    }
+
+    // Goes back to where the checkpoint was taken, unconsuming the tokens since
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.0;
+    }
+
+    // The span of the next token, or an empty span after the last token at the
+    // end of the input
+    pub fn current_span(&self) -> Span {
+        match self.peek() {
+            Some(token) => token.span,
+            None => {
+                let end = self.tokens.last().map_or(0, |token| token.span.end);
+                Span::new(end, end)
+            }
+        }
+    }
+
+    // From the first token consumed since the position to the last, or an empty
+    // span at the next token if there are none
+    pub fn span_since(&self, start: usize) -> Span {
+        if start >= self.position {
+            let start = self.current_span().start;
+            return Span::new(start, start);
+        }
+        self.tokens[start].span.to(self.tokens[self.position - 1].span)
     }
 
     pub fn expect(&mut self, expected: &TokenType) -> Result<(), String> {
-        if let Some(token) = self.current {
+        if let Some(token) = self.peek() {
             if &token.token == expected {
                 self.advance(); // Consume the token
                 Ok(())
             } else {
-                Err(format!("Expected {:?}, found {:?}", expected, token))
+                Err(format!("Expected {:?}, found {:?}", expected, token.token))
             }
         } else {
             Err("Unexpected end of tokens".to_string())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokeniser::tokenise;
+    use crate::tokens::Symbol;
+
+    #[test]
+    fn test_look_ahead_and_rewind() {
+        let tokens = tokenise("a . b ( )".to_string()).unwrap();
+        let mut stream = TokenStream::new(&tokens);
+        assert_eq!(stream.peek_nth(3).map(|token| &token.token), Some(&TokenType::Symbol(Symbol::BracketLeft)));
+        assert_eq!(stream.peek_nth(5), None);
+
+        let start = stream.checkpoint();
+        assert_eq!(stream.advance(), Some(&tokens[0]));
+        stream.expect(&TokenType::Symbol(Symbol::Period)).unwrap();
+        assert_eq!(stream.position(), 2);
+        assert_eq!(stream.span_since(0), Span::new(0, 3));
+        assert_eq!(stream.current_span(), Span::new(4, 5));
+        stream.rewind(start);
+        assert_eq!(stream.position(), 0);
+        assert_eq!(stream.span_since(0), Span::new(0, 0));
+
+        while stream.advance().is_some() {}
+        assert_eq!(stream.current_span(), Span::new(9, 9));
+    }
+}
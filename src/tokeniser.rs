@@ -1,147 +1,204 @@
+use crate::span::{LineIndex, Span};
 use crate::tokens::{Identifier, Keyword, Symbol, Token, TokenType};
 use std::iter::Peekable;
+use std::str::CharIndices;
 
-const COMMENT_BEGIN: &'static str = "//";
+const MAX_INTEGER_CONSTANT: u32 = 32767;
 
-pub fn tokenise(input_data: String) -> Vec<Token> {
-    let whitespaces_cleaned: String = remove_whitespace(input_data);
+// A problem found while tokenising. The tokeniser recovers from every error by
+// skipping the offending input, so the tokens are still usable by the parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub span: Span,
+    pub message: String,
+}
 
-    let mut current_token: String = String::new();
-    let mut chars = whitespaces_cleaned.chars().peekable();
+// Tokenises the whole input, failing at the first lexical error.
+pub fn tokenise(input_data: String) -> Result<Vec<Token>, String> {
+    let (tokens, errors) = tokenise_with_recovery(&input_data);
+    match errors.first() {
+        Some(error) => {
+            let position = LineIndex::new(&input_data).position(error.span.start);
+            Err(format!("line {}: {}", position.line + 1, error.message))
+        }
+        None => Ok(tokens),
+    }
+}
 
+// Tokenises the whole input, collecting lexical errors instead of stopping at them.
+pub fn tokenise_with_recovery(input_data: &str) -> (Vec<Token>, Vec<LexError>) {
+    let mut chars = input_data.char_indices().peekable();
     let mut tokens: Vec<Token> = vec![];
+    let mut errors: Vec<LexError> = vec![];
 
-    while let Some(c) = chars.next() {
+    while let Some((start, c)) = chars.next() {
         if c.is_whitespace() {
-            // Whitespace ends a token
-            if !current_token.is_empty() {
-                finalise_token(&mut current_token, &mut tokens);
-            }
             continue;
         }
 
-        if let Some(symbol) = Symbol::new(c) {
-            // A symbol ends the current token, so finalise it
-            if !current_token.is_empty() {
-                finalise_token(&mut current_token, &mut tokens);
+        if c == '/' {
+            match chars.peek() {
+                Some((_, '/')) => {
+                    skip_line_comment(&mut chars);
+                    continue;
+                }
+                Some((_, '*')) => {
+                    chars.next(); // Consume '*'
+                    if !skip_multi_line_comment(&mut chars) {
+                        errors.push(LexError {
+                            span: Span::new(start, input_data.len()),
+                            message: "Unterminated comment".to_string(),
+                        });
+                    }
+                    continue;
+                }
+                _ => {}
             }
-            // Add the symbol as a new token
-            tokens.push(Token::new(TokenType::Symbol(symbol)));
+        }
+
+        if let Some(symbol) = Symbol::new(c) {
+            let span = Span::new(start, start + c.len_utf8());
+            tokens.push(Token::new(TokenType::Symbol(symbol), span));
             continue;
         }
 
         if c == '"' {
-            // Handle string constants
-            if !current_token.is_empty() {
-                finalise_token(&mut current_token, &mut tokens);
-            }
             // Collect the entire string constant
-            let string_constant = collect_string_constant(&mut chars);
-            tokens.push(Token::new(TokenType::StringConstant(string_constant)));
+            let (string_constant, end, terminated) = collect_string_constant(&mut chars, start + 1);
+            let span = Span::new(start, end);
+            if !terminated {
+                errors.push(LexError {
+                    span,
+                    message: "Unterminated string constant".to_string(),
+                });
+            }
+            tokens.push(Token::new(TokenType::StringConstant(string_constant), span));
             continue;
         }
 
-        if c.is_digit(10) && current_token.is_empty() {
-            // Handle integer constants
-            let integer_constant = collect_integer_constant(c, &mut chars);
-            tokens.push(Token::new(TokenType::IntegerConstant(integer_constant)));
+        if c.is_ascii_digit() {
+            let (value, end) = collect_integer_constant(start, c, &mut chars);
+            let span = Span::new(start, end);
+            if value > MAX_INTEGER_CONSTANT {
+                errors.push(LexError {
+                    span,
+                    message: format!(
+                        "Integer constant {} is larger than {}",
+                        &input_data[start..end],
+                        MAX_INTEGER_CONSTANT
+                    ),
+                });
+            }
+            tokens.push(Token::new(
+                TokenType::IntegerConstant(value.min(MAX_INTEGER_CONSTANT) as u16),
+                span,
+            ));
             continue;
         }
 
-        // Build up the current token
-        current_token.push(c);
-    }
+        if is_identifier_char(c) {
+            let mut end = start + c.len_utf8();
+            while let Some(&(offset, next)) = chars.peek() {
+                if !is_identifier_char(next) {
+                    break;
+                }
+                end = offset + next.len_utf8();
+                chars.next();
+            }
+            tokens.push(finalise_token(
+                &input_data[start..end],
+                Span::new(start, end),
+            ));
+            continue;
+        }
 
-    // Finalize any remaining token
-    if !current_token.is_empty() {
-        finalise_token(&mut current_token, &mut tokens);
+        errors.push(LexError {
+            span: Span::new(start, start + c.len_utf8()),
+            message: format!("Unexpected character {:?}", c),
+        });
     }
 
-    return tokens;
+    (tokens, errors)
 }
 
-fn finalise_token(current_token: &mut String, tokens: &mut Vec<Token>) {
-    if let Some(keyword) = Keyword::new(current_token) {
-        tokens.push(Token::new(TokenType::Keyword(keyword)));
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn finalise_token(word: &str, span: Span) -> Token {
+    if let Some(keyword) = Keyword::new(word) {
+        Token::new(TokenType::Keyword(keyword), span)
     } else {
-        tokens.push(Token::new(TokenType::Identifier(Identifier::new(
-            current_token,
-        ))));
+        Token::new(
+            TokenType::Identifier(Identifier::new(&word.to_string())),
+            span,
+        )
     }
-    current_token.clear();
 }
 
-fn collect_string_constant(chars: &mut impl Iterator<Item = char>) -> String {
+// Collects characters up to the closing quote, which string constants must
+// reach before the end of the line. Returns the end offset of the token and
+// whether the closing quote was found.
+fn collect_string_constant(
+    chars: &mut Peekable<CharIndices>,
+    mut end: usize,
+) -> (String, usize, bool) {
     let mut string_constant = String::new();
-    while let Some(c) = chars.next() {
+    while let Some(&(offset, c)) = chars.peek() {
+        if c == '\n' {
+            return (string_constant, offset, false);
+        }
+        chars.next();
+        end = offset + c.len_utf8();
         if c == '"' {
-            break;
+            return (string_constant, end, true);
         }
         string_constant.push(c);
     }
-    string_constant
+    (string_constant, end, false)
 }
 
-fn collect_integer_constant(start: char, chars: &mut Peekable<impl Iterator<Item = char>>) -> u16 {
-    let mut num = start.to_digit(10).unwrap() as u16;
+fn collect_integer_constant(
+    start: usize,
+    first: char,
+    chars: &mut Peekable<CharIndices>,
+) -> (u32, usize) {
+    let mut num = first.to_digit(10).unwrap();
+    let mut end = start + 1;
 
     // Collect digits while they're available
-    while let Some(&c) = chars.peek() {
-        if c.is_digit(10) {
-            num = num * 10 + c.to_digit(10).unwrap() as u16;
+    while let Some(&(offset, c)) = chars.peek() {
+        if let Some(digit) = c.to_digit(10) {
+            num = num.saturating_mul(10).saturating_add(digit);
+            end = offset + 1;
             chars.next(); // Consume the digit
         } else {
             break;
         }
     }
 
-    num
+    (num, end)
 }
 
-fn remove_whitespace(input_data: String) -> String {
-    let no_multi_lines = remove_multi_line_comments(input_data);
-    let lines: Vec<String> = no_multi_lines
-        .split("\n")
-        .map(|s| s.trim().to_string())
-        .collect();
-    let mut whitespace_cleaned_lines: Vec<String> = vec![];
-
-    for line in lines {
-        if line.is_empty() || line.starts_with(COMMENT_BEGIN) {
-        } else if let Some(comment_index) = line.find(COMMENT_BEGIN) {
-            let trimmed = &line[..comment_index].trim();
-            whitespace_cleaned_lines.push(trimmed.to_string());
-        } else {
-            whitespace_cleaned_lines.push(line);
+fn skip_line_comment(chars: &mut Peekable<CharIndices>) {
+    for (_, c) in chars.by_ref() {
+        if c == '\n' {
+            break;
         }
     }
-    return whitespace_cleaned_lines.concat();
 }
 
-fn remove_multi_line_comments(input_data: String) -> String {
-    let mut result = String::new();
-    let mut in_comment = false;
-    let mut chars = input_data.chars().peekable();
-
-    while let Some(c) = chars.next() {
-        if in_comment {
-            if c == '*' && chars.peek() == Some(&'/') {
-                in_comment = false;
-                chars.next(); // Consume '/'
-            }
-        } else {
-            if c == '/' && chars.peek() == Some(&'*') {
-                in_comment = true;
-                chars.next(); // Consume '*'
-            } else {
-                result.push(c); // Not in comment, so add the character to the result
-            }
+// Returns false if the input ended before the closing "*/".
+fn skip_multi_line_comment(chars: &mut Peekable<CharIndices>) -> bool {
+    while let Some((_, c)) = chars.next() {
+        if c == '*' && matches!(chars.peek(), Some((_, '/'))) {
+            chars.next(); // Consume '/'
+            return true;
         }
     }
-    return result;
+    false
 }
 
-#[allow(dead_code)]
 pub fn format_tokens_for_display(tokens: Vec<Token>) -> String {
     let mut output: String = String::new();
     for token in tokens {
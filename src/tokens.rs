@@ -1,3 +1,5 @@
+use crate::span::Span;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenType {
     Keyword(Keyword),
@@ -30,11 +32,13 @@ impl std::fmt::Display for TokenType {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token {
     pub token: TokenType,
+    // Where the token appears in the source
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(tt: TokenType) -> Self {
-        Self { token: tt }
+    pub fn new(tt: TokenType, span: Span) -> Self {
+        Self { token: tt, span }
     }
 }
 
@@ -75,7 +79,7 @@ pub enum Keyword {
 
 impl Keyword {
     pub fn new(s: &str) -> Option<Keyword> {
-        let result = match s {
+        match s {
             "class" => Some(Keyword::Class),
             "constructor" => Some(Keyword::Constructor),
             "function" => Some(Keyword::Function),
@@ -103,8 +107,7 @@ impl Keyword {
             "return" => Some(Keyword::Return),
 
             _ => None,
-        };
-        return result;
+        }
     }
 }
 
@@ -171,7 +174,7 @@ pub enum Symbol {
 
 impl Symbol {
     pub fn new(s: char) -> Option<Symbol> {
-        let result = match s {
+        match s {
             '(' => Some(Symbol::BracketLeft),
             ')' => Some(Symbol::BracketRight),
 
@@ -200,8 +203,7 @@ impl Symbol {
             '#' => Some(Symbol::ShiftRight),
 
             _ => None,
-        };
-        return result;
+        }
     }
 }
 
@@ -0,0 +1,263 @@
+// A reader for the subset of TOML used by project files: [section] headers
+// and key = value pairs whose values are strings, integers, booleans or
+// arrays of those. Keys keep the order they appear in.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Array(values) => {
+                let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    // Empty for keys before the first section header
+    pub section: String,
+    pub key: String,
+    pub value: Value,
+    // The line the key is on, counted from 1
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub entries: Vec<Entry>,
+}
+
+impl Document {
+    pub fn parse(text: &str) -> Result<Document, String> {
+        let mut entries: Vec<Entry> = vec![];
+        let mut section = String::new();
+        let mut lines = text.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let line_number = index + 1;
+            let error = |message: &str| format!("line {}: {}", line_number, message);
+            let mut line = strip_comment(line).trim().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                section = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| error("expected ] at the end of the section header"))?
+                    .trim()
+                    .to_string();
+                continue;
+            }
+
+            let (key, _) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected key = value"))?;
+            let key = key.trim().trim_matches('"').to_string();
+            if key.is_empty() {
+                return Err(error("missing key"));
+            }
+            // Arrays may continue over several lines
+            while line.matches('[').count() > line.matches(']').count() {
+                match lines.next() {
+                    Some((_, next)) => {
+                        line.push(' ');
+                        line.push_str(strip_comment(next).trim());
+                    }
+                    None => return Err(error("unterminated array")),
+                }
+            }
+            let (_, value) = line.split_once('=').unwrap();
+            let mut chars = value.trim().chars().peekable();
+            let value = parse_value(&mut chars).map_err(|e| error(&e))?;
+            if chars.any(|c| !c.is_whitespace()) {
+                return Err(error("unexpected text after the value"));
+            }
+            if entries
+                .iter()
+                .any(|entry| entry.section == section && entry.key == key)
+            {
+                return Err(error(&format!("duplicate key {}", key)));
+            }
+            entries.push(Entry {
+                section: section.clone(),
+                key,
+                value,
+                line: line_number,
+            });
+        }
+        Ok(Document { entries })
+    }
+
+    pub fn section<'a>(&'a self, section: &'a str) -> impl Iterator<Item = &'a Entry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.section == section)
+    }
+}
+
+// Removes a "#" comment, unless the "#" is inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Value, String> {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    match chars.next() {
+        Some('"') => {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Value::String(value)),
+                    Some('\\') => match chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some(c @ ('"' | '\\')) => value.push(c),
+                        _ => return Err("invalid escape in string".to_string()),
+                    },
+                    Some(c) => value.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+        }
+        Some('\'') => {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('\'') => return Ok(Value::String(value)),
+                    Some(c) => value.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+        }
+        Some('[') => {
+            let mut values = vec![];
+            loop {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    return Ok(Value::Array(values));
+                }
+                values.push(parse_value(chars)?);
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Value::Array(values)),
+                    _ => return Err("expected , or ] in array".to_string()),
+                }
+            }
+        }
+        Some(first) => {
+            let mut word = first.to_string();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || matches!(c, '_' | '+' | '-') {
+                    word.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            match word.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                _ => word
+                    .replace('_', "")
+                    .parse()
+                    .map(Value::Integer)
+                    .map_err(|_| format!("invalid value {}", word)),
+            }
+        }
+        None => Err("missing value".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get<'a>(document: &'a Document, section: &'a str, key: &str) -> Option<&'a Entry> {
+        document.section(section).find(|entry| entry.key == key)
+    }
+
+    #[test]
+    fn test_parse() {
+        let document = Document::parse(
+            "# Project settings
+name = \"Pong # 2\"
+[lint]
+unused-local = 'off'  # quiet
+magic-number = \"error\"
+[build]
+jobs = 4
+force = false
+sources = [
+  \"src\",  # main
+  \"lib\",
+]
+",
+        )
+        .unwrap();
+        assert_eq!(
+            get(&document, "", "name").unwrap().value,
+            Value::String("Pong # 2".to_string())
+        );
+        let lint: Vec<(&str, Option<&str>)> = document
+            .section("lint")
+            .map(|entry| (entry.key.as_str(), entry.value.as_str()))
+            .collect();
+        assert_eq!(
+            lint,
+            [("unused-local", Some("off")), ("magic-number", Some("error"))]
+        );
+        assert_eq!(get(&document, "build", "jobs").unwrap().value, Value::Integer(4));
+        assert_eq!(
+            get(&document, "build", "sources").unwrap().value.to_string(),
+            "[\"src\", \"lib\"]"
+        );
+        assert_eq!(get(&document, "build", "sources").unwrap().line, 9);
+        assert_eq!(
+            Document::parse("[lint]\nx = \nn").unwrap_err(),
+            "line 2: missing value"
+        );
+    }
+}
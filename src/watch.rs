@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Polling for changed files without help from the operating system: a snapshot
+// records when each file was last modified, and comparing two snapshots gives
+// the files that were added or modified and those that were removed.
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot(BTreeMap<PathBuf, SystemTime>);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    // Added or modified, in path order
+    pub modified: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Snapshot {
+    // The modification times of the files, leaving out any that cannot be read,
+    // such as one removed since the paths were listed
+    pub fn take(paths: &[PathBuf]) -> Snapshot {
+        Snapshot(
+            paths
+                .iter()
+                .filter_map(|path| {
+                    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+                    Some((path.clone(), modified))
+                })
+                .collect(),
+        )
+    }
+
+    // What changed from this snapshot to the newer one
+    pub fn changes(&self, newer: &Snapshot) -> Changes {
+        Changes {
+            modified: newer
+                .0
+                .iter()
+                .filter(|(path, modified)| self.0.get(*path) != Some(modified))
+                .map(|(path, _)| path.clone())
+                .collect(),
+            removed: self.0.keys().filter(|path| !newer.0.contains_key(*path)).cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_changes() {
+        let time = |seconds| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+        let snapshot = |files: &[(&str, u64)]| {
+            Snapshot(files.iter().map(|(path, seconds)| (PathBuf::from(path), time(*seconds))).collect())
+        };
+        let old = snapshot(&[("A.jack", 1), ("B.jack", 1), ("C.jack", 1)]);
+        let new = snapshot(&[("A.jack", 1), ("B.jack", 2), ("D.jack", 1)]);
+        assert_eq!(
+            old.changes(&new),
+            Changes {
+                modified: vec![PathBuf::from("B.jack"), PathBuf::from("D.jack")],
+                removed: vec![PathBuf::from("C.jack")],
+            }
+        );
+        assert!(new.changes(&new).is_empty());
+        assert_eq!(Snapshot::default().changes(&old).modified.len(), 3);
+
+        let path = std::env::temp_dir().join(format!("jack-watch-test-{}.jack", std::process::id()));
+        fs::write(&path, "class A {}").unwrap();
+        let taken = Snapshot::take(&[path.clone(), path.with_extension("missing")]);
+        assert_eq!(taken.0.keys().collect::<Vec<_>>(), vec![&path]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Runs the analyzer over every project under tests/fixtures and compares the
+// parse tree (<name>.xml) and tokens (<name>T.xml) it writes for each .jack
+// file with the expected files next to it. Like the course's TextComparer,
+// the comparison ignores all whitespace.
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const CONTEXT_LINES: usize = 3;
+
+#[test]
+fn test_fixtures_match_expected_output() {
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(FIXTURES)
+        .expect("Failed to read the fixtures directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "No fixtures under {}", FIXTURES);
+
+    let mut failures = vec![];
+    let mut compared = 0;
+    for fixture in &fixtures {
+        let name = fixture.file_name().unwrap().to_string_lossy();
+        let output = std::env::temp_dir().join(format!("jack-conformance-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&output).unwrap();
+        let mut sources: Vec<PathBuf> = fs::read_dir(fixture)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "jack"))
+            .collect();
+        sources.sort();
+        for source in &sources {
+            fs::copy(source, output.join(source.file_name().unwrap())).unwrap();
+        }
+
+        run(&[output.to_str().unwrap()]);
+        run(&["tokens", output.to_str().unwrap()]);
+        for source in &sources {
+            let stem = source.file_stem().unwrap().to_string_lossy();
+            for file_name in [format!("{}.xml", stem), format!("{}T.xml", stem)] {
+                compared += 1;
+                if let Err(difference) = compare(&fixture.join(&file_name), &output.join(&file_name)) {
+                    failures.push(format!("{}/{}: {}", name, file_name, difference));
+                }
+            }
+        }
+        fs::remove_dir_all(&output).unwrap();
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} files differ from the expected output:\n\n{}",
+        failures.len(),
+        compared,
+        failures.join("\n")
+    );
+}
+
+fn run(args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_JackAnalyzer"))
+        .args(args)
+        .output()
+        .expect("Failed to run JackAnalyzer");
+    assert!(
+        output.status.success(),
+        "JackAnalyzer {} failed:\n{}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+// The lines of the file with all whitespace removed, skipping blank lines,
+// each with its line number counted from 1
+fn comparable_lines(path: &Path) -> Result<Vec<(usize, String)>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    Ok(text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.split_whitespace().collect::<String>()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect())
+}
+
+// Describes the first difference between the files, with the lines around it.
+fn compare(expected: &Path, actual: &Path) -> Result<(), String> {
+    let expected_lines = comparable_lines(expected)?;
+    let actual_lines = comparable_lines(actual)?;
+    let first_difference = (0..expected_lines.len().max(actual_lines.len()))
+        .find(|&i| expected_lines.get(i).map(|(_, line)| line) != actual_lines.get(i).map(|(_, line)| line));
+    let Some(i) = first_difference else {
+        return Ok(());
+    };
+    let excerpt = |lines: &[(usize, String)], marker: &str| -> String {
+        let start = i.saturating_sub(CONTEXT_LINES);
+        let end = (i + CONTEXT_LINES + 1).min(lines.len());
+        let mut excerpt: String = lines[start.min(end)..end]
+            .iter()
+            .enumerate()
+            .map(|(offset, (number, line))| {
+                let prefix = if start + offset == i { marker } else { " " };
+                format!("  {} {:>5} | {}\n", prefix, number, line)
+            })
+            .collect();
+        if i >= lines.len() {
+            excerpt.push_str(&format!("  {}       | <end of file>\n", marker));
+        }
+        excerpt
+    };
+    Err(format!(
+        "first difference at comparable line {}\n  expected ({}):\n{}  actual:\n{}",
+        i + 1,
+        expected.display(),
+        excerpt(&expected_lines, "-"),
+        excerpt(&actual_lines, "+")
+    ))
+}